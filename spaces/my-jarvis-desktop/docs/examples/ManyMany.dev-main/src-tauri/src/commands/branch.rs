@@ -0,0 +1,51 @@
+use std::path::Path;
+
+use crate::error::AppError;
+use crate::git::backend;
+use crate::git::branch::{self, BranchComparison, BranchInfo, DeletedBranch};
+use crate::process;
+
+/// Local and remote branches with last commit, upstream tracking and worktree checkout
+#[tauri::command]
+pub async fn list_branches(project_path: String) -> Result<Vec<BranchInfo>, AppError> {
+    process::blocking(move || backend::current().branches(Path::new(&project_path)))
+        .await
+        .map_err(|e| e.context("Failed to list branches"))
+}
+
+/// Create a branch from any ref (defaults to `HEAD`)
+#[tauri::command]
+pub async fn create_branch(
+    project_path: String,
+    name: String,
+    start_point: Option<String>,
+) -> Result<BranchInfo, AppError> {
+    branch::create_branch(Path::new(&project_path), &name, start_point.as_deref())
+}
+
+#[tauri::command]
+pub async fn rename_branch(project_path: String, old_name: String, new_name: String) -> Result<BranchInfo, AppError> {
+    branch::rename_branch(Path::new(&project_path), &old_name, &new_name)
+}
+
+/// Delete a branch; unmerged branches need `force`
+#[tauri::command]
+pub async fn delete_branch(project_path: String, name: String, force: bool) -> Result<DeletedBranch, AppError> {
+    branch::delete_branch(Path::new(&project_path), &name, force)
+}
+
+/// Track an upstream branch, or stop tracking when `upstream` is `None`
+#[tauri::command]
+pub async fn set_branch_upstream(
+    project_path: String,
+    name: String,
+    upstream: Option<String>,
+) -> Result<BranchInfo, AppError> {
+    branch::set_upstream(Path::new(&project_path), &name, upstream.as_deref())
+}
+
+/// Ahead/behind counts and merge state relative to the default branch
+#[tauri::command]
+pub async fn compare_branch_to_default(project_path: String, name: String) -> Result<BranchComparison, AppError> {
+    branch::compare_to_default(Path::new(&project_path), &name)
+}
@@ -0,0 +1,188 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::error::AppError;
+use crate::terminal::{EnvironmentInfo, TerminalManager};
+use crate::terminal::environment::EnvironmentChange;
+use crate::terminal::profile::{EnvProfile, ProfileStore};
+use crate::terminal::toolchain::{toolchain_report, ToolchainReport};
+use crate::secrets::{SecretInfo, SecretValue, SecretVault};
+use crate::terminal::worktree_env::{build_terminal_env, EnvTrustStore, LayeredEnv};
+
+/// Re-detect the shell environment and swap it in for terminals created from now on.
+/// Emits `environment-changed` with the names of what changed.
+#[tauri::command]
+pub async fn refresh_environment(app: AppHandle) -> Result<EnvironmentChange, AppError> {
+    update_environment(&app, true).await
+}
+
+/// Detect the environment off the async runtime and install it in the terminal manager.
+/// Without `force` a still-valid on-disk cache is reused.
+pub async fn update_environment(app: &AppHandle, force: bool) -> Result<EnvironmentChange, AppError> {
+    let detected = tokio::task::spawn_blocking(move || {
+        if force {
+            EnvironmentInfo::detect_and_cache()
+        } else {
+            EnvironmentInfo::detect_cached()
+        }
+    })
+    .await
+    .map_err(|e| AppError::other(format!("Environment detection failed: {}", e)))?;
+
+    let state = app.state::<Mutex<TerminalManager>>();
+    let change = {
+        let mut manager = state.lock().unwrap();
+        let change = manager.env_info().diff(&detected);
+        if !change.is_empty() {
+            manager.set_env_info(Arc::new(detected));
+        }
+        change
+    };
+
+    if !change.is_empty() {
+        let _ = app.emit("environment-changed", &change);
+    }
+
+    Ok(change)
+}
+
+/// Show the environment a new terminal in this worktree would get, with each variable's origin.
+/// `working_directory` is only needed for worktrees that haven't had a terminal opened yet,
+/// and `profile` previews a profile other than the worktree's default. Secret values are `null`.
+#[tauri::command]
+pub async fn get_effective_env(
+    worktree_id: String,
+    working_directory: Option<String>,
+    profile: Option<String>,
+    state: State<'_, Mutex<TerminalManager>>,
+) -> Result<LayeredEnv, AppError> {
+    let (env_info, known_path) = {
+        let manager = state.lock().unwrap();
+        (manager.env_info(), manager.worktree_path(&worktree_id))
+    };
+
+    let worktree_path = working_directory
+        .or(known_path)
+        .ok_or_else(|| AppError::invalid(format!("Unknown worktree: {}", worktree_id)))?;
+
+    let worktree_path = PathBuf::from(worktree_path);
+    if !worktree_path.exists() {
+        return Err(AppError::path_not_found(worktree_path.display()));
+    }
+
+    tokio::task::spawn_blocking(move || {
+        build_terminal_env(&env_info, &worktree_path, &worktree_id, profile.as_deref(), &HashMap::new())
+    })
+    .await
+    .map_err(|e| AppError::other(format!("Failed to load worktree environment: {}", e)))?
+}
+
+/// List saved environment profiles
+#[tauri::command]
+pub async fn list_env_profiles() -> Result<Vec<EnvProfile>, AppError> {
+    Ok(ProfileStore::load()?.profiles().to_vec())
+}
+
+/// Create or update a profile. Profiles without an id are created with a new one.
+#[tauri::command]
+pub async fn save_env_profile(profile: EnvProfile) -> Result<EnvProfile, AppError> {
+    let mut store = ProfileStore::load()?;
+    let saved = store.upsert(profile)?;
+    store.save()?;
+    Ok(saved)
+}
+
+/// Delete a profile and unassign it from any worktree
+#[tauri::command]
+pub async fn delete_env_profile(profile_id: String) -> Result<(), AppError> {
+    let mut store = ProfileStore::load()?;
+    store.remove(&profile_id)?;
+    store.save()?;
+    Ok(())
+}
+
+/// Choose the profile new terminals in a worktree use by default (`None` clears it)
+#[tauri::command]
+pub async fn set_worktree_profile(worktree_id: String, profile_id: Option<String>) -> Result<(), AppError> {
+    let mut store = ProfileStore::load()?;
+    store.assign(&worktree_id, profile_id.as_deref())?;
+    store.save()?;
+    Ok(())
+}
+
+/// Profile new terminals in a worktree use by default
+#[tauri::command]
+pub async fn get_worktree_profile(worktree_id: String) -> Result<Option<EnvProfile>, AppError> {
+    Ok(ProfileStore::load()?.worktree_profile(&worktree_id).cloned())
+}
+
+/// Names and descriptions of stored secrets; values are never returned
+#[tauri::command]
+pub async fn list_secrets() -> Result<Vec<SecretInfo>, AppError> {
+    tokio::task::spawn_blocking(|| SecretVault::open()?.list())
+        .await
+        .map_err(|e| AppError::other(format!("Failed to list secrets: {}", e)))?
+}
+
+/// Store or replace a secret in the OS keychain
+#[tauri::command]
+pub async fn set_secret(name: String, value: String, description: Option<String>) -> Result<SecretInfo, AppError> {
+    let value = SecretValue::new(value);
+    tokio::task::spawn_blocking(move || SecretVault::open()?.set(&name, value, description))
+        .await
+        .map_err(|e| AppError::other(format!("Failed to store secret: {}", e)))?
+}
+
+#[tauri::command]
+pub async fn delete_secret(name: String) -> Result<(), AppError> {
+    tokio::task::spawn_blocking(move || SecretVault::open()?.delete(&name))
+        .await
+        .map_err(|e| AppError::other(format!("Failed to delete secret: {}", e)))?
+}
+
+/// Compare toolchain versions pinned in the worktree with what's installed
+#[tauri::command]
+pub async fn get_toolchain_report(
+    worktree_path: String,
+    state: State<'_, Mutex<TerminalManager>>,
+) -> Result<ToolchainReport, AppError> {
+    let worktree_path = PathBuf::from(worktree_path);
+    if !worktree_path.exists() {
+        return Err(AppError::path_not_found(worktree_path.display()));
+    }
+
+    // Reads pin files and install directories; keep that off the async runtime
+    let env_info = state.lock().unwrap().env_info();
+    tokio::task::spawn_blocking(move || toolchain_report(&env_info, &worktree_path))
+        .await
+        .map_err(|e| AppError::other(format!("Toolchain check failed: {}", e)))
+}
+
+/// Allow a `.env`/`.envrc` file with its current contents to be loaded into terminals
+#[tauri::command]
+pub async fn trust_env_file(path: String) -> Result<(), AppError> {
+    let mut store = EnvTrustStore::load()?;
+    store.decide(&PathBuf::from(path), true)?;
+    store.save()?;
+    Ok(())
+}
+
+/// Refuse a `.env`/`.envrc` file until it is trusted explicitly
+#[tauri::command]
+pub async fn deny_env_file(path: String) -> Result<(), AppError> {
+    let mut store = EnvTrustStore::load()?;
+    store.decide(&PathBuf::from(path), false)?;
+    store.save()?;
+    Ok(())
+}
+
+/// Drop any decision about a file so the user is prompted again
+#[tauri::command]
+pub async fn forget_env_file(path: String) -> Result<(), AppError> {
+    let mut store = EnvTrustStore::load()?;
+    store.forget(&PathBuf::from(path));
+    store.save()?;
+    Ok(())
+}
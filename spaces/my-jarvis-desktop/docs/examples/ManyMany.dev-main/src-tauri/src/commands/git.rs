@@ -1,174 +1,325 @@
-use serde::{Deserialize, Serialize};
-use std::process::Command;
+use std::path::Path;
+use tauri::{AppHandle, Emitter, State};
+use tokio_util::sync::CancellationToken;
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct GitStatus {
-    pub branch: String,
-    pub ahead: u32,
-    pub behind: u32,
-    pub staged: Vec<GitFile>,
-    pub unstaged: Vec<GitFile>,
-    pub untracked: Vec<GitFile>,
+use crate::error::AppError;
+use crate::git::{self, backend, snapshot, stage};
+use crate::git::backend::BackendKind;
+use crate::git::blame::{blame, Blame};
+use crate::git::commit::{commit, CommitRequest, CommitResult};
+use crate::git::compare::{self, WorktreeComparison};
+use crate::git::conflict::{self, ConflictDetails, Resolution};
+use crate::git::diff::{file_diff, FileDiff, WorktreeDiff, DEFAULT_CONTEXT_LINES};
+use crate::git::log::{commit_details, CommitDetails, CommitLog, LogQuery};
+use crate::git::operation::{self, MergeRequest, OperationResult, RebaseRequest};
+use crate::git::remote::GitOperations;
+use crate::git::revision::{file_at_revision, FileAtRevision};
+use crate::git::snapshot::Snapshot;
+use crate::git::stage::DiffSelection;
+use crate::git::transfer::{self, TransferMode, TransferResult};
+use crate::git::status::{DefaultBranchDivergence, GitStatus};
+use crate::git::watch::{GitStatusChanged, StatusWatchers};
+use crate::process::{self, RunOptions};
+
+/// Branch, upstream tracking state, ahead/behind and changed files
+#[tauri::command]
+pub async fn get_git_status(worktree_path: String) -> Result<GitStatus, AppError> {
+    process::blocking(move || backend::current().status(Path::new(&worktree_path))).await
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct GitFile {
-    pub path: String,
-    pub status: String,
+/// How far a worktree has diverged from the project's default branch; on demand, not polled
+#[tauri::command]
+pub async fn get_default_branch_divergence(worktree_path: String) -> Result<DefaultBranchDivergence, AppError> {
+    process::blocking(move || backend::current().default_branch_divergence(Path::new(&worktree_path))).await
 }
 
+/// Which implementation status, branches, worktrees, diffs and history are read with
 #[tauri::command]
-pub async fn get_git_status(worktree_path: String) -> Result<GitStatus, String> {
-    // Get current branch
-    let branch_output = Command::new("git")
-        .args(&["-C", &worktree_path, "branch", "--show-current"])
-        .output()
-        .map_err(|e| format!("Failed to get branch: {}", e))?;
-    
-    let branch = if branch_output.status.success() {
-        String::from_utf8_lossy(&branch_output.stdout).trim().to_string()
-    } else {
-        "unknown".to_string()
+pub async fn get_git_backend() -> Result<BackendKind, AppError> {
+    Ok(backend::current().kind())
+}
+
+#[tauri::command]
+pub async fn set_git_backend(backend: BackendKind) -> Result<(), AppError> {
+    backend::select(backend);
+    Ok(())
+}
+
+/// Keep the worktree's status current: changes are emitted as `git-status-changed` events.
+/// Returns the status at the time the watch starts.
+#[tauri::command]
+pub async fn watch_worktree(
+    app: AppHandle,
+    watchers: State<'_, StatusWatchers>,
+    worktree_path: String,
+) -> Result<GitStatus, AppError> {
+    let event_path = worktree_path.clone();
+    watchers.watch(Path::new(&worktree_path), move |status| {
+        let _ = app.emit("git-status-changed", GitStatusChanged {
+            worktree_path: event_path.clone(),
+            status,
+        });
+    })
+    .await
+}
+
+/// Stop watching a worktree; false if it wasn't watched
+#[tauri::command]
+pub async fn unwatch_worktree(watchers: State<'_, StatusWatchers>, worktree_path: String) -> Result<bool, AppError> {
+    Ok(watchers.unwatch(Path::new(&worktree_path)))
+}
+
+/// Structured diff of one file; `None` when it has no changes on that side
+#[tauri::command]
+pub async fn get_file_diff(
+    worktree_path: String,
+    path: String,
+    staged: bool,
+    context_lines: Option<u32>,
+) -> Result<Option<FileDiff>, AppError> {
+    file_diff(Path::new(&worktree_path), &path, staged, context_lines.unwrap_or(DEFAULT_CONTEXT_LINES))
+        .map_err(|e| e.context("Failed to get diff"))
+}
+
+/// Staged and unstaged diffs of every changed file. Cancellable by `operation_id`.
+#[tauri::command]
+pub async fn get_worktree_diff(
+    operations: State<'_, GitOperations>,
+    worktree_path: String,
+    context_lines: Option<u32>,
+    operation_id: Option<String>,
+) -> Result<WorktreeDiff, AppError> {
+    let context_lines = context_lines.unwrap_or(DEFAULT_CONTEXT_LINES);
+    cancellable(&operations, operation_id, move |cancel| {
+        backend::current().diff(Path::new(&worktree_path), context_lines, cancel)
+    })
+    .await
+    .map_err(|e| e.context("Failed to get diff"))
+}
+
+/// A page of history starting at `reference` (default `HEAD`), newest first. Cancellable by `operation_id`.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn get_commit_log(
+    operations: State<'_, GitOperations>,
+    worktree_path: String,
+    reference: Option<String>,
+    path_filter: Option<String>,
+    author: Option<String>,
+    since: Option<String>,
+    skip: Option<u32>,
+    limit: Option<u32>,
+    operation_id: Option<String>,
+) -> Result<CommitLog, AppError> {
+    let query = LogQuery {
+        reference,
+        path_filter,
+        author,
+        since,
+        skip: skip.unwrap_or(0),
+        limit,
     };
-    
-    // Get status
-    let status_output = Command::new("git")
-        .args(&["-C", &worktree_path, "status", "--porcelain=v1"])
-        .output()
-        .map_err(|e| format!("Failed to get status: {}", e))?;
-    
-    let mut staged = Vec::new();
-    let mut unstaged = Vec::new();
-    let mut untracked = Vec::new();
-    
-    if status_output.status.success() {
-        let status_str = String::from_utf8_lossy(&status_output.stdout);
-        
-        for line in status_str.lines() {
-            if line.len() < 3 {
-                continue;
-            }
-            
-            let index_status = &line[0..1];
-            let worktree_status = &line[1..2];
-            let file_path = line[3..].trim();
-            
-            if index_status != " " && index_status != "?" {
-                staged.push(GitFile {
-                    path: file_path.to_string(),
-                    status: get_status_type(index_status),
-                });
-            }
-            
-            if worktree_status != " " && worktree_status != "?" {
-                unstaged.push(GitFile {
-                    path: file_path.to_string(),
-                    status: get_status_type(worktree_status),
-                });
-            }
-            
-            if index_status == "?" && worktree_status == "?" {
-                untracked.push(GitFile {
-                    path: file_path.to_string(),
-                    status: "untracked".to_string(),
-                });
-            }
-        }
-    }
-    
-    // Get ahead/behind count
-    let (ahead, behind) = get_ahead_behind(&worktree_path, &branch);
-    
-    Ok(GitStatus {
-        branch,
-        ahead,
-        behind,
-        staged,
-        unstaged,
-        untracked,
+    cancellable(&operations, operation_id, move |cancel| {
+        backend::current().log(Path::new(&worktree_path), &query, cancel)
     })
+    .await
+    .map_err(|e| e.context("Failed to read history"))
 }
 
 #[tauri::command]
-pub async fn git_commit(worktree_path: String, message: String) -> Result<(), String> {
-    let output = Command::new("git")
-        .args(&["-C", &worktree_path, "commit", "-m", &message])
-        .output()
-        .map_err(|e| format!("Failed to commit: {}", e))?;
-    
-    if !output.status.success() {
-        let error = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("Commit failed: {}", error));
-    }
-    
-    Ok(())
+pub async fn get_commit_details(worktree_path: String, sha: String) -> Result<CommitDetails, AppError> {
+    commit_details(Path::new(&worktree_path), &sha)
+        .map_err(|e| e.context("Failed to read commit"))
+}
+
+/// Who last changed each line, as ranges with commit details. Without `rev` the worktree file is blamed.
+/// Cancellable by `operation_id`.
+#[tauri::command]
+pub async fn get_blame(
+    operations: State<'_, GitOperations>,
+    worktree_path: String,
+    path: String,
+    rev: Option<String>,
+    operation_id: Option<String>,
+) -> Result<Blame, AppError> {
+    let context = format!("Failed to blame {}", path);
+    cancellable(&operations, operation_id, move |cancel| {
+        blame(Path::new(&worktree_path), &path, rev.as_deref(), cancel)
+    })
+    .await
+    .map_err(|e| e.context(context))
+}
+
+/// A file's contents at a revision; binary and oversized files come without contents
+#[tauri::command]
+pub async fn get_file_at_revision(worktree_path: String, path: String, rev: String) -> Result<FileAtRevision, AppError> {
+    file_at_revision(Path::new(&worktree_path), &path, &rev)
+}
+
+/// Stage selected hunks or lines of a file. Returns the safety snapshot taken first.
+#[tauri::command]
+pub async fn stage_hunks(worktree_path: String, path: String, selections: Vec<DiffSelection>) -> Result<Snapshot, AppError> {
+    stage::stage_hunks(Path::new(&worktree_path), &path, &selections)
+}
+
+#[tauri::command]
+pub async fn unstage_hunks(worktree_path: String, path: String, selections: Vec<DiffSelection>) -> Result<Snapshot, AppError> {
+    stage::unstage_hunks(Path::new(&worktree_path), &path, &selections)
+}
+
+/// Throw away selected unstaged hunks or lines of a file
+#[tauri::command]
+pub async fn discard_hunks(worktree_path: String, path: String, selections: Vec<DiffSelection>) -> Result<Snapshot, AppError> {
+    stage::discard_hunks(Path::new(&worktree_path), &path, &selections)
+}
+
+/// Restore files to their staged version
+#[tauri::command]
+pub async fn discard_file_changes(worktree_path: String, paths: Vec<String>) -> Result<Snapshot, AppError> {
+    stage::discard_file_changes(Path::new(&worktree_path), &paths)
+}
+
+#[tauri::command]
+pub async fn delete_untracked(worktree_path: String, paths: Vec<String>) -> Result<Snapshot, AppError> {
+    stage::delete_untracked(Path::new(&worktree_path), &paths)
+}
+
+/// Safety snapshots of the repository, newest first
+#[tauri::command]
+pub async fn list_snapshots(worktree_path: String) -> Result<Vec<Snapshot>, AppError> {
+    snapshot::list_snapshots(Path::new(&worktree_path))
+}
+
+/// Put the files a snapshot covers back the way they were
+#[tauri::command]
+pub async fn restore_snapshot(worktree_path: String, snapshot_id: String) -> Result<Snapshot, AppError> {
+    snapshot::restore_snapshot(Path::new(&worktree_path), &snapshot_id)
+}
+
+/// Commit staged changes (or only `request.paths`). Returns the new commit.
+#[tauri::command]
+pub async fn git_commit(worktree_path: String, request: CommitRequest) -> Result<CommitResult, AppError> {
+    commit(Path::new(&worktree_path), &request)
+}
+
+/// Copy or move uncommitted changes (untracked and binary files included) to another worktree
+#[tauri::command]
+pub async fn transfer_changes(
+    from_worktree: String,
+    to_worktree: String,
+    paths: Option<Vec<String>>,
+    mode: TransferMode,
+) -> Result<TransferResult, AppError> {
+    transfer::transfer_changes(Path::new(&from_worktree), Path::new(&to_worktree), paths.as_deref(), mode)
 }
 
+/// File summary, per-file diffs, branch divergence and a similarity score for two worktrees.
+/// Cancellable by `operation_id`.
 #[tauri::command]
-pub async fn git_stage_file(worktree_path: String, file_path: String) -> Result<(), String> {
-    let output = Command::new("git")
-        .args(&["-C", &worktree_path, "add", &file_path])
-        .output()
-        .map_err(|e| format!("Failed to stage file: {}", e))?;
+pub async fn compare_worktrees(
+    operations: State<'_, GitOperations>,
+    worktree_a: String,
+    worktree_b: String,
+    context_lines: Option<u32>,
+    operation_id: Option<String>,
+) -> Result<WorktreeComparison, AppError> {
+    let context_lines = context_lines.unwrap_or(DEFAULT_CONTEXT_LINES);
+    cancellable(&operations, operation_id, move |cancel| {
+        compare::compare_worktrees(Path::new(&worktree_a), Path::new(&worktree_b), context_lines, cancel)
+    })
+    .await
+    .map_err(|e| e.context("Failed to compare worktrees"))
+}
+
+/// Merge a branch into the current one. Stopping on conflicts is reported in the result, not as an error.
+#[tauri::command]
+pub async fn git_merge(worktree_path: String, request: MergeRequest) -> Result<OperationResult, AppError> {
+    operation::merge(Path::new(&worktree_path), &request)
+}
+
+#[tauri::command]
+pub async fn git_rebase(worktree_path: String, request: RebaseRequest) -> Result<OperationResult, AppError> {
+    operation::rebase(Path::new(&worktree_path), &request)
+}
+
+#[tauri::command]
+pub async fn git_cherry_pick(worktree_path: String, commits: Vec<String>) -> Result<OperationResult, AppError> {
+    operation::cherry_pick(Path::new(&worktree_path), &commits)
+}
+
+/// Continue the merge, rebase, cherry-pick or revert in progress
+#[tauri::command]
+pub async fn git_continue_operation(worktree_path: String) -> Result<OperationResult, AppError> {
+    operation::continue_operation(Path::new(&worktree_path))
+}
+
+#[tauri::command]
+pub async fn git_skip_operation(worktree_path: String) -> Result<OperationResult, AppError> {
+    operation::skip_operation(Path::new(&worktree_path))
+}
+
+#[tauri::command]
+pub async fn git_abort_operation(worktree_path: String) -> Result<OperationResult, AppError> {
+    operation::abort_operation(Path::new(&worktree_path))
+}
+
+#[tauri::command]
+pub async fn git_stage_file(worktree_path: String, file_path: String) -> Result<(), AppError> {
+    let output = process::git(Path::new(&worktree_path), &["add", &file_path], RunOptions::default())
+        .await
+        .map_err(|e| e.context("Failed to stage file"))?;
     
     if !output.status.success() {
         let error = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("Failed to stage file: {}", error));
+        return Err(git::git_error(Path::new(&worktree_path), "add", &error, output.status.code())
+            .context("Failed to stage file"));
     }
     
     Ok(())
 }
 
 #[tauri::command]
-pub async fn git_unstage_file(worktree_path: String, file_path: String) -> Result<(), String> {
-    let output = Command::new("git")
-        .args(&["-C", &worktree_path, "reset", "HEAD", &file_path])
-        .output()
-        .map_err(|e| format!("Failed to unstage file: {}", e))?;
+pub async fn git_unstage_file(worktree_path: String, file_path: String) -> Result<(), AppError> {
+    let output = process::git(Path::new(&worktree_path), &["reset", "HEAD", &file_path], RunOptions::default())
+        .await
+        .map_err(|e| e.context("Failed to unstage file"))?;
     
     if !output.status.success() {
         let error = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("Failed to unstage file: {}", error));
+        return Err(git::git_error(Path::new(&worktree_path), "reset", &error, output.status.code())
+            .context("Failed to unstage file"));
     }
     
     Ok(())
 }
 
-fn get_status_type(status: &str) -> String {
-    match status {
-        "M" => "modified".to_string(),
-        "A" => "added".to_string(),
-        "D" => "deleted".to_string(),
-        "R" => "renamed".to_string(),
-        "C" => "copied".to_string(),
-        "U" => "unmerged".to_string(),
-        _ => "unknown".to_string(),
-    }
+/// Base, ours and theirs contents of a conflicted file, with its conflict regions
+#[tauri::command]
+pub async fn get_conflict(worktree_path: String, path: String) -> Result<ConflictDetails, AppError> {
+    conflict::get_conflict(Path::new(&worktree_path), &path)
+        .map_err(|e| e.context("Failed to read conflict"))
 }
 
-fn get_ahead_behind(path: &str, branch: &str) -> (u32, u32) {
-    let output = Command::new("git")
-        .args(&[
-            "-C",
-            path,
-            "rev-list",
-            "--left-right",
-            "--count",
-            &format!("{}...origin/{}", branch, branch),
-        ])
-        .output();
-    
-    if let Ok(output) = output {
-        if output.status.success() {
-            let result = String::from_utf8_lossy(&output.stdout);
-            let parts: Vec<&str> = result.trim().split('\t').collect();
-            if parts.len() == 2 {
-                let ahead = parts[0].parse().unwrap_or(0);
-                let behind = parts[1].parse().unwrap_or(0);
-                return (ahead, behind);
-            }
-        }
+/// Resolve a conflicted file and stage it. Returns the paths still in conflict.
+#[tauri::command]
+pub async fn resolve_conflict(worktree_path: String, path: String, resolution: Resolution) -> Result<Vec<String>, AppError> {
+    conflict::resolve_conflict(Path::new(&worktree_path), &path, &resolution)
+        .map_err(|e| e.context("Failed to resolve conflict"))
+}
+
+/// Run `work` on the blocking pool. With an `operation_id` it can be stopped through `cancel_git_operation`.
+async fn cancellable<T: Send + 'static>(
+    operations: &GitOperations,
+    operation_id: Option<String>,
+    work: impl FnOnce(&CancellationToken) -> Result<T, AppError> + Send + 'static,
+) -> Result<T, AppError> {
+    let cancel = match &operation_id {
+        Some(operation_id) => operations.register(operation_id),
+        None => CancellationToken::new(),
+    };
+    let result = process::blocking(move || work(&cancel)).await;
+    if let Some(operation_id) = &operation_id {
+        operations.finish(operation_id);
     }
-    
-    (0, 0)
-}
\ No newline at end of file
+    result
+}
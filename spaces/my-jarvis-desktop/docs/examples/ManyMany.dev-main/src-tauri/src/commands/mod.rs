@@ -1,4 +1,9 @@
 pub mod project;
 pub mod worktree;
 pub mod git;
-pub mod terminal;
\ No newline at end of file
+pub mod branch;
+pub mod remote;
+pub mod stash;
+pub mod tag;
+pub mod terminal;
+pub mod environment;
\ No newline at end of file
@@ -1,10 +1,14 @@
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::fs;
 use std::process::Command;
 use uuid::Uuid;
 use chrono::Utc;
 
+use crate::error::{AppError, ErrorKind};
+use crate::git;
+use crate::git::branch;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Project {
     pub id: String,
@@ -41,21 +45,24 @@ pub struct WorkspaceRepo {
 }
 
 #[tauri::command]
-pub async fn add_project(request: AddProjectRequest) -> Result<Project, String> {
+pub async fn add_project(request: AddProjectRequest) -> Result<Project, AppError> {
     let project_path = PathBuf::from(&request.path);
     
     if !project_path.exists() {
-        return Err("Project path does not exist".to_string());
+        return Err(AppError::path_not_found(project_path.display()));
     }
     
     // Validate project type
     if request.project_type == "repository" {
         if !project_path.join(".git").exists() {
-            return Err("Selected folder is not a Git repository".to_string());
+            return Err(AppError::new(
+                ErrorKind::NotAGitRepo { path: request.path.clone() },
+                "Selected folder is not a Git repository",
+            ));
         }
     } else if request.project_type == "workspace" {
         if !request.path.ends_with(".code-workspace") && !request.path.ends_with(".json") {
-            return Err("Selected file is not a valid workspace file".to_string());
+            return Err(AppError::other("Selected file is not a valid workspace file"));
         }
     }
     
@@ -73,65 +80,36 @@ pub async fn add_project(request: AddProjectRequest) -> Result<Project, String>
 }
 
 #[tauri::command]
-pub async fn list_projects() -> Result<Vec<Project>, String> {
+pub async fn list_projects() -> Result<Vec<Project>, AppError> {
     // TODO: Load from persistent storage
     Ok(vec![])
 }
 
 #[tauri::command]
-pub async fn remove_project(_id: String) -> Result<(), String> {
+pub async fn remove_project(_id: String) -> Result<(), AppError> {
     // TODO: Remove from persistent storage
     Ok(())
 }
 
 #[tauri::command]
-pub fn get_default_branch(path: String) -> Result<String, String> {
-    use std::process::Command;
-    
-    let output = Command::new("git")
-        .args(&["-C", &path, "symbolic-ref", "refs/remotes/origin/HEAD"])
-        .output()
-        .map_err(|e| format!("Failed to execute git command: {}", e))?;
-    
-    if output.status.success() {
-        let branch = String::from_utf8_lossy(&output.stdout);
-        let branch = branch.trim().replace("refs/remotes/origin/", "");
-        if !branch.is_empty() {
-            return Ok(branch);
-        }
-    }
-    
-    // Fallback to checking current branch
-    let output = Command::new("git")
-        .args(&["-C", &path, "branch", "--show-current"])
-        .output()
-        .map_err(|e| format!("Failed to execute git command: {}", e))?;
-    
-    if output.status.success() {
-        let branch = String::from_utf8_lossy(&output.stdout).trim().to_string();
-        if !branch.is_empty() {
-            return Ok(branch);
-        }
-    }
-    
-    // Final fallback to "main"
-    Ok("main".to_string())
+pub async fn get_default_branch(path: String) -> Result<String, AppError> {
+    branch::default_branch(Path::new(&path))
 }
 
 #[tauri::command]
-pub async fn parse_workspace_file(workspace_path: String) -> Result<Vec<WorkspaceRepo>, String> {
+pub async fn parse_workspace_file(workspace_path: String) -> Result<Vec<WorkspaceRepo>, AppError> {
     let workspace_path = PathBuf::from(&workspace_path);
     
     if !workspace_path.exists() {
-        return Err("Workspace file does not exist".to_string());
+        return Err(AppError::path_not_found(workspace_path.display()));
     }
     
     // Read and parse the workspace file
     let content = fs::read_to_string(&workspace_path)
-        .map_err(|e| format!("Failed to read workspace file: {}", e))?;
+        .map_err(|e| AppError::io("Failed to read workspace file", e))?;
     
     let workspace_data: serde_json::Value = serde_json::from_str(&content)
-        .map_err(|e| format!("Failed to parse workspace file: {}", e))?;
+        .map_err(|e| AppError::invalid(format!("Failed to parse workspace file: {}", e)))?;
     
     let mut repos = Vec::new();
     
@@ -173,6 +151,7 @@ pub async fn parse_workspace_file(workspace_path: String) -> Result<Vec<Workspac
                     // Get default branch if it's a Git repo
                     let default_branch = if is_git_repo {
                         get_default_branch(folder_path.to_string_lossy().to_string())
+                            .await
                             .unwrap_or_else(|_| "main".to_string())
                     } else {
                         "main".to_string()
@@ -193,7 +172,7 @@ pub async fn parse_workspace_file(workspace_path: String) -> Result<Vec<Workspac
 }
 
 #[tauri::command]
-pub async fn open_in_app(path: String, app: String) -> Result<(), String> {
+pub async fn open_in_app(path: String, app: String) -> Result<(), AppError> {
     let command_result = match app.as_str() {
         "cursor" => Command::new("cursor").arg(&path).spawn(),
         "vscode" => Command::new("code").arg(&path).spawn(),
@@ -211,7 +190,7 @@ pub async fn open_in_app(path: String, app: String) -> Result<(), String> {
                 Command::new("xdg-open").arg(&path).spawn()
             }
         },
-        _ => return Err(format!("Unsupported app: {}", app))
+        _ => return Err(AppError::invalid(format!("Unsupported app: {}", app)))
     };
         
     match command_result {
@@ -221,7 +200,7 @@ pub async fn open_in_app(path: String, app: String) -> Result<(), String> {
         },
         Err(e) => {
             eprintln!("Failed to open {} in {}: {}", path, app, e);
-            Err(format!("Failed to open in {}: {}", app, e))
+            Err(AppError::other(format!("Failed to open in {}: {}", app, e)))
         }
     }
 }
\ No newline at end of file
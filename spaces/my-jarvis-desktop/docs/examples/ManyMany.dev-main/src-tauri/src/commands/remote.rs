@@ -0,0 +1,96 @@
+use std::path::Path;
+use tauri::{AppHandle, Emitter, State};
+
+use crate::error::AppError;
+use crate::git::remote::{self, GitOperations, PullMode, PushOptions, RemoteResult};
+
+/// Fetch one remote, or all of them. Progress is emitted as `git-progress` events tagged with `operation_id`.
+#[tauri::command]
+pub async fn git_fetch(
+    app: AppHandle,
+    operations: State<'_, GitOperations>,
+    worktree_path: String,
+    operation_id: String,
+    remote: Option<String>,
+    prune: Option<bool>,
+) -> Result<RemoteResult, AppError> {
+    let cancel = operations.register(&operation_id);
+    let mut on_progress = progress_emitter(app, &operation_id);
+    let result = remote::fetch(
+        Path::new(&worktree_path),
+        remote.as_deref(),
+        prune.unwrap_or(false),
+        &mut on_progress,
+        cancel,
+    )
+    .await;
+    operations.finish(&operation_id);
+    Ok(result?)
+}
+
+/// Pull the current branch; `mode` overrides the user's pull config
+#[tauri::command]
+pub async fn git_pull(
+    app: AppHandle,
+    operations: State<'_, GitOperations>,
+    worktree_path: String,
+    operation_id: String,
+    mode: Option<PullMode>,
+) -> Result<RemoteResult, AppError> {
+    let cancel = operations.register(&operation_id);
+    let mut on_progress = progress_emitter(app, &operation_id);
+    let result = remote::pull(Path::new(&worktree_path), mode, &mut on_progress, cancel).await;
+    operations.finish(&operation_id);
+    Ok(result?)
+}
+
+#[tauri::command]
+pub async fn git_push(
+    app: AppHandle,
+    operations: State<'_, GitOperations>,
+    worktree_path: String,
+    operation_id: String,
+    options: PushOptions,
+) -> Result<RemoteResult, AppError> {
+    let cancel = operations.register(&operation_id);
+    let mut on_progress = progress_emitter(app, &operation_id);
+    let result = remote::push(Path::new(&worktree_path), &options, &mut on_progress, cancel).await;
+    operations.finish(&operation_id);
+    Ok(result?)
+}
+
+/// Push a tag to `remote` (defaults to `origin`), or delete it there
+#[tauri::command]
+pub async fn git_push_tag(
+    app: AppHandle,
+    operations: State<'_, GitOperations>,
+    worktree_path: String,
+    operation_id: String,
+    tag: String,
+    remote: Option<String>,
+    delete: Option<bool>,
+) -> Result<RemoteResult, AppError> {
+    let delete = delete.unwrap_or(false);
+    let cancel = operations.register(&operation_id);
+    let mut on_progress = progress_emitter(app, &operation_id);
+    let result = remote::push_tag(Path::new(&worktree_path), remote.as_deref(), &tag, delete, &mut on_progress, cancel).await;
+    operations.finish(&operation_id);
+    Ok(result?)
+}
+
+/// Stop a running fetch, pull or push, or a log, diff, blame or comparison started with an `operation_id`.
+/// Returns false if it already finished.
+#[tauri::command]
+pub async fn cancel_git_operation(
+    operations: State<'_, GitOperations>,
+    operation_id: String,
+) -> Result<bool, AppError> {
+    Ok(operations.cancel(&operation_id))
+}
+
+fn progress_emitter(app: AppHandle, operation_id: &str) -> impl FnMut(&str) + Send {
+    let operation_id = operation_id.to_string();
+    move |line| {
+        let _ = app.emit("git-progress", remote::parse_progress(&operation_id, line));
+    }
+}
@@ -0,0 +1,43 @@
+use std::path::Path;
+
+use crate::error::AppError;
+use crate::git::diff::{stash_diff, FileDiff, DEFAULT_CONTEXT_LINES};
+use crate::git::stash::{self, StashApplyResult, StashEntry, StashPushRequest};
+
+/// Stashes of the repository with the worktree and branch each was made in, newest first
+#[tauri::command]
+pub async fn list_stashes(worktree_path: String) -> Result<Vec<StashEntry>, AppError> {
+    stash::list_stashes(Path::new(&worktree_path))
+        .map_err(|e| e.context("Failed to list stashes"))
+}
+
+#[tauri::command]
+pub async fn push_stash(worktree_path: String, request: StashPushRequest) -> Result<StashEntry, AppError> {
+    stash::push_stash(Path::new(&worktree_path), &request)
+}
+
+/// Apply a stash (`stash@{n}` or sha) to `worktree_path`, whichever worktree it came from
+#[tauri::command]
+pub async fn apply_stash(worktree_path: String, stash: String, restore_index: Option<bool>) -> Result<StashApplyResult, AppError> {
+    stash::apply_stash(Path::new(&worktree_path), &stash, restore_index.unwrap_or(false))
+}
+
+/// Apply a stash and drop it; it's kept if applying conflicts
+#[tauri::command]
+pub async fn pop_stash(worktree_path: String, stash: String, restore_index: Option<bool>) -> Result<StashApplyResult, AppError> {
+    stash::pop_stash(Path::new(&worktree_path), &stash, restore_index.unwrap_or(false))
+}
+
+#[tauri::command]
+pub async fn drop_stash(worktree_path: String, stash: String) -> Result<StashEntry, AppError> {
+    stash::drop_stash(Path::new(&worktree_path), &stash)
+}
+
+/// Per-file diffs of a stash, untracked files included
+#[tauri::command]
+pub async fn get_stash_diff(worktree_path: String, stash: String, context_lines: Option<u32>) -> Result<Vec<FileDiff>, AppError> {
+    let path = Path::new(&worktree_path);
+    let entry = stash::find_stash(path, &stash)?;
+    stash_diff(path, &entry.sha, context_lines.unwrap_or(DEFAULT_CONTEXT_LINES))
+        .map_err(|e| e.context("Failed to get stash diff"))
+}
@@ -0,0 +1,34 @@
+use std::path::Path;
+
+use crate::error::AppError;
+use crate::git::tag::{self, Changelog, CreateTagRequest, DeletedTag, TagInfo, TagSuggestion, VersionBump};
+
+/// All tags, newest first, with target commits and annotation messages
+#[tauri::command]
+pub async fn list_tags(project_path: String) -> Result<Vec<TagInfo>, AppError> {
+    tag::list_tags(Path::new(&project_path)).map_err(|e| e.context("Failed to list tags"))
+}
+
+/// Create a lightweight, annotated or signed tag
+#[tauri::command]
+pub async fn create_tag(project_path: String, request: CreateTagRequest) -> Result<TagInfo, AppError> {
+    tag::create_tag(Path::new(&project_path), &request)
+}
+
+/// Delete a local tag; use `git_push_tag` with `delete` to remove it from a remote
+#[tauri::command]
+pub async fn delete_tag(project_path: String, name: String) -> Result<DeletedTag, AppError> {
+    tag::delete_tag(Path::new(&project_path), &name)
+}
+
+/// Next semver tag, bumped as requested or as the commits since the last release suggest
+#[tauri::command]
+pub async fn suggest_next_tag(project_path: String, bump: Option<VersionBump>) -> Result<TagSuggestion, AppError> {
+    tag::suggest_next_tag(Path::new(&project_path), bump)
+}
+
+/// Conventional-commit changelog for `from..to`; defaults to the latest tag up to `HEAD`
+#[tauri::command]
+pub async fn get_changelog(project_path: String, from: Option<String>, to: Option<String>) -> Result<Changelog, AppError> {
+    tag::changelog(Path::new(&project_path), from.as_deref(), to.as_deref())
+}
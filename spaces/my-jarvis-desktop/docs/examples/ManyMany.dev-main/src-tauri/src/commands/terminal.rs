@@ -2,6 +2,7 @@ use serde::{Deserialize, Serialize};
 use tauri::{AppHandle, State};
 use std::sync::Mutex;
 
+use crate::error::AppError;
 use crate::terminal::{TerminalManager};
 use crate::terminal::task::CreateTerminalRequest;
 
@@ -12,6 +13,7 @@ pub struct Terminal {
     pub name: String,
     pub terminal_type: String,
     pub working_directory: String,
+    pub profile: Option<String>,
     pub is_active: bool,
 }
 
@@ -21,7 +23,7 @@ pub async fn create_terminal(
     request: CreateTerminalRequest,
     app: AppHandle,
     state: State<'_, Mutex<TerminalManager>>,
-) -> Result<String, String> {
+) -> Result<String, AppError> {
     let terminal_id = {
         let mut manager = state.lock().unwrap();
         manager.create_terminal(request, app)?
@@ -36,7 +38,7 @@ pub async fn terminal_input(
     terminal_id: String,
     data: String,
     state: State<'_, Mutex<TerminalManager>>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     let manager = state.lock().unwrap();
     manager.send_input(&terminal_id, &data)?;
     
@@ -48,7 +50,7 @@ pub async fn terminal_input(
 pub async fn close_terminal(
     terminal_id: String,
     state: State<'_, Mutex<TerminalManager>>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     {
         let mut manager = state.lock().unwrap();
         manager.close_terminal(&terminal_id)?;
@@ -61,7 +63,7 @@ pub async fn close_terminal(
 #[tauri::command]
 pub async fn list_terminals(
     state: State<'_, Mutex<TerminalManager>>,
-) -> Result<Vec<String>, String> {
+) -> Result<Vec<String>, AppError> {
     let manager = state.lock().unwrap();
     let terminal_ids = manager.list_terminals();
     
@@ -75,7 +77,7 @@ pub async fn resize_terminal(
     cols: u16, 
     rows: u16,
     _state: State<'_, Mutex<TerminalManager>>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     // TODO: Implement terminal resizing in the streaming architecture
     // For now, just return success to avoid breaking existing code
     Ok(())
@@ -86,7 +88,7 @@ pub async fn resize_terminal(
 pub async fn get_terminal_info(
     terminal_id: String,
     state: State<'_, Mutex<TerminalManager>>,
-) -> Result<Option<Terminal>, String> {
+) -> Result<Option<Terminal>, AppError> {
     let manager = state.lock().unwrap();
     
     if let Some(task) = manager.get_terminal(terminal_id.as_str()) {
@@ -96,6 +98,7 @@ pub async fn get_terminal_info(
             name: task.name.clone(),
             terminal_type: "shell".to_string(),
             working_directory: task.working_directory.clone(),
+            profile: task.profile.clone(),
             is_active: task.is_active,
         };
         
@@ -109,7 +112,7 @@ pub async fn get_terminal_info(
 #[tauri::command]
 pub async fn cleanup_terminals(
     state: State<'_, Mutex<TerminalManager>>,
-) -> Result<usize, String> {
+) -> Result<usize, AppError> {
     let (count_before, count_after) = {
         let mut manager = state.lock().unwrap();
         let count_before = manager.terminal_count();
@@ -127,19 +130,19 @@ pub async fn cleanup_terminals(
 
 /// Open a file in an external editor
 #[tauri::command]
-pub async fn open_editor(path: String, editor: String) -> Result<(), String> {
+pub async fn open_editor(path: String, editor: String) -> Result<(), AppError> {
     use std::process::Command;
     
     let editor_cmd = match editor.as_str() {
         "vscode" => "code",
         "cursor" => "cursor",
-        _ => return Err("Unsupported editor".to_string()),
+        _ => return Err(AppError::invalid("Unsupported editor")),
     };
     
     let _output = Command::new(editor_cmd)
         .arg(&path)
         .spawn()
-        .map_err(|e| format!("Failed to open editor: {}", e))?;
+        .map_err(|e| AppError::io("Failed to open editor", e))?;
     
     Ok(())
 }
@@ -154,7 +157,7 @@ pub async fn write_to_terminal(
     terminal_id: String,
     data: String,
     state: State<'_, Mutex<TerminalManager>>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     // Forward to new command
     terminal_input(terminal_id, data, state).await
 }
@@ -164,7 +167,7 @@ pub async fn write_to_terminal(
 pub async fn read_from_terminal(
     terminal_id: String,
     _state: State<'_, Mutex<TerminalManager>>,
-) -> Result<String, String> {
+) -> Result<String, AppError> {
     // Return empty string since output is now streamed via events
     Ok(String::new())
 }
\ No newline at end of file
@@ -1,9 +1,13 @@
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
-use std::process::Command;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 use uuid::Uuid;
 use chrono::Utc;
 
+use crate::error::{AppError, ErrorKind};
+use crate::git::{self, backend};
+use crate::process::{self, RunOptions};
+
 fn sanitize_project_name(name: &str) -> String {
     name.chars()
         .map(|c| match c {
@@ -16,6 +20,12 @@ fn sanitize_project_name(name: &str) -> String {
         .to_string()
 }
 
+/// The other worktree's path from git's "'x' is already checked out at '/path'"
+fn checked_out_at(stderr: &str) -> Option<String> {
+    let (_, rest) = stderr.split_once(" at '")?;
+    rest.split_once('\'').map(|(path, _)| path.to_string())
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Worktree {
     pub id: String,
@@ -33,9 +43,9 @@ pub async fn create_worktree(
     branch: String,
     project_id: String,
     worktree_name: String,
-) -> Result<Worktree, String> {
+) -> Result<Worktree, AppError> {
     let home_dir = dirs::home_dir()
-        .ok_or_else(|| "Could not find home directory".to_string())?;
+        .ok_or_else(|| AppError::other("Could not find home directory"))?;
     
     let worktree_base = home_dir.join(".manymany");
     let project_name = PathBuf::from(&project_path)
@@ -51,45 +61,29 @@ pub async fn create_worktree(
         .join(&sanitized_project_name)
         .join(&sanitized_worktree_name);
     
-    // Create directory if it doesn't exist
-    std::fs::create_dir_all(&worktree_path)
-        .map_err(|e| format!("Failed to create worktree directory: {}", e))?;
-    
-    // Check if branch is already checked out somewhere
-    let list_output = Command::new("git")
-        .args(&["-C", &project_path, "worktree", "list", "--porcelain"])
-        .output()
-        .map_err(|e| format!("Failed to list existing worktrees: {}", e))?;
-    
-    if list_output.status.success() {
-        let list_str = String::from_utf8_lossy(&list_output.stdout);
-        let mut current_path = String::new();
-        let mut current_branch = String::new();
-        
-        for line in list_str.lines() {
-            if line.starts_with("worktree ") {
-                current_path = line.strip_prefix("worktree ").unwrap_or("").to_string();
-                current_branch.clear();
-            } else if line.starts_with("branch ") {
-                current_branch = line.strip_prefix("branch refs/heads/")
-                    .unwrap_or(line.strip_prefix("branch ").unwrap_or(""))
-                    .to_string();
-                
-                // Check if this branch is already checked out (but allow main/master to be used in multiple worktrees)
-                if current_branch == branch && !current_path.is_empty() && branch != "main" && branch != "master" {
-                    return Err(format!(
-                        "Branch '{}' is already checked out at: {}\n\nPlease choose a different branch or delete the existing worktree first.",
-                        branch, current_path
-                    ));
-                }
-            }
+    // Check if branch is already checked out somewhere (but allow main/master to be used in multiple worktrees)
+    if branch != "main" && branch != "master" {
+        let repo = project_path.clone();
+        let worktrees = process::blocking(move || backend::current().worktrees(Path::new(&repo)))
+            .await
+            .map_err(|e| e.context("Failed to list existing worktrees"))?;
+        if let Some(existing) = worktrees.iter().find(|worktree| worktree.branch.as_deref() == Some(branch.as_str())) {
+            return Err(AppError::new(
+                ErrorKind::BranchCheckedOut { branch: branch.clone(), path: Some(existing.path.clone()) },
+                format!(
+                    "Branch '{}' is already checked out at: {}\n\nPlease choose a different branch or delete the existing worktree first.",
+                    branch, existing.path
+                ),
+            ));
         }
     }
 
+    // Create directory if it doesn't exist
+    std::fs::create_dir_all(&worktree_path)
+        .map_err(|e| AppError::io("Failed to create worktree directory", e))?;
+
     // Create Git worktree
     let mut args = vec![
-        "-C",
-        &project_path,
         "worktree",
         "add",
     ];
@@ -103,26 +97,32 @@ pub async fn create_worktree(
     args.push(worktree_path.to_str().unwrap());
     args.push(&branch);
     
-    let output = Command::new("git")
-        .args(&args)
-        .output()
-        .map_err(|e| format!("Failed to create worktree: {}", e))?;
+    let output = process::git(Path::new(&project_path), &args, RunOptions::default())
+        .await
+        .map_err(|e| e.context("Failed to create worktree"))?;
     
     if !output.status.success() {
         let error = String::from_utf8_lossy(&output.stderr);
         // Provide more helpful error messages
         if error.contains("already checked out") {
-            return Err(format!(
-                "Branch '{}' is already checked out in another worktree.\n\nPlease choose a different branch or delete the existing worktree first.",
-                branch
+            return Err(AppError::new(
+                ErrorKind::BranchCheckedOut { branch: branch.clone(), path: checked_out_at(&error) },
+                format!(
+                    "Branch '{}' is already checked out in another worktree.\n\nPlease choose a different branch or delete the existing worktree first.",
+                    branch
+                ),
             ));
         } else if error.contains("not a valid branch") {
-            return Err(format!(
-                "Branch '{}' does not exist.\n\nPlease create the branch first or choose an existing branch.",
-                branch
+            return Err(AppError::new(
+                ErrorKind::BranchNotFound { branch: branch.clone() },
+                format!(
+                    "Branch '{}' does not exist.\n\nPlease create the branch first or choose an existing branch.",
+                    branch
+                ),
             ));
         } else {
-            return Err(format!("Failed to create worktree: {}", error));
+            return Err(git::git_error(Path::new(&project_path), "worktree", &error, output.status.code())
+                .context("Failed to create worktree"));
         }
     }
     
@@ -140,137 +140,68 @@ pub async fn create_worktree(
 }
 
 #[tauri::command]
-pub async fn list_worktrees(project_path: String) -> Result<Vec<Worktree>, String> {
-    let output = Command::new("git")
-        .args(&["-C", &project_path, "worktree", "list", "--porcelain"])
-        .output()
-        .map_err(|e| format!("Failed to list worktrees: {}", e))?;
-    
-    if !output.status.success() {
-        return Err("Failed to list worktrees".to_string());
-    }
-    
-    let output_str = String::from_utf8_lossy(&output.stdout);
-    let mut worktrees = Vec::new();
-    let mut current_worktree: Option<Worktree> = None;
-    
-    for line in output_str.lines() {
-        if line.starts_with("worktree ") {
-            if let Some(wt) = current_worktree.take() {
-                worktrees.push(wt);
-            }
-            
-            let path = line.strip_prefix("worktree ").unwrap_or("");
-            current_worktree = Some(Worktree {
-                id: format!("worktree-{:x}", path.bytes().fold(0u64, |acc, b| acc.wrapping_mul(31).wrapping_add(b as u64))),
-                project_id: String::new(), // Will be set by caller
-                branch: String::new(), // Will be set from branch line
-                path: path.to_string(),
-                is_active: false,
-                has_uncommitted_changes: false,
-                created_at: Utc::now().to_rfc3339(),
-            });
-        } else if line.starts_with("branch ") {
-            if let Some(ref mut wt) = current_worktree {
-                wt.branch = line.strip_prefix("branch refs/heads/")
-                    .unwrap_or(line.strip_prefix("branch ").unwrap_or(""))
-                    .to_string();
-            }
-        }
-    }
-    
-    if let Some(wt) = current_worktree {
-        worktrees.push(wt);
-    }
-    
-    Ok(worktrees)
+pub async fn list_worktrees(project_path: String) -> Result<Vec<Worktree>, AppError> {
+    let worktrees = process::blocking(move || backend::current().worktrees(Path::new(&project_path)))
+        .await
+        .map_err(|e| e.context("Failed to list worktrees"))?;
+
+    Ok(worktrees
+        .into_iter()
+        .map(|worktree| Worktree {
+            id: format!("worktree-{:x}", worktree.path.bytes().fold(0u64, |acc, b| acc.wrapping_mul(31).wrapping_add(b as u64))),
+            project_id: String::new(), // Will be set by caller
+            branch: worktree.branch.unwrap_or_default(),
+            path: worktree.path,
+            is_active: false,
+            has_uncommitted_changes: false,
+            created_at: Utc::now().to_rfc3339(),
+        })
+        .collect())
 }
 
 #[tauri::command]
-pub async fn remove_worktree(project_path: String, worktree_path: String) -> Result<(), String> {
-    let output = Command::new("git")
-        .args(&[
-            "-C",
-            &project_path,
-            "worktree",
-            "remove",
-            &worktree_path,
-            "--force",
-        ])
-        .output()
-        .map_err(|e| format!("Failed to remove worktree: {}", e))?;
+pub async fn remove_worktree(project_path: String, worktree_path: String) -> Result<(), AppError> {
+    let output = process::git(Path::new(&project_path), &["worktree", "remove", &worktree_path, "--force"], RunOptions::default())
+        .await
+        .map_err(|e| e.context("Failed to remove worktree"))?;
     
     if !output.status.success() {
         let error = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("Failed to remove worktree: {}", error));
+        return Err(git::git_error(Path::new(&project_path), "worktree", &error, output.status.code())
+            .context("Failed to remove worktree"));
     }
     
     Ok(())
 }
 
 #[tauri::command]
-pub async fn get_available_branches(project_path: String) -> Result<Vec<String>, String> {
-    // First get all existing worktrees to filter out checked-out branches
-    let mut checked_out_branches = std::collections::HashSet::new();
-    
-    let list_output = Command::new("git")
-        .args(&["-C", &project_path, "worktree", "list", "--porcelain"])
-        .output()
-        .map_err(|e| format!("Failed to list existing worktrees: {}", e))?;
-    
-    if list_output.status.success() {
-        let list_str = String::from_utf8_lossy(&list_output.stdout);
-        let mut current_branch;
-        
-        for line in list_str.lines() {
-            if line.starts_with("branch ") {
-                current_branch = line.strip_prefix("branch refs/heads/")
-                    .unwrap_or(line.strip_prefix("branch ").unwrap_or(""))
-                    .to_string();
-                // Don't filter out main/master branches - users should be able to create worktrees from them
-                if !current_branch.is_empty() && current_branch != "main" && current_branch != "master" {
-                    checked_out_branches.insert(current_branch);
-                }
-            }
-        }
-    }
-    // Get local branches
-    let local_output = Command::new("git")
-        .args(&["-C", &project_path, "branch", "--format=%(refname:short)"])
-        .output()
-        .map_err(|e| format!("Failed to get local branches: {}", e))?;
-    
-    // Get remote branches
-    let remote_output = Command::new("git")
-        .args(&["-C", &project_path, "branch", "-r", "--format=%(refname:short)"])
-        .output()
-        .map_err(|e| format!("Failed to get remote branches: {}", e))?;
-    
-    let mut branches = Vec::new();
-    
-    // Process local branches
-    if local_output.status.success() {
-        let local_str = String::from_utf8_lossy(&local_output.stdout);
-        for line in local_str.lines() {
-            let branch = line.trim();
-            if !branch.is_empty() && branch != "HEAD" && !checked_out_branches.contains(branch) {
-                branches.push(branch.to_string());
-            }
+pub async fn get_available_branches(project_path: String) -> Result<Vec<String>, AppError> {
+    let (worktrees, all_branches) = process::blocking(move || {
+        let repo = Path::new(&project_path);
+        let backend = backend::current();
+        let worktrees = backend.worktrees(repo).map_err(|e| e.context("Failed to list existing worktrees"))?;
+        let branches = backend.branches(repo).map_err(|e| e.context("Failed to get branches"))?;
+        Ok((worktrees, branches))
+    })
+    .await?;
+
+    // Branches checked out in a worktree can't be checked out again, except main/master
+    let checked_out_branches: HashSet<String> = worktrees
+        .into_iter()
+        .filter_map(|worktree| worktree.branch)
+        .filter(|branch| branch != "main" && branch != "master")
+        .collect();
+
+    let mut branches: Vec<String> = Vec::new();
+    // Local branches first, so a remote one with the same name is skipped
+    for info in all_branches.iter().filter(|info| !info.is_remote).chain(all_branches.iter().filter(|info| info.is_remote)) {
+        if info.name.ends_with("/HEAD") || info.name == "HEAD" {
+            continue;
         }
-    }
-    
-    // Process remote branches
-    if remote_output.status.success() {
-        let remote_str = String::from_utf8_lossy(&remote_output.stdout);
-        for line in remote_str.lines() {
-            let branch = line.trim();
-            if !branch.is_empty() && !branch.contains("HEAD") {
-                // Remove origin/ prefix for remote branches
-                let clean_branch = branch.strip_prefix("origin/").unwrap_or(branch);
-                if !branches.contains(&clean_branch.to_string()) && !checked_out_branches.contains(clean_branch) {
-                    branches.push(clean_branch.to_string());
-                }
-            }
+        // Remove origin/ prefix for remote branches
+        let name = if info.is_remote { info.name.strip_prefix("origin/").unwrap_or(&info.name) } else { &info.name };
+        if !branches.iter().any(|branch| branch == name) && !checked_out_branches.contains(name) {
+            branches.push(name.to_string());
         }
     }
     
@@ -0,0 +1,150 @@
+//! The error every command returns. The frontend switches on `code`; `message` is for display
+//! and `context` says what was being done when it happened, outermost first.
+//!
+//! Serialized as e.g.
+//! `{"code": "branch_checked_out", "branch": "feature", "path": "/work/feature", "message": "...", "context": ["Failed to create worktree"]}`
+
+use std::fmt;
+use std::io;
+use serde::{Deserialize, Serialize};
+
+use crate::git::operation::OperationInProgress;
+use crate::git::remote::RemoteError;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AppError {
+    #[serde(flatten)]
+    pub kind: ErrorKind,
+    pub message: String,
+    pub context: Vec<String>,
+}
+
+/// What went wrong. The `code` tags are stable; don't rename them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "code", rename_all = "snake_case")]
+pub enum ErrorKind {
+    NotAGitRepo { path: String },
+    PathNotFound { path: String },
+    /// The branch is checked out in another worktree
+    BranchCheckedOut { branch: String, path: Option<String> },
+    BranchNotFound { branch: String },
+    BranchExists { branch: String },
+    /// Deleting the branch without force would lose commits
+    BranchNotMerged { branch: String, ahead: u32 },
+    TagNotFound { tag: String },
+    TagExists { tag: String },
+    StashNotFound { stash: String },
+    CommitNotFound { sha: String },
+    SnapshotNotFound { id: String },
+    SecretNotFound { name: String },
+    ProfileNotFound { profile_id: String },
+    /// A merge, rebase, cherry-pick, revert or bisect has to finish first
+    OperationInProgress { operation: OperationInProgress },
+    /// No `git` executable on PATH
+    GitNotFound,
+    GitFailed { command: String, stderr: String, exit_code: Option<i32> },
+    /// The process was killed after running longer than its timeout
+    TimedOut { command: String, timeout_ms: u64 },
+    Cancelled { command: String },
+    Remote { error: RemoteError },
+    TerminalNotFound { terminal_id: String },
+    /// A bad argument, e.g. an invalid ref or branch name
+    InvalidInput,
+    Io,
+    Other,
+}
+
+impl AppError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        AppError {
+            kind,
+            message: message.into(),
+            context: Vec::new(),
+        }
+    }
+
+    pub fn invalid(message: impl Into<String>) -> Self {
+        AppError::new(ErrorKind::InvalidInput, message)
+    }
+
+    pub fn other(message: impl Into<String>) -> Self {
+        AppError::new(ErrorKind::Other, message)
+    }
+
+    /// An I/O error while doing `action`, e.g. "Failed to read settings"
+    pub fn io(action: impl Into<String>, error: io::Error) -> Self {
+        AppError::from(error).context(action)
+    }
+
+    pub fn path_not_found(path: impl fmt::Display) -> Self {
+        AppError::new(ErrorKind::PathNotFound { path: path.to_string() }, format!("Path not found: {}", path))
+    }
+
+    pub fn cancelled(command: impl Into<String>) -> Self {
+        let command = command.into();
+        let message = format!("{} was cancelled", command);
+        AppError::new(ErrorKind::Cancelled { command }, message)
+    }
+
+    pub fn terminal_not_found(terminal_id: &str) -> Self {
+        AppError::new(
+            ErrorKind::TerminalNotFound { terminal_id: terminal_id.to_string() },
+            format!("Terminal not found: {}", terminal_id),
+        )
+    }
+
+    /// Add what was being done, e.g. "Failed to list branches"
+    pub fn context(mut self, context: impl Into<String>) -> Self {
+        self.context.insert(0, context.into());
+        self
+    }
+}
+
+/// The context chain and message, e.g. "Failed to create worktree: Branch 'x' does not exist"
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for context in &self.context {
+            write!(f, "{}: ", context)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl From<io::Error> for AppError {
+    fn from(error: io::Error) -> Self {
+        AppError::new(ErrorKind::Io, error.to_string())
+    }
+}
+
+impl From<RemoteError> for AppError {
+    fn from(error: RemoteError) -> Self {
+        let message = error.to_string();
+        AppError::new(ErrorKind::Remote { error }, message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialized_shape() {
+        let error = AppError::new(
+            ErrorKind::BranchCheckedOut { branch: "feature".to_string(), path: Some("/work/feature".to_string()) },
+            "Branch 'feature' is already checked out",
+        )
+        .context("Failed to create worktree");
+
+        let json = serde_json::to_value(&error).unwrap();
+        assert_eq!(json["code"], "branch_checked_out");
+        assert_eq!(json["path"], "/work/feature");
+        assert_eq!(json["context"][0], "Failed to create worktree");
+        assert_eq!(error.to_string(), "Failed to create worktree: Branch 'feature' is already checked out");
+        assert_eq!(serde_json::from_value::<AppError>(json).unwrap(), error);
+
+        let remote = AppError::from(RemoteError::Cancelled);
+        assert_eq!(serde_json::to_value(&remote).unwrap()["error"]["kind"], "cancelled");
+    }
+}
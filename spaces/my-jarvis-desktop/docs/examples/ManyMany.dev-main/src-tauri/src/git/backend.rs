@@ -0,0 +1,258 @@
+//! The read operations the UI polls most - status, branches, worktrees, diff and log - behind
+//! one trait, so they can run on the `git` binary or in-process. The backend is picked at runtime.
+
+use std::path::Path;
+use std::sync::RwLock;
+use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
+
+use crate::error::AppError;
+use crate::git::branch::{self, BranchInfo};
+use crate::git::diff::{self, WorktreeDiff};
+use crate::git::libgit2::Libgit2Backend;
+use crate::git::log::{self, CommitLog, LogQuery};
+use crate::git::status::{self, DefaultBranchDivergence, GitStatus};
+use crate::git::worktree::{self, WorktreeInfo};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackendKind {
+    /// Runs the `git` binary on PATH
+    Cli,
+    /// Runs in-process on libgit2
+    Libgit2,
+}
+
+pub trait GitBackend: Send + Sync {
+    fn kind(&self) -> BackendKind;
+    /// Branch, upstream, divergence and changed files of a worktree
+    fn status(&self, worktree_path: &Path) -> Result<GitStatus, AppError>;
+    /// Ahead/behind of `HEAD` against the project's default branch
+    fn default_branch_divergence(&self, worktree_path: &Path) -> Result<DefaultBranchDivergence, AppError>;
+    /// Local and remote-tracking branches, newest commit first
+    fn branches(&self, repo: &Path) -> Result<Vec<BranchInfo>, AppError>;
+    /// The main worktree first, then linked ones by path
+    fn worktrees(&self, repo: &Path) -> Result<Vec<WorktreeInfo>, AppError>;
+    /// Staged and unstaged changes, untracked files as additions. Gives up once `cancel` fires.
+    fn diff(&self, worktree_path: &Path, context_lines: u32, cancel: &CancellationToken) -> Result<WorktreeDiff, AppError>;
+    /// One page of history with file stats and graph lanes. Gives up once `cancel` fires.
+    fn log(&self, worktree_path: &Path, query: &LogQuery, cancel: &CancellationToken) -> Result<CommitLog, AppError>;
+}
+
+pub struct CliBackend;
+
+impl GitBackend for CliBackend {
+    fn kind(&self) -> BackendKind {
+        BackendKind::Cli
+    }
+
+    fn status(&self, worktree_path: &Path) -> Result<GitStatus, AppError> {
+        status::read_status(worktree_path)
+    }
+
+    fn default_branch_divergence(&self, worktree_path: &Path) -> Result<DefaultBranchDivergence, AppError> {
+        status::default_branch_divergence(worktree_path)
+    }
+
+    fn branches(&self, repo: &Path) -> Result<Vec<BranchInfo>, AppError> {
+        branch::list_branches(repo)
+    }
+
+    fn worktrees(&self, repo: &Path) -> Result<Vec<WorktreeInfo>, AppError> {
+        worktree::list_worktrees(repo)
+    }
+
+    fn diff(&self, worktree_path: &Path, context_lines: u32, cancel: &CancellationToken) -> Result<WorktreeDiff, AppError> {
+        diff::worktree_diff(worktree_path, context_lines, cancel)
+    }
+
+    fn log(&self, worktree_path: &Path, query: &LogQuery, cancel: &CancellationToken) -> Result<CommitLog, AppError> {
+        log::commit_log(worktree_path, query, cancel)
+    }
+}
+
+static SELECTED: RwLock<BackendKind> = RwLock::new(BackendKind::Cli);
+
+pub fn backend(kind: BackendKind) -> &'static dyn GitBackend {
+    match kind {
+        BackendKind::Cli => &CliBackend,
+        BackendKind::Libgit2 => &Libgit2Backend,
+    }
+}
+
+/// The backend commands use
+pub fn current() -> &'static dyn GitBackend {
+    backend(selected())
+}
+
+fn selected() -> BackendKind {
+    *SELECTED.read().unwrap()
+}
+
+pub fn select(kind: BackendKind) {
+    *SELECTED.write().unwrap() = kind;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorKind;
+    use crate::git::fixture::TempRepo;
+    use crate::git::status::{FileStatus, UpstreamState};
+    use std::path::PathBuf;
+
+    const BACKENDS: [BackendKind; 2] = [BackendKind::Cli, BackendKind::Libgit2];
+    const T0: i64 = 1_700_000_000;
+
+    /// `main` one commit ahead of `origin/main` with every kind of change, and `feature`
+    /// checked out in a linked worktree with a gone upstream
+    fn fixture() -> (TempRepo, TempRepo) {
+        let repo = TempRepo::new();
+        repo.write(".gitignore", "*.log\n");
+        repo.write("a.txt", "one\ntwo\n");
+        repo.write("dir/b.txt", "bee\nbee\nbee\n");
+        repo.commit_all_at("initial", T0);
+        repo.git(&["remote", "add", "origin", "https://example.invalid/repo.git"]);
+        repo.git(&["update-ref", "refs/remotes/origin/main", "HEAD"]);
+        repo.git(&["branch", "--set-upstream-to", "origin/main"]);
+
+        repo.write("a.txt", "one\ntwo\nthree\n");
+        repo.commit_all_at("second\n\nbody text", T0 + 100);
+        repo.git(&["tag", "v1"]);
+
+        let linked = repo.add_worktree("feature");
+        repo.git(&["config", "branch.feature.remote", "origin"]);
+        repo.git(&["config", "branch.feature.merge", "refs/heads/feature"]);
+        linked.write("feature.txt", "feature\n");
+        linked.commit_all_at("feature work", T0 + 200);
+
+        repo.write("a.txt", "one\ntwo\nthree\nfour\n");
+        repo.write("staged.txt", "staged\n");
+        repo.git(&["add", "staged.txt"]);
+        repo.git(&["mv", "dir/b.txt", "dir/c.txt"]);
+        repo.write("new/u.txt", "untracked\n");
+        repo.write("debug.log", "ignored\n");
+        (repo, linked)
+    }
+
+    fn canonical(path: &Path) -> String {
+        std::fs::canonicalize(path).unwrap().to_string_lossy().into_owned()
+    }
+
+    fn check_backend(backend: &dyn GitBackend, repo: &TempRepo, linked: &TempRepo) {
+        let kind = backend.kind();
+        let no_cancel = CancellationToken::new();
+        let status = backend.status(repo.path()).unwrap();
+        assert_eq!(status.branch, "main", "{:?}", kind);
+        assert_eq!(status.upstream.as_deref(), Some("origin/main"), "{:?}", kind);
+        assert_eq!((status.upstream_state, status.ahead, status.behind), (UpstreamState::Tracking, 1, 0), "{:?}", kind);
+        let divergence = backend.default_branch_divergence(repo.path()).unwrap();
+        assert_eq!((divergence.name.as_str(), divergence.ahead, divergence.behind), ("main", 0, 0), "{:?}", kind);
+        let files = |files: &[crate::git::status::GitFile]| {
+            files.iter().map(|file| (file.path.clone(), file.status, file.orig_path.clone())).collect::<Vec<_>>()
+        };
+        assert_eq!(files(&status.staged), vec![
+            ("dir/c.txt".to_string(), FileStatus::Renamed, Some("dir/b.txt".to_string())),
+            ("staged.txt".to_string(), FileStatus::Added, None),
+        ], "{:?}", kind);
+        assert_eq!(status.staged[0].similarity, Some(100), "{:?}", kind);
+        assert_eq!(files(&status.unstaged), vec![("a.txt".to_string(), FileStatus::Modified, None)], "{:?}", kind);
+        assert_eq!(files(&status.untracked), vec![("new/u.txt".to_string(), FileStatus::Untracked, None)], "{:?}", kind);
+
+        let linked_status = backend.status(linked.path()).unwrap();
+        assert_eq!(linked_status.branch, "feature", "{:?}", kind);
+        assert_eq!(linked_status.upstream_state, UpstreamState::Gone, "{:?}", kind);
+        assert_eq!(backend.default_branch_divergence(linked.path()).unwrap().ahead, 1, "{:?}", kind);
+
+        let branches = backend.branches(repo.path()).unwrap();
+        let names: Vec<&str> = branches.iter().map(|branch| branch.name.as_str()).collect();
+        assert_eq!(names, vec!["feature", "main", "origin/main"], "{:?}", kind);
+        assert!(branches[0].upstream_gone, "{:?}", kind);
+        assert_eq!(branches[0].worktree_path, Some(canonical(linked.path())), "{:?}", kind);
+        assert_eq!((branches[1].ahead, branches[1].behind), (1, 0), "{:?}", kind);
+        assert_eq!(branches[1].last_commit_subject, "second", "{:?}", kind);
+        assert!(branches[2].is_remote, "{:?}", kind);
+
+        let worktrees = backend.worktrees(linked.path()).unwrap();
+        assert_eq!(worktrees.len(), 2, "{:?}", kind);
+        assert!(worktrees[0].is_main, "{:?}", kind);
+        assert_eq!(worktrees[0].path, canonical(repo.path()), "{:?}", kind);
+        assert_eq!(worktrees[1].branch.as_deref(), Some("feature"), "{:?}", kind);
+        assert_eq!(worktrees[1].head, branches[0].sha.clone().into(), "{:?}", kind);
+
+        let diff = backend.diff(repo.path(), 3, &no_cancel).unwrap();
+        let paths = |diffs: &[crate::git::diff::FileDiff]| {
+            diffs.iter().map(|diff| (diff.path.clone(), diff.status)).collect::<Vec<_>>()
+        };
+        assert_eq!(paths(&diff.staged), vec![
+            ("dir/c.txt".to_string(), FileStatus::Renamed),
+            ("staged.txt".to_string(), FileStatus::Added),
+        ], "{:?}", kind);
+        assert_eq!(paths(&diff.unstaged), vec![
+            ("a.txt".to_string(), FileStatus::Modified),
+            ("new/u.txt".to_string(), FileStatus::Added),
+        ], "{:?}", kind);
+        assert_eq!(diff.unstaged[0].hunks[0].lines.last().unwrap().content, "four", "{:?}", kind);
+
+        let log = backend.log(repo.path(), &LogQuery { limit: Some(1), ..Default::default() }, &no_cancel).unwrap();
+        assert!(log.has_more, "{:?}", kind);
+        let head = &log.commits[0];
+        assert_eq!((head.subject.as_str(), head.body.as_str()), ("second", "body text"), "{:?}", kind);
+        assert!(head.refs.contains(&"HEAD -> main".to_string()) && head.refs.contains(&"tag: v1".to_string()), "{:?}", kind);
+        assert_eq!((head.additions, head.deletions), (1, 0), "{:?}", kind);
+        assert_eq!(head.author_date, "2023-11-14T23:15:00+01:00", "{:?}", kind);
+
+        let query = |query: LogQuery| -> Vec<String> {
+            backend.log(repo.path(), &query, &no_cancel).unwrap().commits.into_iter().map(|commit| commit.subject).collect()
+        };
+        assert_eq!(query(LogQuery { reference: Some("feature".into()), ..Default::default() }),
+            vec!["feature work", "second", "initial"], "{:?}", kind);
+        assert_eq!(query(LogQuery { path_filter: Some("dir".into()), ..Default::default() }), vec!["initial"], "{:?}", kind);
+        assert_eq!(query(LogQuery { skip: 1, ..Default::default() }), vec!["initial"], "{:?}", kind);
+        assert!(query(LogQuery { author: Some("Nobody".into()), ..Default::default() }).is_empty(), "{:?}", kind);
+        assert!(query(LogQuery { since: Some("1 week ago".into()), ..Default::default() }).is_empty(), "{:?}", kind);
+        // Between the initial commit and the second one
+        let since = Some("2023-11-14T23:14:10+01:00".to_string());
+        assert_eq!(query(LogQuery { reference: Some("feature".into()), since, ..Default::default() }),
+            vec!["feature work", "second"], "{:?}", kind);
+
+        let cancelled = CancellationToken::new();
+        cancelled.cancel();
+        let error = backend.log(repo.path(), &LogQuery::default(), &cancelled).unwrap_err();
+        assert!(matches!(error.kind, ErrorKind::Cancelled { .. }), "{:?}: {}", kind, error);
+        let error = backend.diff(repo.path(), 3, &cancelled).unwrap_err();
+        assert!(matches!(error.kind, ErrorKind::Cancelled { .. }), "{:?}: {}", kind, error);
+    }
+
+    #[test]
+    fn test_backend_conformance() {
+        let (repo, linked) = fixture();
+        for kind in BACKENDS {
+            check_backend(backend(kind), &repo, &linked);
+        }
+    }
+
+    #[test]
+    fn test_backends_agree() {
+        let (repo, linked) = fixture();
+        let paths: [PathBuf; 2] = [repo.path().to_path_buf(), linked.path().to_path_buf()];
+        let [cli, libgit2] = BACKENDS.map(backend);
+        let no_cancel = CancellationToken::new();
+
+        for path in &paths {
+            assert_eq!(cli.status(path).unwrap(), libgit2.status(path).unwrap());
+            assert_eq!(cli.default_branch_divergence(path).unwrap(), libgit2.default_branch_divergence(path).unwrap());
+            assert_eq!(cli.diff(path, 1, &no_cancel).unwrap(), libgit2.diff(path, 1, &no_cancel).unwrap());
+
+            let mut logs = [cli, libgit2].map(|backend| backend.log(path, &LogQuery::default(), &no_cancel).unwrap());
+            for log in &mut logs {
+                for commit in &mut log.commits {
+                    commit.refs.sort();
+                }
+            }
+            assert_eq!(logs[0], logs[1]);
+        }
+        assert_eq!(cli.branches(repo.path()).unwrap(), libgit2.branches(repo.path()).unwrap());
+        assert_eq!(cli.worktrees(repo.path()).unwrap(), libgit2.worktrees(repo.path()).unwrap());
+    }
+}
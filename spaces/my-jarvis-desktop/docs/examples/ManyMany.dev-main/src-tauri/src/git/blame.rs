@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+use std::path::Path;
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
+
+use crate::error::AppError;
+use crate::git;
+
+/// The all-zero sha `git blame` gives lines that aren't committed
+const UNCOMMITTED_SHA: &str = "0000000000000000000000000000000000000000";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Blame {
+    pub path: String,
+    /// `None` when the worktree file was blamed, uncommitted lines included
+    pub revision: Option<String>,
+    pub lines: Vec<String>,
+    pub ranges: Vec<BlameRange>,
+    /// Commits the ranges refer to, in order of first appearance
+    pub commits: Vec<BlameCommit>,
+}
+
+/// Consecutive lines last changed by the same commit
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlameRange {
+    /// 1-based
+    pub start_line: u32,
+    pub line_count: u32,
+    pub commit: String,
+    /// Where the range starts in the file as that commit left it
+    pub original_start_line: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlameCommit {
+    pub sha: String,
+    /// Lines changed in the worktree or index
+    pub uncommitted: bool,
+    pub author_name: String,
+    pub author_email: String,
+    pub author_date: String,
+    pub committer_name: String,
+    pub committer_email: String,
+    pub committer_date: String,
+    pub summary: String,
+    /// The path in that commit, which differs if the file was renamed since
+    pub filename: String,
+    /// The commit before this one touched the file, to blame further back
+    pub previous: Option<BlamePrevious>,
+    /// The root of the blamed history
+    pub boundary: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlamePrevious {
+    pub sha: String,
+    pub path: String,
+}
+
+/// Blame `path` at `revision`, or the worktree file when `None`; git is killed if `cancel` fires
+pub fn blame(worktree_path: &Path, path: &str, revision: Option<&str>, cancel: &CancellationToken) -> Result<Blame, AppError> {
+    let mut args = vec!["blame", "--porcelain"];
+    if let Some(revision) = revision {
+        if revision.starts_with('-') {
+            return Err(AppError::invalid(format!("Invalid revision: {}", revision)));
+        }
+        args.push(revision);
+    }
+    args.extend(["--", path]);
+
+    let output = git::run_cancellable(worktree_path, &args, cancel)?;
+    let mut blame = parse_blame(&String::from_utf8_lossy(&output))?;
+    blame.path = path.to_string();
+    blame.revision = revision.map(str::to_string);
+    Ok(blame)
+}
+
+/// Parse `git blame --porcelain`, whose commit details only follow a commit's first line
+pub fn parse_blame(output: &str) -> Result<Blame, AppError> {
+    let mut blame = Blame {
+        path: String::new(),
+        revision: None,
+        lines: Vec::new(),
+        ranges: Vec::new(),
+        commits: Vec::new(),
+    };
+    let mut commit_index: HashMap<String, usize> = HashMap::new();
+
+    let mut lines = output.lines();
+    while let Some(header) = lines.next() {
+        // <sha> <original line> <final line> [<lines in group>]
+        let fields: Vec<&str> = header.split(' ').collect();
+        let (sha, original_line, final_line) = match fields.as_slice() {
+            [sha, original, last, ..] => (
+                sha.to_string(),
+                original.parse::<u32>().map_err(|_| AppError::other(format!("Unexpected blame line: {}", header)))?,
+                last.parse::<u32>().map_err(|_| AppError::other(format!("Unexpected blame line: {}", header)))?,
+            ),
+            _ => return Err(AppError::other(format!("Unexpected blame line: {}", header))),
+        };
+
+        let index = *commit_index.entry(sha.clone()).or_insert_with(|| {
+            blame.commits.push(BlameCommit {
+                uncommitted: sha == UNCOMMITTED_SHA,
+                sha: sha.clone(),
+                author_name: String::new(),
+                author_email: String::new(),
+                author_date: String::new(),
+                committer_name: String::new(),
+                committer_email: String::new(),
+                committer_date: String::new(),
+                summary: String::new(),
+                filename: String::new(),
+                previous: None,
+                boundary: false,
+            });
+            blame.commits.len() - 1
+        });
+
+        // Metadata lines, then the line itself prefixed with a tab
+        let content = loop {
+            let line = lines.next().ok_or_else(|| AppError::other(format!("Truncated blame output after: {}", header)))?;
+            if let Some(content) = line.strip_prefix('\t') {
+                break content;
+            }
+            let commit = &mut blame.commits[index];
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            match key {
+                "author" => commit.author_name = value.to_string(),
+                "author-mail" => commit.author_email = value.trim_matches(['<', '>']).to_string(),
+                "author-time" => commit.author_date = value.to_string(),
+                "author-tz" => commit.author_date = format_time(&commit.author_date, value),
+                "committer" => commit.committer_name = value.to_string(),
+                "committer-mail" => commit.committer_email = value.trim_matches(['<', '>']).to_string(),
+                "committer-time" => commit.committer_date = value.to_string(),
+                "committer-tz" => commit.committer_date = format_time(&commit.committer_date, value),
+                "summary" => commit.summary = value.to_string(),
+                "filename" => commit.filename = value.to_string(),
+                "boundary" => commit.boundary = true,
+                "previous" => {
+                    commit.previous = value.split_once(' ').map(|(sha, path)| BlamePrevious {
+                        sha: sha.to_string(),
+                        path: path.to_string(),
+                    })
+                }
+                _ => {}
+            }
+        };
+        blame.lines.push(content.to_string());
+
+        match blame.ranges.last_mut() {
+            Some(range)
+                if range.commit == sha
+                    && range.start_line + range.line_count == final_line
+                    && range.original_start_line + range.line_count == original_line =>
+            {
+                range.line_count += 1;
+            }
+            _ => blame.ranges.push(BlameRange {
+                start_line: final_line,
+                line_count: 1,
+                commit: sha,
+                original_start_line: original_line,
+            }),
+        }
+    }
+
+    Ok(blame)
+}
+
+/// Unix seconds and a `+hhmm` offset as an RFC 3339 date in that offset
+fn format_time(seconds: &str, tz: &str) -> String {
+    let offset = tz.get(1..)
+        .and_then(|digits| digits.parse::<i32>().ok())
+        .map(|hhmm| (hhmm / 100 * 3600 + hhmm % 100 * 60) * if tz.starts_with('-') { -1 } else { 1 })
+        .and_then(FixedOffset::east_opt);
+    match (seconds.parse().ok().and_then(|seconds| DateTime::from_timestamp(seconds, 0)), offset) {
+        (Some(time), Some(offset)) => time.with_timezone(&offset).to_rfc3339(),
+        _ => seconds.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::fixture::TempRepo;
+
+    #[test]
+    fn test_format_time() {
+        assert_eq!(format_time("0", "+0130"), "1970-01-01T01:30:00+01:30");
+        assert_eq!(format_time("0", "-0500"), "1969-12-31T19:00:00-05:00");
+    }
+
+    #[test]
+    fn test_blame_ranges() {
+        let repo = TempRepo::new();
+        repo.write("code.rs", "one\ntwo\nthree\n");
+        repo.commit_all("first");
+        repo.write("code.rs", "one\nTWO\nthree\nfour\n");
+        repo.git(&["add", "-A"]);
+        repo.git(&["-c", "user.name=Agent", "-c", "user.email=agent@example.com", "commit", "-q", "-m", "second"]);
+        repo.write("code.rs", "one\nTWO\nthree\nfour\nfive\n");
+
+        let blame = blame(repo.path(), "code.rs", None, &CancellationToken::new()).unwrap();
+        assert_eq!(blame.lines, vec!["one", "TWO", "three", "four", "five"]);
+        let ranges: Vec<_> = blame.ranges.iter().map(|range| (range.start_line, range.line_count)).collect();
+        assert_eq!(ranges, vec![(1, 1), (2, 1), (3, 1), (4, 1), (5, 1)]);
+
+        let commit = |line: usize| {
+            let range = blame.ranges.iter().find(|range| range.start_line as usize == line).unwrap();
+            blame.commits.iter().find(|commit| commit.sha == range.commit).unwrap()
+        };
+        assert_eq!(commit(1).summary, "first");
+        assert_eq!(commit(1).author_name, "Test");
+        assert_eq!(commit(2).author_email, "agent@example.com");
+        assert_eq!(commit(4).summary, "second");
+        assert!(commit(5).uncommitted);
+        assert_eq!(commit(2).previous.as_ref().map(|previous| previous.path.as_str()), Some("code.rs"));
+        assert!(DateTime::parse_from_rfc3339(&commit(1).author_date).is_ok());
+        assert_eq!(blame.commits.len(), 3);
+
+        let at_first = super::blame(repo.path(), "code.rs", Some("HEAD~1"), &CancellationToken::new()).unwrap();
+        assert_eq!(at_first.ranges.len(), 1);
+        assert_eq!(at_first.ranges[0].line_count, 3);
+        assert!(at_first.commits[0].boundary || at_first.commits[0].previous.is_none());
+    }
+}
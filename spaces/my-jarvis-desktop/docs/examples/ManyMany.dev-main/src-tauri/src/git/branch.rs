@@ -0,0 +1,340 @@
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AppError, ErrorKind};
+use crate::git;
+
+const BRANCH_FORMAT: &str = "%(refname)%00%(refname:short)%00%(objectname)%00%(upstream:short)%00%(upstream:track,nobracket)%00%(committerdate:iso-strict)%00%(authorname)%00%(contents:subject)%00%(worktreepath)";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BranchInfo {
+    /// Short name, e.g. `feature/login` or `origin/feature/login`
+    pub name: String,
+    pub is_remote: bool,
+    pub sha: String,
+    pub upstream: Option<String>,
+    /// The upstream is configured but its remote branch no longer exists
+    pub upstream_gone: bool,
+    pub ahead: u32,
+    pub behind: u32,
+    pub last_commit_date: String,
+    pub last_commit_author: String,
+    pub last_commit_subject: String,
+    /// Worktree the branch is checked out in, if any
+    pub worktree_path: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BranchComparison {
+    pub branch: String,
+    /// What the branch was compared with, e.g. `main` or `origin/main`
+    pub default_branch: String,
+    /// Commits on the branch that aren't on the default branch
+    pub ahead: u32,
+    /// Commits on the default branch that aren't on the branch
+    pub behind: u32,
+    pub merge_base: Option<String>,
+    pub merged: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeletedBranch {
+    pub name: String,
+    /// Where the branch pointed, so it can be recreated
+    pub sha: String,
+}
+
+/// Local and remote-tracking branches, newest commit first
+pub fn list_branches(repo: &Path) -> Result<Vec<BranchInfo>, AppError> {
+    let output = git::run_text(repo, &[
+        "for-each-ref",
+        "--sort=-committerdate",
+        &format!("--format={}", BRANCH_FORMAT),
+        "refs/heads",
+        "refs/remotes",
+    ])?;
+
+    Ok(output.lines().filter_map(parse_branch).collect())
+}
+
+fn parse_branch(line: &str) -> Option<BranchInfo> {
+    let fields: Vec<&str> = line.split('\0').collect();
+    if fields.len() != 9 {
+        return None;
+    }
+    // Skip symbolic refs like origin/HEAD
+    if fields[0].ends_with("/HEAD") {
+        return None;
+    }
+
+    let (ahead, behind, upstream_gone) = parse_track(fields[4]);
+    let non_empty = |value: &str| (!value.is_empty()).then(|| value.to_string());
+    Some(BranchInfo {
+        name: fields[1].to_string(),
+        is_remote: fields[0].starts_with("refs/remotes/"),
+        sha: fields[2].to_string(),
+        upstream: non_empty(fields[3]),
+        upstream_gone,
+        ahead,
+        behind,
+        last_commit_date: fields[5].to_string(),
+        last_commit_author: fields[6].to_string(),
+        last_commit_subject: fields[7].to_string(),
+        worktree_path: non_empty(fields[8]),
+    })
+}
+
+/// `%(upstream:track,nobracket)`: empty, `gone`, `ahead 1`, `behind 2` or `ahead 1, behind 2`
+fn parse_track(track: &str) -> (u32, u32, bool) {
+    if track == "gone" {
+        return (0, 0, true);
+    }
+    let mut ahead = 0;
+    let mut behind = 0;
+    for part in track.split(", ") {
+        if let Some(count) = part.strip_prefix("ahead ") {
+            ahead = count.parse().unwrap_or(0);
+        } else if let Some(count) = part.strip_prefix("behind ") {
+            behind = count.parse().unwrap_or(0);
+        }
+    }
+    (ahead, behind, false)
+}
+
+/// Create `name` at `start_point` (any branch, tag or commit; `HEAD` by default)
+pub fn create_branch(repo: &Path, name: &str, start_point: Option<&str>) -> Result<BranchInfo, AppError> {
+    validate_branch_name(repo, name)?;
+    let start_point = start_point.filter(|start| !start.is_empty()).unwrap_or("HEAD");
+    if start_point.starts_with('-') {
+        return Err(AppError::invalid(format!("Invalid start point: {}", start_point)));
+    }
+
+    git::run(repo, &["branch", name, start_point])
+        .map_err(|e| e.context(format!("Failed to create branch '{}'", name)))?;
+    find_branch(repo, name)
+}
+
+pub fn rename_branch(repo: &Path, old_name: &str, new_name: &str) -> Result<BranchInfo, AppError> {
+    validate_branch_name(repo, new_name)?;
+    find_branch(repo, old_name)?;
+
+    git::run(repo, &["branch", "-m", old_name, new_name])
+        .map_err(|e| e.context(format!("Failed to rename branch '{}'", old_name)))?;
+    find_branch(repo, new_name)
+}
+
+/// Delete a local branch. Without `force` it must be merged into the default branch or its upstream.
+pub fn delete_branch(repo: &Path, name: &str, force: bool) -> Result<DeletedBranch, AppError> {
+    let branch = find_branch(repo, name)?;
+    if let Some(worktree_path) = &branch.worktree_path {
+        return Err(AppError::new(
+            ErrorKind::BranchCheckedOut { branch: name.to_string(), path: Some(worktree_path.clone()) },
+            format!("Branch '{}' is checked out at {}", name, worktree_path),
+        ));
+    }
+
+    if !force {
+        let comparison = compare_to_default(repo, name)?;
+        let merged_upstream = branch.upstream.is_some() && !branch.upstream_gone && branch.ahead == 0;
+        if !comparison.merged && !merged_upstream {
+            return Err(AppError::new(
+                ErrorKind::BranchNotMerged { branch: name.to_string(), ahead: comparison.ahead },
+                format!(
+                    "Branch '{}' has {} commit(s) not merged into {}. Delete it with force to discard them.",
+                    name, comparison.ahead, comparison.default_branch
+                ),
+            ));
+        }
+    }
+
+    git::run(repo, &["branch", "-D", name])
+        .map_err(|e| e.context(format!("Failed to delete branch '{}'", name)))?;
+
+    Ok(DeletedBranch {
+        name: branch.name,
+        sha: branch.sha,
+    })
+}
+
+/// Track `upstream` (e.g. `origin/feature`), or stop tracking with `None`
+pub fn set_upstream(repo: &Path, name: &str, upstream: Option<&str>) -> Result<BranchInfo, AppError> {
+    find_branch(repo, name)?;
+    match upstream {
+        Some(upstream) => git::run(repo, &["branch", &format!("--set-upstream-to={}", upstream), name]),
+        None => git::run(repo, &["branch", "--unset-upstream", name]),
+    }
+    .map_err(|e| e.context(format!("Failed to set upstream of '{}'", name)))?;
+    find_branch(repo, name)
+}
+
+/// How far `branch` has diverged from the project's default branch
+pub fn compare_to_default(repo: &Path, branch: &str) -> Result<BranchComparison, AppError> {
+    if branch.starts_with('-') {
+        return Err(AppError::invalid(format!("Invalid branch: {}", branch)));
+    }
+    let default_branch = default_branch_ref(repo)?;
+    let counts = git::run_text(repo, &[
+        "rev-list", "--left-right", "--count", &format!("{}...{}", branch, default_branch), "--",
+    ])
+    .map_err(|e| e.context(format!("Failed to compare '{}' with {}", branch, default_branch)))?;
+    let (ahead, behind) = counts.split_once('\t').unwrap_or(("0", "0"));
+    let ahead: u32 = ahead.parse().unwrap_or(0);
+
+    Ok(BranchComparison {
+        branch: branch.to_string(),
+        merge_base: git::run_text(repo, &["merge-base", branch, &default_branch]).ok(),
+        default_branch,
+        ahead,
+        behind: behind.parse().unwrap_or(0),
+        merged: ahead == 0,
+    })
+}
+
+/// Name of the project's default branch: what `origin/HEAD` points at, otherwise the first of
+/// `init.defaultBranch`, `main` and `master` that exists locally or on origin, otherwise `main`
+pub fn default_branch(repo: &Path) -> Result<String, AppError> {
+    match git::run_text(repo, &["symbolic-ref", "--short", "-q", "refs/remotes/origin/HEAD"]) {
+        Ok(branch) => {
+            if let Some(name) = branch.strip_prefix("origin/").filter(|name| !name.is_empty()) {
+                return Ok(name.to_string());
+            }
+        }
+        Err(e) if e.kind == ErrorKind::GitNotFound => return Err(e),
+        Err(_) => {}
+    }
+
+    let configured = git::run_text(repo, &["config", "--get", "init.defaultBranch"]).ok();
+    let candidates = configured.iter().map(String::as_str).chain(["main", "master"]);
+    for name in candidates.filter(|name| !name.is_empty()) {
+        if resolve_branch_ref(repo, name).is_some() {
+            return Ok(name.to_string());
+        }
+    }
+    Ok("main".to_string())
+}
+
+/// The default branch as a ref: the local branch if there is one, otherwise `origin/<name>`
+pub fn default_branch_ref(repo: &Path) -> Result<String, AppError> {
+    let name = default_branch(repo)?;
+    resolve_branch_ref(repo, &name).ok_or_else(|| AppError::new(
+        ErrorKind::BranchNotFound { branch: name.clone() },
+        format!("Default branch '{}' not found", name),
+    ))
+}
+
+/// `name` or `origin/name`, whichever exists first
+fn resolve_branch_ref(repo: &Path, name: &str) -> Option<String> {
+    [format!("refs/heads/{}", name), format!("refs/remotes/origin/{}", name)]
+        .into_iter()
+        .find(|candidate| git::run(repo, &["rev-parse", "--verify", "-q", candidate]).is_ok())
+        .map(|candidate| candidate.trim_start_matches("refs/heads/").trim_start_matches("refs/remotes/").to_string())
+}
+
+fn find_branch(repo: &Path, name: &str) -> Result<BranchInfo, AppError> {
+    let output = git::run_text(repo, &[
+        "for-each-ref",
+        &format!("--format={}", BRANCH_FORMAT),
+        &format!("refs/heads/{}", name),
+    ])?;
+    output.lines()
+        .filter_map(parse_branch)
+        .find(|branch| branch.name == name)
+        .ok_or_else(|| AppError::new(
+            ErrorKind::BranchNotFound { branch: name.to_string() },
+            format!("Branch '{}' does not exist", name),
+        ))
+}
+
+fn validate_branch_name(repo: &Path, name: &str) -> Result<(), AppError> {
+    if name.starts_with('-') || git::run(repo, &["check-ref-format", "--branch", name]).is_err() {
+        return Err(AppError::invalid(format!("'{}' is not a valid branch name", name)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::fixture::TempRepo;
+
+    #[test]
+    fn test_parse_track() {
+        assert_eq!(parse_track(""), (0, 0, false));
+        assert_eq!(parse_track("gone"), (0, 0, true));
+        assert_eq!(parse_track("ahead 2, behind 5"), (2, 5, false));
+        assert_eq!(parse_track("behind 1"), (0, 1, false));
+    }
+
+    #[test]
+    fn test_branch_lifecycle() {
+        let repo = TempRepo::new();
+        repo.write("a.txt", "a\n");
+        repo.commit_all("initial");
+        repo.git(&["tag", "v1"]);
+        repo.write("a.txt", "b\n");
+        repo.commit_all("second");
+
+        let from_tag = create_branch(repo.path(), "from-tag", Some("v1")).unwrap();
+        assert_eq!(from_tag.sha, repo.git(&["rev-parse", "v1"]).trim());
+        assert!(create_branch(repo.path(), "bad..name", None).is_err());
+
+        let renamed = rename_branch(repo.path(), "from-tag", "feature/old").unwrap();
+        assert_eq!(renamed.name, "feature/old");
+
+        let tracking = set_upstream(repo.path(), "feature/old", Some("main")).unwrap();
+        assert_eq!(tracking.upstream.as_deref(), Some("main"));
+        assert_eq!((tracking.ahead, tracking.behind), (0, 1));
+        assert_eq!(set_upstream(repo.path(), "feature/old", None).unwrap().upstream, None);
+
+        // Merged into main, so it can go without force
+        delete_branch(repo.path(), "feature/old", false).unwrap();
+
+        create_branch(repo.path(), "work", None).unwrap();
+        repo.git(&["checkout", "-q", "work"]);
+        repo.write("a.txt", "work\n");
+        repo.commit_all("unmerged work");
+        repo.git(&["checkout", "-q", "main"]);
+
+        let comparison = compare_to_default(repo.path(), "work").unwrap();
+        assert_eq!(comparison.default_branch, "main");
+        assert_eq!((comparison.ahead, comparison.behind), (1, 0));
+        assert!(!comparison.merged);
+
+        let error = delete_branch(repo.path(), "work", false).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::BranchNotMerged { branch: "work".to_string(), ahead: 1 });
+        assert!(error.to_string().contains("1 commit(s) not merged into main"), "{}", error);
+        let error = delete_branch(repo.path(), "main", true).unwrap_err();
+        assert!(matches!(error.kind, ErrorKind::BranchCheckedOut { ref branch, path: Some(_) } if branch == "main"), "{}", error);
+
+        let listed = list_branches(repo.path()).unwrap();
+        let main = listed.iter().find(|branch| branch.name == "main").unwrap();
+        assert_eq!(main.worktree_path.as_deref().map(std::path::Path::new), Some(repo.path()));
+        assert_eq!(main.last_commit_subject, "second");
+        let work = listed.iter().find(|branch| branch.name == "work").unwrap();
+        assert_eq!(work.last_commit_author, "Test");
+        assert_eq!(work.worktree_path, None);
+
+        let deleted = delete_branch(repo.path(), "work", true).unwrap();
+        assert_eq!(deleted.sha, work.sha);
+        assert!(matches!(compare_to_default(repo.path(), "--all").unwrap_err().kind, ErrorKind::InvalidInput));
+    }
+
+    #[test]
+    fn test_default_branch_ignores_checkout() {
+        let repo = TempRepo::new();
+        repo.write("a.txt", "a\n");
+        repo.commit_all("initial");
+        repo.git(&["checkout", "-q", "-b", "feature"]);
+        assert_eq!(default_branch(repo.path()).unwrap(), "main");
+
+        repo.git(&["branch", "-m", "main", "trunk"]);
+        repo.git(&["config", "init.defaultBranch", "trunk"]);
+        assert_eq!(default_branch_ref(repo.path()).unwrap(), "trunk");
+
+        let remote = TempRepo::new_bare();
+        repo.git(&["push", "-q", remote.path().to_str().unwrap(), "trunk:develop"]);
+        remote.git(&["symbolic-ref", "HEAD", "refs/heads/develop"]);
+        let clone = TempRepo::clone_from(&remote);
+        assert_eq!(default_branch(clone.path()).unwrap(), "develop");
+    }
+}
@@ -0,0 +1,174 @@
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppError;
+use crate::git;
+use crate::process::RunOptions;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CommitRequest {
+    pub message: String,
+    /// Replace the last commit; an empty message keeps its message
+    #[serde(default)]
+    pub amend: bool,
+    /// Add a `Signed-off-by` trailer
+    #[serde(default)]
+    pub sign_off: bool,
+    /// `Some(true)` signs (GPG or SSH, per `gpg.format`), `Some(false)` doesn't, `None` follows git config
+    #[serde(default)]
+    pub sign: Option<bool>,
+    /// `Name <email>` to record instead of the configured author
+    #[serde(default)]
+    pub author: Option<String>,
+    /// Skip the pre-commit and commit-msg hooks
+    #[serde(default)]
+    pub no_verify: bool,
+    #[serde(default)]
+    pub allow_empty: bool,
+    /// Commit only these paths, leaving anything else that's staged in the index
+    #[serde(default)]
+    pub paths: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommitResult {
+    pub sha: String,
+    pub summary: String,
+    /// What git and the hooks printed
+    pub output: String,
+}
+
+/// Create a commit. On failure the error carries git's and the hooks' output.
+pub fn commit(worktree_path: &Path, request: &CommitRequest) -> Result<CommitResult, AppError> {
+    if request.message.trim().is_empty() && !request.amend {
+        return Err(AppError::invalid("Commit message cannot be empty"));
+    }
+
+    let mut args = vec!["commit".to_string()];
+    if request.message.trim().is_empty() {
+        args.push("--no-edit".to_string());
+    } else {
+        args.extend(["--file".to_string(), "-".to_string()]);
+    }
+    if request.amend {
+        args.push("--amend".to_string());
+    }
+    if request.sign_off {
+        args.push("--signoff".to_string());
+    }
+    match request.sign {
+        Some(true) => args.push("--gpg-sign".to_string()),
+        Some(false) => args.push("--no-gpg-sign".to_string()),
+        None => {}
+    }
+    if let Some(author) = &request.author {
+        args.push(format!("--author={}", author));
+    }
+    if request.no_verify {
+        args.push("--no-verify".to_string());
+    }
+    if request.allow_empty {
+        args.push("--allow-empty".to_string());
+    }
+    if let Some(paths) = &request.paths {
+        if paths.is_empty() {
+            return Err(AppError::invalid("No paths selected"));
+        }
+        args.push("--only".to_string());
+        args.push("--".to_string());
+        args.extend(paths.iter().cloned());
+    }
+
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let options = RunOptions { stdin: Some(request.message.as_bytes().to_vec()), ..Default::default() };
+    let result = git::output(worktree_path, &args, options).map_err(|e| e.context("Failed to commit"))?;
+
+    // Hooks write to stderr; "nothing to commit" and friends go to stdout
+    let output = format!(
+        "{}{}",
+        String::from_utf8_lossy(&result.stderr),
+        String::from_utf8_lossy(&result.stdout)
+    );
+    if !result.status.success() {
+        return Err(git::git_error(worktree_path, "commit", &output, result.status.code()));
+    }
+
+    let head = git::run_text(worktree_path, &["log", "-1", "--format=%H%x00%s"])?;
+    let (sha, summary) = head.split_once('\0').unwrap_or((&head, ""));
+
+    Ok(CommitResult {
+        sha: sha.to_string(),
+        summary: summary.to_string(),
+        output,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorKind;
+    use crate::git::fixture::TempRepo;
+
+    fn request(message: &str) -> CommitRequest {
+        CommitRequest {
+            message: message.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_commit_options() {
+        let repo = TempRepo::new();
+        repo.write("a.txt", "a\n");
+        repo.write("b.txt", "b\n");
+        repo.git(&["add", "-A"]);
+
+        let first = commit(repo.path(), &CommitRequest {
+            author: Some("Someone Else <else@example.com>".to_string()),
+            sign_off: true,
+            sign: Some(false),
+            paths: Some(vec!["a.txt".to_string()]),
+            ..request("Add a\n\nWith a body")
+        })
+        .unwrap();
+        assert_eq!(first.summary, "Add a");
+        assert_eq!(repo.git(&["rev-parse", "HEAD"]).trim(), first.sha);
+        assert_eq!(repo.git(&["log", "-1", "--format=%an"]).trim(), "Someone Else");
+        assert!(repo.git(&["log", "-1", "--format=%b"]).contains("Signed-off-by: Test <test@example.com>"));
+        // b.txt stays staged
+        assert_eq!(repo.git(&["diff", "--cached", "--name-only"]).trim(), "b.txt");
+
+        let amended = commit(repo.path(), &CommitRequest { amend: true, ..request("") }).unwrap();
+        assert_eq!(amended.summary, "Add a");
+        assert_ne!(amended.sha, first.sha);
+        assert_eq!(repo.git(&["show", "--name-only", "--format=", "HEAD"]).trim(), "a.txt\nb.txt");
+
+        assert!(commit(repo.path(), &request("Nothing")).unwrap_err().to_string().contains("nothing to commit"));
+        commit(repo.path(), &CommitRequest { allow_empty: true, ..request("Empty") }).unwrap();
+        assert!(commit(repo.path(), &request("  ")).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hook_output_is_returned() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let repo = TempRepo::new();
+        let hook = repo.path().join(".git/hooks/pre-commit");
+        std::fs::write(&hook, "#!/bin/sh\necho 'lint: 3 problems found'\nexit 1\n").unwrap();
+        std::fs::set_permissions(&hook, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        repo.write("a.txt", "a\n");
+        repo.git(&["add", "-A"]);
+        let error = commit(repo.path(), &request("Blocked")).unwrap_err();
+        match &error.kind {
+            ErrorKind::GitFailed { command, stderr, exit_code } => {
+                assert_eq!((command.as_str(), *exit_code), ("commit", Some(1)));
+                assert!(stderr.contains("lint: 3 problems found"), "{}", stderr);
+            }
+            other => panic!("unexpected error kind {:?}", other),
+        }
+
+        commit(repo.path(), &CommitRequest { no_verify: true, ..request("Skipped hooks") }).unwrap();
+    }
+}
@@ -0,0 +1,230 @@
+use std::collections::BTreeSet;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
+
+use crate::error::AppError;
+use crate::git;
+use crate::git::diff::{tree_diff, FileDiff};
+use crate::git::snapshot::worktree_tree;
+use crate::git::status::{read_status, FileStatus};
+
+/// Reported as the command when a comparison is cancelled between git calls
+const COMPARE: &str = "worktree comparison";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorktreeComparison {
+    pub a: WorktreeSide,
+    pub b: WorktreeSide,
+    /// Common ancestor of the two branch tips
+    pub merge_base: Option<String>,
+    /// Commits on `a`'s tip that aren't on `b`'s, and the reverse
+    pub a_ahead: u32,
+    pub b_ahead: u32,
+    /// Every file whose contents differ between the two worktrees
+    pub files: Vec<FileComparison>,
+    /// Diffs from `a` to `b`, uncommitted and untracked files included
+    pub diffs: Vec<FileDiff>,
+    /// How alike the two worktrees' changes since the merge base are: 1.0 identical, 0.0 unrelated
+    pub similarity: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorktreeSide {
+    pub path: String,
+    pub branch: String,
+    pub head: Option<String>,
+    /// Files with staged or unstaged changes
+    pub uncommitted: usize,
+    pub untracked: usize,
+    /// Lines changed since the merge base, committed or not
+    pub changed_lines: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileComparison {
+    pub path: String,
+    /// What happened to the file going from `a` to `b`
+    pub status: FileStatus,
+    pub additions: u32,
+    pub deletions: u32,
+    /// Which worktrees changed the file since the merge base
+    pub changed_in_a: bool,
+    pub changed_in_b: bool,
+}
+
+/// Diff the full state of two worktrees of the same repository, and how they diverged.
+/// Stops between steps, killing git, once `cancel` fires.
+pub fn compare_worktrees(
+    a: &Path,
+    b: &Path,
+    context_lines: u32,
+    cancel: &CancellationToken,
+) -> Result<WorktreeComparison, AppError> {
+    if git::common_dir(a)? != git::common_dir(b)? {
+        return Err(AppError::invalid("Both worktrees must belong to the same repository"));
+    }
+
+    let tree_a = worktree_tree(a, &[])?;
+    git::check_cancelled(cancel, COMPARE)?;
+    let tree_b = worktree_tree(b, &[])?;
+    git::check_cancelled(cancel, COMPARE)?;
+    let head_a = head(a);
+    let head_b = head(b);
+
+    let merge_base = match (&head_a, &head_b) {
+        (Some(head_a), Some(head_b)) => git::run_text(a, &["merge-base", head_a, head_b]).ok(),
+        _ => None,
+    };
+    let (a_ahead, b_ahead) = match (&head_a, &head_b) {
+        (Some(head_a), Some(head_b)) => {
+            let range = format!("{}...{}", head_a, head_b);
+            let counts = git::run_cancellable(a, &["rev-list", "--left-right", "--count", &range, "--"], cancel)?;
+            let counts = String::from_utf8_lossy(&counts);
+            let counts = counts.trim();
+            let (left, right) = counts.split_once('\t').unwrap_or(("0", "0"));
+            (left.parse().unwrap_or(0), right.parse().unwrap_or(0))
+        }
+        _ => (0, 0),
+    };
+    let base_tree = match &merge_base {
+        Some(merge_base) => git::run_text(a, &["rev-parse", &format!("{}^{{tree}}", merge_base)])?,
+        None => git::run_text(a, &["hash-object", "-t", "tree", "/dev/null"])?,
+    };
+
+    let (changed_a, paths_a) = changed_lines(a, &base_tree, &tree_a, cancel)?;
+    let (changed_b, paths_b) = changed_lines(a, &base_tree, &tree_b, cancel)?;
+    let (between, _) = changed_lines(a, &tree_a, &tree_b, cancel)?;
+
+    let diffs = tree_diff(a, &tree_a, &tree_b, context_lines, cancel)?;
+    let files = diffs
+        .iter()
+        .map(|diff| FileComparison {
+            path: diff.path.clone(),
+            status: diff.status,
+            additions: diff.additions,
+            deletions: diff.deletions,
+            changed_in_a: paths_a.contains(&diff.path)
+                || diff.orig_path.as_ref().is_some_and(|path| paths_a.contains(path)),
+            changed_in_b: paths_b.contains(&diff.path),
+        })
+        .collect();
+
+    let side_a = side(a, head_a, changed_a)?;
+    git::check_cancelled(cancel, COMPARE)?;
+    Ok(WorktreeComparison {
+        a: side_a,
+        b: side(b, head_b, changed_b)?,
+        merge_base,
+        a_ahead,
+        b_ahead,
+        files,
+        diffs,
+        similarity: similarity(changed_a, changed_b, between),
+    })
+}
+
+/// 1 minus the lines that differ between the two results, relative to all lines either side changed
+fn similarity(changed_a: u64, changed_b: u64, between: u64) -> f64 {
+    let total = changed_a + changed_b;
+    if total == 0 {
+        return if between == 0 { 1.0 } else { 0.0 };
+    }
+    (1.0 - between as f64 / total as f64).clamp(0.0, 1.0)
+}
+
+fn head(worktree_path: &Path) -> Option<String> {
+    git::run_text(worktree_path, &["rev-parse", "--verify", "-q", "HEAD"]).ok()
+}
+
+fn side(worktree_path: &Path, head: Option<String>, changed_lines: u64) -> Result<WorktreeSide, AppError> {
+    let status = read_status(worktree_path)?;
+    let uncommitted: BTreeSet<&str> = status.staged.iter()
+        .chain(&status.unstaged)
+        .chain(&status.conflicted)
+        .map(|file| file.path.as_str())
+        .collect();
+
+    Ok(WorktreeSide {
+        path: worktree_path.display().to_string(),
+        branch: status.branch.clone(),
+        head,
+        uncommitted: uncommitted.len(),
+        untracked: status.untracked.len(),
+        changed_lines,
+    })
+}
+
+/// Lines added plus removed between two trees (a binary file counts as one), and the paths involved
+fn changed_lines(
+    repo: &Path,
+    from_tree: &str,
+    to_tree: &str,
+    cancel: &CancellationToken,
+) -> Result<(u64, BTreeSet<String>), AppError> {
+    let args = ["diff", "--numstat", "-z", "--no-renames", from_tree, to_tree, "--"];
+    let output = git::run_cancellable(repo, &args, cancel)?;
+    let mut total = 0;
+    let mut paths = BTreeSet::new();
+    for record in output.split(|b| *b == 0).filter(|record| !record.is_empty()) {
+        // <added> TAB <deleted> TAB <path>
+        let record = String::from_utf8_lossy(record);
+        let mut fields = record.splitn(3, '\t');
+        let (Some(added), Some(deleted), Some(path)) = (fields.next(), fields.next(), fields.next()) else {
+            continue;
+        };
+        total += match (added.parse::<u64>(), deleted.parse::<u64>()) {
+            (Ok(added), Ok(deleted)) => added + deleted,
+            _ => 1,
+        };
+        paths.insert(path.to_string());
+    }
+    Ok((total, paths))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::fixture::TempRepo;
+
+    #[test]
+    fn test_similarity() {
+        assert_eq!(similarity(0, 0, 0), 1.0);
+        assert_eq!(similarity(4, 4, 0), 1.0);
+        assert_eq!(similarity(4, 4, 8), 0.0);
+        assert_eq!(similarity(4, 4, 2), 0.75);
+    }
+
+    #[test]
+    fn test_compare_parallel_attempts() {
+        let repo = TempRepo::new();
+        repo.write("lib.rs", "fn main() {}\n");
+        repo.write("README.md", "readme\n");
+        repo.commit_all("initial");
+
+        let attempt = repo.add_worktree("attempt");
+
+        // Same committed fix in both, then each goes its own way uncommitted
+        repo.write("lib.rs", "fn main() { fix(); }\n");
+        repo.commit_all("fix");
+        attempt.write("lib.rs", "fn main() { fix(); }\n");
+        attempt.commit_all("fix too");
+        repo.write("README.md", "readme\nmain notes\n");
+        attempt.write("extra.txt", "untracked\n");
+
+        let comparison = compare_worktrees(repo.path(), attempt.path(), 3, &CancellationToken::new()).unwrap();
+        assert_eq!(comparison.a.branch, "main");
+        assert_eq!(comparison.b.branch, "attempt");
+        assert_eq!((comparison.a_ahead, comparison.b_ahead), (1, 1));
+        assert_eq!((comparison.a.uncommitted, comparison.a.untracked), (1, 0));
+        assert_eq!((comparison.b.uncommitted, comparison.b.untracked), (0, 1));
+
+        let files: Vec<_> = comparison.files.iter().map(|file| (file.path.as_str(), file.status)).collect();
+        assert_eq!(files, vec![("README.md", FileStatus::Modified), ("extra.txt", FileStatus::Added)]);
+        assert!(comparison.files[0].changed_in_a && !comparison.files[0].changed_in_b);
+        assert!(!comparison.files[1].changed_in_a && comparison.files[1].changed_in_b);
+        assert_eq!(comparison.diffs.len(), 2);
+        // lib.rs is the same on both sides: 4 of 6 changed lines agree
+        assert!((comparison.similarity - 4.0 / 6.0).abs() < 1e-9, "{}", comparison.similarity);
+    }
+}
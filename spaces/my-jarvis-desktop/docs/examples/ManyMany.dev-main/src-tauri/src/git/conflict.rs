@@ -0,0 +1,371 @@
+use std::fs;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppError;
+use crate::git::{self, is_binary};
+use crate::git::operation::conflicted_paths;
+use crate::git::status::ConflictKind;
+
+/// Conflict markers are this many characters, unless `conflict-marker-size` says otherwise
+const MARKER_SIZE: usize = 7;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConflictDetails {
+    pub path: String,
+    pub kind: ConflictKind,
+    /// Contents of each index stage; `None` when that side has no file or it's binary
+    pub base: Option<String>,
+    pub ours: Option<String>,
+    pub theirs: Option<String>,
+    pub binary: bool,
+    /// The file as it is in the worktree, markers included
+    pub merged: Option<String>,
+    pub regions: Vec<ConflictRegion>,
+}
+
+/// One `<<<<<<<` ... `>>>>>>>` block of the worktree file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConflictRegion {
+    /// 1-based lines of the opening and closing markers
+    pub start_line: usize,
+    pub end_line: usize,
+    pub ours_label: String,
+    pub theirs_label: String,
+    pub ours: String,
+    /// Only present with the `diff3` or `zdiff3` conflict style
+    pub base: Option<String>,
+    pub theirs: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Resolution {
+    Ours,
+    Theirs,
+    /// Both sides' lines, ours first, without markers
+    Union,
+    /// Hand-edited result
+    Content { content: String },
+}
+
+/// Stage contents and parsed conflict regions of an unmerged path
+pub fn get_conflict(worktree_path: &Path, path: &str) -> Result<ConflictDetails, AppError> {
+    let stages = read_stages(worktree_path, path)?;
+    let kind = conflict_kind(&stages)?;
+
+    let mut contents = [None, None, None];
+    let mut binary = false;
+    for (content, stage) in contents.iter_mut().zip(&stages) {
+        if let Some(oid) = stage {
+            let blob = git::run(worktree_path, &["cat-file", "blob", oid])?;
+            if is_binary(&blob) {
+                binary = true;
+            } else {
+                *content = Some(String::from_utf8_lossy(&blob).into_owned());
+            }
+        }
+    }
+
+    let merged = fs::read(worktree_path.join(path))
+        .ok()
+        .filter(|bytes| !is_binary(bytes))
+        .map(|bytes| String::from_utf8_lossy(&bytes).into_owned());
+    let regions = merged.as_deref().map(parse_conflict_regions).unwrap_or_default();
+    let [base, ours, theirs] = contents;
+
+    Ok(ConflictDetails {
+        path: path.to_string(),
+        kind,
+        base,
+        ours,
+        theirs,
+        binary,
+        merged,
+        regions,
+    })
+}
+
+/// Resolve `path` and stage the result. Returns the paths still in conflict.
+/// `path` is a literal index path, never a pattern or a directory.
+pub fn resolve_conflict(worktree_path: &Path, path: &str, resolution: &Resolution) -> Result<Vec<String>, AppError> {
+    // Also makes sure `path` is an unmerged index entry before anything is written to it
+    let [base, ours, theirs] = read_stages(worktree_path, path)?;
+    let file = worktree_path.join(path);
+
+    match resolution {
+        Resolution::Ours | Resolution::Theirs => {
+            let (side, flag) = match resolution {
+                Resolution::Ours => (&ours, "--ours"),
+                _ => (&theirs, "--theirs"),
+            };
+            if side.is_some() {
+                git::run(worktree_path, &["--literal-pathspecs", "checkout", flag, "--", path])?;
+                git::run(worktree_path, &["--literal-pathspecs", "add", "--", path])?;
+            } else {
+                // That side deleted the file
+                git::run(worktree_path, &["--literal-pathspecs", "rm", "-q", "--cached", "--", path])?;
+                if file.exists() {
+                    fs::remove_file(&file).map_err(|e| AppError::io(format!("Failed to remove {}", path), e))?;
+                }
+            }
+        }
+        Resolution::Union => {
+            let merged = union_merge(worktree_path, base.as_deref(), ours.as_deref(), theirs.as_deref())?;
+            fs::write(&file, merged).map_err(|e| AppError::io(format!("Failed to write {}", path), e))?;
+            git::run(worktree_path, &["--literal-pathspecs", "add", "--", path])?;
+        }
+        Resolution::Content { content } => {
+            fs::write(&file, content).map_err(|e| AppError::io(format!("Failed to write {}", path), e))?;
+            git::run(worktree_path, &["--literal-pathspecs", "add", "--", path])?;
+        }
+    }
+
+    conflicted_paths(worktree_path)
+}
+
+/// Blob ids of stages 1 (base), 2 (ours) and 3 (theirs) of exactly `path`
+fn read_stages(worktree_path: &Path, path: &str) -> Result<[Option<String>; 3], AppError> {
+    let output = git::run(worktree_path, &["--literal-pathspecs", "ls-files", "-u", "-z", "--", path])?;
+    let mut stages = [None, None, None];
+    for record in output.split(|b| *b == 0).filter(|record| !record.is_empty()) {
+        // <mode> SP <oid> SP <stage> TAB <path>
+        let record = String::from_utf8_lossy(record);
+        let (info, record_path) = record.split_once('\t').unwrap_or((&record, ""));
+        // A literal pathspec still matches everything under a directory
+        if record_path != path {
+            continue;
+        }
+        let fields: Vec<&str> = info.split(' ').collect();
+        if let [_, oid, stage] = fields.as_slice() {
+            if let Ok(stage @ 1..=3) = stage.parse::<usize>() {
+                stages[stage - 1] = Some(oid.to_string());
+            }
+        }
+    }
+
+    if stages.iter().all(Option::is_none) {
+        return Err(AppError::invalid(format!("{} is not in conflict", path)));
+    }
+    Ok(stages)
+}
+
+fn conflict_kind(stages: &[Option<String>; 3]) -> Result<ConflictKind, AppError> {
+    Ok(match (stages[0].is_some(), stages[1].is_some(), stages[2].is_some()) {
+        (true, true, true) => ConflictKind::BothModified,
+        (false, true, true) => ConflictKind::BothAdded,
+        (true, false, false) => ConflictKind::BothDeleted,
+        (false, true, false) => ConflictKind::AddedByUs,
+        (false, false, true) => ConflictKind::AddedByThem,
+        (true, false, true) => ConflictKind::DeletedByUs,
+        (true, true, false) => ConflictKind::DeletedByThem,
+        (false, false, false) => return Err(AppError::other("No conflict stages")),
+    })
+}
+
+/// `git merge-file --union` over the three stages; a missing side counts as empty
+fn union_merge(
+    worktree_path: &Path,
+    base: Option<&str>,
+    ours: Option<&str>,
+    theirs: Option<&str>,
+) -> Result<Vec<u8>, AppError> {
+    let scratch = std::env::temp_dir().join(format!("manymany-merge-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&scratch).map_err(|e| AppError::io("Failed to create temp dir", e))?;
+
+    let result = (|| {
+        let mut files = Vec::new();
+        for (name, oid) in [("ours", ours), ("base", base), ("theirs", theirs)] {
+            let content = match oid {
+                Some(oid) => git::run(worktree_path, &["cat-file", "blob", oid])?,
+                None => Vec::new(),
+            };
+            if is_binary(&content) {
+                return Err(AppError::other("Binary files can't be union merged"));
+            }
+            let file = scratch.join(name);
+            fs::write(&file, content).map_err(|e| AppError::io(format!("Failed to write {}", name), e))?;
+            files.push(file.to_string_lossy().into_owned());
+        }
+        // Exit code is the number of conflicts, which --union resolves
+        git::run_allowing(
+            worktree_path,
+            &["merge-file", "-p", "--union", &files[0], &files[1], &files[2]],
+            &(1..=127).collect::<Vec<_>>(),
+        )
+    })();
+
+    let _ = fs::remove_dir_all(&scratch);
+    result
+}
+
+/// Parse conflict marker blocks, in the `merge` or `diff3` style
+pub fn parse_conflict_regions(content: &str) -> Vec<ConflictRegion> {
+    #[derive(PartialEq)]
+    enum Section {
+        Ours,
+        Base,
+        Theirs,
+    }
+
+    let marker = |line: &str, ch: char| -> Option<String> {
+        let rest = line.strip_prefix(&ch.to_string().repeat(MARKER_SIZE))?;
+        if rest.starts_with(ch) {
+            return None;
+        }
+        if rest.is_empty() || rest.starts_with(' ') {
+            return Some(rest.trim().to_string());
+        }
+        None
+    };
+
+    let mut regions = Vec::new();
+    let mut current: Option<(ConflictRegion, Section)> = None;
+    for (index, line) in content.split_inclusive('\n').enumerate() {
+        let bare = line.trim_end_matches(['\n', '\r']);
+        let line_number = index + 1;
+
+        match current.as_mut() {
+            None => {
+                if let Some(label) = marker(bare, '<') {
+                    current = Some((
+                        ConflictRegion {
+                            start_line: line_number,
+                            end_line: line_number,
+                            ours_label: label,
+                            theirs_label: String::new(),
+                            ours: String::new(),
+                            base: None,
+                            theirs: String::new(),
+                        },
+                        Section::Ours,
+                    ));
+                }
+            }
+            Some((region, section)) => {
+                if *section == Section::Ours && marker(bare, '|').is_some() {
+                    region.base = Some(String::new());
+                    *section = Section::Base;
+                } else if *section != Section::Theirs && marker(bare, '=').is_some_and(|rest| rest.is_empty()) {
+                    *section = Section::Theirs;
+                } else if *section == Section::Theirs && marker(bare, '>').is_some() {
+                    region.theirs_label = marker(bare, '>').unwrap_or_default();
+                    region.end_line = line_number;
+                    regions.push(current.take().unwrap().0);
+                } else {
+                    match section {
+                        Section::Ours => region.ours.push_str(line),
+                        Section::Base => region.base.get_or_insert_with(String::new).push_str(line),
+                        Section::Theirs => region.theirs.push_str(line),
+                    }
+                }
+            }
+        }
+    }
+
+    regions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::fixture::TempRepo;
+
+    /// A merge of `theirs` into `main` that conflicts in `text.txt`, and in `gone.txt`
+    /// which `theirs` deleted
+    fn conflicted_repo() -> TempRepo {
+        let repo = TempRepo::new();
+        repo.git(&["config", "merge.conflictStyle", "diff3"]);
+        repo.write("text.txt", "one\nbase\nthree\n");
+        repo.write("gone.txt", "kept?\n");
+        repo.commit_all("initial");
+
+        repo.git(&["checkout", "-q", "-b", "theirs"]);
+        repo.write("text.txt", "one\ntheirs\nthree\n");
+        repo.git(&["rm", "-q", "gone.txt"]);
+        repo.commit_all("their change");
+
+        repo.git(&["checkout", "-q", "main"]);
+        repo.write("text.txt", "one\nours\nthree\n");
+        repo.write("gone.txt", "kept and changed\n");
+        repo.commit_all("our change");
+
+        let _ = std::process::Command::new("git")
+            .args(["-C", repo.path().to_str().unwrap(), "merge", "-q", "theirs"])
+            .output();
+        repo
+    }
+
+    #[test]
+    fn test_parse_conflict_regions() {
+        let content = "before\n<<<<<<< HEAD\nours 1\nours 2\n||||||| base\nbase\n=======\ntheirs\n>>>>>>> feature\nbetween\n<<<<<<< HEAD\n=======\nonly theirs\n>>>>>>> feature\n";
+        let regions = parse_conflict_regions(content);
+        assert_eq!(regions.len(), 2);
+        assert_eq!((regions[0].start_line, regions[0].end_line), (2, 9));
+        assert_eq!(regions[0].ours, "ours 1\nours 2\n");
+        assert_eq!(regions[0].base.as_deref(), Some("base\n"));
+        assert_eq!(regions[0].theirs, "theirs\n");
+        assert_eq!((regions[0].ours_label.as_str(), regions[0].theirs_label.as_str()), ("HEAD", "feature"));
+        assert_eq!(regions[1].ours, "");
+        assert_eq!(regions[1].base, None);
+        assert_eq!(regions[1].theirs, "only theirs\n");
+
+        // Not markers: too long, or not at the start of a line
+        assert!(parse_conflict_regions("<<<<<<<< x\n=======\n>>>>>>> y\n").is_empty());
+        assert!(parse_conflict_regions(" <<<<<<< x\n").is_empty());
+    }
+
+    #[test]
+    fn test_get_conflict_from_merge() {
+        let repo = conflicted_repo();
+
+        let text = get_conflict(repo.path(), "text.txt").unwrap();
+        assert_eq!(text.kind, ConflictKind::BothModified);
+        assert_eq!(text.base.as_deref(), Some("one\nbase\nthree\n"));
+        assert_eq!(text.ours.as_deref(), Some("one\nours\nthree\n"));
+        assert_eq!(text.theirs.as_deref(), Some("one\ntheirs\nthree\n"));
+        assert_eq!(text.regions.len(), 1);
+        assert_eq!(text.regions[0].ours, "ours\n");
+        assert_eq!(text.regions[0].base.as_deref(), Some("base\n"));
+        assert_eq!(text.regions[0].theirs, "theirs\n");
+
+        let gone = get_conflict(repo.path(), "gone.txt").unwrap();
+        assert_eq!(gone.kind, ConflictKind::DeletedByThem);
+        assert_eq!(gone.theirs, None);
+        assert!(gone.regions.is_empty());
+
+        assert!(get_conflict(repo.path(), "nope.txt").unwrap_err().to_string().contains("not in conflict"));
+    }
+
+    #[test]
+    fn test_paths_are_not_patterns() {
+        let repo = conflicted_repo();
+        for path in ["*.txt", "", ".", "../text.txt"] {
+            assert!(get_conflict(repo.path(), path).is_err(), "{:?}", path);
+            assert!(resolve_conflict(repo.path(), path, &Resolution::Content { content: "x\n".to_string() }).is_err());
+        }
+        assert!(!repo.path().join("*.txt").exists());
+        assert_eq!(conflicted_paths(repo.path()).unwrap(), vec!["gone.txt", "text.txt"]);
+    }
+
+    #[test]
+    fn test_resolve_conflicts() {
+        let repo = conflicted_repo();
+        let remaining = resolve_conflict(repo.path(), "text.txt", &Resolution::Union).unwrap();
+        assert_eq!(remaining, vec!["gone.txt"]);
+        assert_eq!(repo.read("text.txt"), "one\nours\ntheirs\nthree\n");
+        assert_eq!(repo.git(&["diff", "--cached", "--name-only", "--", "text.txt"]).trim(), "text.txt");
+
+        // Taking their side of a deletion deletes the file
+        let remaining = resolve_conflict(repo.path(), "gone.txt", &Resolution::Theirs).unwrap();
+        assert!(remaining.is_empty());
+        assert!(!repo.path().join("gone.txt").exists());
+
+        let repo = conflicted_repo();
+        resolve_conflict(repo.path(), "text.txt", &Resolution::Ours).unwrap();
+        assert_eq!(repo.read("text.txt"), "one\nours\nthree\n");
+        resolve_conflict(repo.path(), "gone.txt", &Resolution::Content { content: "edited\n".to_string() }).unwrap();
+        assert_eq!(repo.read("gone.txt"), "edited\n");
+        assert_eq!(repo.git(&["ls-files", "-u"]), "");
+    }
+}
@@ -0,0 +1,779 @@
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
+
+use crate::error::AppError;
+use crate::git;
+use crate::git::status::FileStatus;
+
+pub const DEFAULT_CONTEXT_LINES: u32 = 3;
+
+/// Patches bigger than this are summarized instead of returned line by line
+const MAX_PATCH_BYTES: usize = 1024 * 1024;
+
+/// Line pairs with more tokens than this are not word-diffed
+const MAX_WORD_DIFF_TOKENS: usize = 400;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileDiff {
+    pub path: String,
+    /// Source path of a rename or copy
+    pub orig_path: Option<String>,
+    pub status: FileStatus,
+    pub similarity: Option<u8>,
+    pub old_mode: Option<String>,
+    pub new_mode: Option<String>,
+    pub binary: bool,
+    /// The patch was over the size limit: `hunks` is empty but the counts are accurate
+    pub too_large: bool,
+    pub additions: u32,
+    pub deletions: u32,
+    pub hunks: Vec<DiffHunk>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DiffHunk {
+    /// The full `@@ -a,b +c,d @@ section` line
+    pub header: String,
+    pub old_start: u32,
+    pub old_lines: u32,
+    pub new_start: u32,
+    pub new_lines: u32,
+    pub lines: Vec<DiffLine>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LineKind {
+    Context,
+    Added,
+    Removed,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DiffLine {
+    pub kind: LineKind,
+    pub content: String,
+    pub old_line: Option<u32>,
+    pub new_line: Option<u32>,
+    /// Byte ranges of `content` that differ from the paired removed/added line
+    pub changes: Vec<Span>,
+    /// The line is followed by `\ No newline at end of file`
+    pub no_newline: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Everything that changed in a worktree
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorktreeDiff {
+    pub staged: Vec<FileDiff>,
+    /// Unstaged changes, with untracked files as additions
+    pub unstaged: Vec<FileDiff>,
+}
+
+/// Diff of one file, either staged (index vs HEAD) or unstaged (worktree vs index)
+pub fn file_diff(worktree_path: &Path, path: &str, staged: bool, context_lines: u32) -> Result<Option<FileDiff>, AppError> {
+    if !staged && is_untracked(worktree_path, path)? {
+        return untracked_diff(worktree_path, path).map(Some);
+    }
+
+    let context = format!("-U{}", context_lines);
+    let mut args = diff_args("diff", &context);
+    if staged {
+        args.push("--cached");
+    }
+    args.extend(["--", path]);
+    Ok(parse_diff(&git::run(worktree_path, &args)?)?.into_iter().next())
+}
+
+/// Staged and unstaged diffs of every changed file, with renames detected; stops early if `cancel` fires
+pub fn worktree_diff(worktree_path: &Path, context_lines: u32, cancel: &CancellationToken) -> Result<WorktreeDiff, AppError> {
+    let context = format!("-U{}", context_lines);
+
+    let mut staged_args = diff_args("diff", &context);
+    staged_args.push("--cached");
+    let staged = parse_diff(&git::run_cancellable(worktree_path, &staged_args, cancel)?)?;
+
+    let mut unstaged = parse_diff(&git::run_cancellable(worktree_path, &diff_args("diff", &context), cancel)?)?;
+    let untracked = git::run_cancellable(worktree_path, &["ls-files", "-z", "--others", "--exclude-standard"], cancel)?;
+    for path in untracked.split(|b| *b == 0).filter(|path| !path.is_empty()) {
+        git::check_cancelled(cancel, "git diff")?;
+        unstaged.push(untracked_diff(worktree_path, &String::from_utf8_lossy(path))?);
+    }
+
+    Ok(WorktreeDiff { staged, unstaged })
+}
+
+/// Changes a commit made, against its first parent
+pub fn commit_diff(worktree_path: &Path, sha: &str, context_lines: u32) -> Result<Vec<FileDiff>, AppError> {
+    let context = format!("-U{}", context_lines);
+    let mut args = diff_args("show", &context);
+    args.extend(["--format=", "--diff-merges=first-parent", sha, "--"]);
+    parse_diff(&git::run(worktree_path, &args)?)
+}
+
+/// Differences between two trees, e.g. from [`crate::git::snapshot::worktree_tree`]
+pub fn tree_diff(
+    repo: &Path,
+    from_tree: &str,
+    to_tree: &str,
+    context_lines: u32,
+    cancel: &CancellationToken,
+) -> Result<Vec<FileDiff>, AppError> {
+    let context = format!("-U{}", context_lines);
+    let mut args = diff_args("diff", &context);
+    args.extend([from_tree, to_tree, "--"]);
+    parse_diff(&git::run_cancellable(repo, &args, cancel)?)
+}
+
+/// Changes a stash entry holds relative to the commit it was made on, untracked files included
+pub fn stash_diff(worktree_path: &Path, sha: &str, context_lines: u32) -> Result<Vec<FileDiff>, AppError> {
+    let context = format!("-U{}", context_lines);
+    let base = format!("{}^1", sha);
+    let mut args = diff_args("diff", &context);
+    args.extend([base.as_str(), sha, "--"]);
+    let mut files = parse_diff(&git::run(worktree_path, &args)?)?;
+
+    // Untracked files are kept in a parentless third commit
+    let untracked = format!("{}^3", sha);
+    if git::run(worktree_path, &["rev-parse", "--verify", "-q", &untracked]).is_ok() {
+        let empty_tree = git::run_text(worktree_path, &["hash-object", "-t", "tree", "/dev/null"])?;
+        let mut args = diff_args("diff", &context);
+        args.extend([empty_tree.as_str(), untracked.as_str(), "--"]);
+        files.extend(parse_diff(&git::run(worktree_path, &args)?)?);
+    }
+    Ok(files)
+}
+
+/// Arguments that make `git diff` output predictable regardless of user config
+fn diff_args<'a>(command: &'a str, context: &'a str) -> Vec<&'a str> {
+    vec![
+        "-c", "core.quotePath=false",
+        command, "--no-color", "--no-ext-diff", "--find-renames",
+        "--src-prefix=a/", "--dst-prefix=b/", context,
+    ]
+}
+
+fn is_untracked(worktree_path: &Path, path: &str) -> Result<bool, AppError> {
+    let listed = git::run(worktree_path, &["ls-files", "--others", "--exclude-standard", "--", path])?;
+    Ok(!listed.is_empty())
+}
+
+/// An untracked file as an addition, the way `git diff --no-index /dev/null <path>` reports it.
+/// Built from the file itself rather than a git process per file; oversized files are only
+/// streamed to count their lines, and binary ones are never read past the sniffed prefix.
+fn untracked_diff(worktree_path: &Path, path: &str) -> Result<FileDiff, AppError> {
+    let full_path = worktree_path.join(path);
+    let read_error = |e| AppError::io(format!("Failed to read {}", path), e);
+    let metadata = fs::symlink_metadata(&full_path).map_err(read_error)?;
+
+    let mut file = FileDiff {
+        path: path.to_string(),
+        orig_path: None,
+        status: FileStatus::Added,
+        similarity: None,
+        old_mode: None,
+        new_mode: Some(file_mode(&metadata).to_string()),
+        binary: false,
+        too_large: false,
+        additions: 0,
+        deletions: 0,
+        hunks: Vec::new(),
+    };
+
+    // A symlink's content is its target
+    let content = if metadata.file_type().is_symlink() {
+        fs::read_link(&full_path).map_err(read_error)?.to_string_lossy().into_owned().into_bytes()
+    } else {
+        let mut reader = fs::File::open(&full_path).map_err(read_error)?;
+        let mut head = Vec::new();
+        (&mut reader).take(8000).read_to_end(&mut head).map_err(read_error)?;
+        if git::is_binary(&head) {
+            file.binary = true;
+            return Ok(file);
+        }
+        if metadata.len() as usize > MAX_PATCH_BYTES {
+            file.too_large = true;
+            file.additions = count_lines(&head, reader).map_err(read_error)?;
+            return Ok(file);
+        }
+        reader.read_to_end(&mut head).map_err(read_error)?;
+        head
+    };
+    if content.is_empty() {
+        return Ok(file);
+    }
+
+    let text = String::from_utf8_lossy(&content);
+    let no_newline = !text.ends_with('\n');
+    let lines: Vec<&str> = text.strip_suffix('\n').unwrap_or(&text).split('\n').collect();
+    let count = lines.len() as u32;
+    let mut hunk = DiffHunk {
+        header: if count == 1 { "@@ -0,0 +1 @@".to_string() } else { format!("@@ -0,0 +1,{} @@", count) },
+        old_start: 0,
+        old_lines: 0,
+        new_start: 1,
+        new_lines: count,
+        lines: lines.iter().zip(1..).map(|(line, number)| DiffLine {
+            kind: LineKind::Added,
+            content: line.to_string(),
+            old_line: None,
+            new_line: Some(number),
+            changes: Vec::new(),
+            no_newline: false,
+        }).collect(),
+    };
+    if let Some(last) = hunk.lines.last_mut() {
+        last.no_newline = no_newline;
+    }
+    file.additions = count;
+    file.hunks.push(hunk);
+    Ok(file)
+}
+
+/// Git's mode for a new file: symlink, executable or regular
+fn file_mode(metadata: &fs::Metadata) -> &'static str {
+    if metadata.file_type().is_symlink() {
+        return "120000";
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if metadata.permissions().mode() & 0o111 != 0 {
+            return "100755";
+        }
+    }
+    "100644"
+}
+
+/// Lines in `head` followed by the rest of `reader`, counting an unterminated last line
+fn count_lines(head: &[u8], mut reader: impl Read) -> std::io::Result<u32> {
+    let mut lines = head.iter().filter(|b| **b == b'\n').count();
+    let mut last = head.last().copied();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        lines += buffer[..read].iter().filter(|b| **b == b'\n').count();
+        last = Some(buffer[read - 1]);
+    }
+    if last.is_some_and(|b| b != b'\n') {
+        lines += 1;
+    }
+    Ok(lines as u32)
+}
+
+/// Parse the output of `git diff` into per-file diffs
+pub fn parse_diff(patch: &[u8]) -> Result<Vec<FileDiff>, AppError> {
+    let text = String::from_utf8_lossy(patch);
+    let mut files: Vec<FileDiff> = Vec::new();
+    let mut file_bytes = 0;
+    // Lines still expected in the current hunk (old side, new side)
+    let mut remaining = (0u32, 0u32);
+    let mut line_numbers = (0u32, 0u32);
+
+    let mut lines = text.split('\n').peekable();
+    while let Some(line) = lines.next() {
+        if line.is_empty() && lines.peek().is_none() {
+            break;
+        }
+
+        if remaining.0 > 0 || remaining.1 > 0 {
+            let file = files.last_mut().ok_or_else(|| AppError::other("Hunk outside of a file"))?;
+            let hunk = file.hunks.last_mut().ok_or_else(|| AppError::other("Hunk line outside of a hunk"))?;
+            file_bytes += line.len() + 1;
+
+            let (kind, old_line, new_line) = match line.chars().next() {
+                Some(' ') | None => {
+                    remaining = (remaining.0.saturating_sub(1), remaining.1.saturating_sub(1));
+                    line_numbers = (line_numbers.0 + 1, line_numbers.1 + 1);
+                    (LineKind::Context, Some(line_numbers.0), Some(line_numbers.1))
+                }
+                Some('-') => {
+                    remaining.0 = remaining.0.saturating_sub(1);
+                    line_numbers.0 += 1;
+                    file.deletions += 1;
+                    (LineKind::Removed, Some(line_numbers.0), None)
+                }
+                Some('+') => {
+                    remaining.1 = remaining.1.saturating_sub(1);
+                    line_numbers.1 += 1;
+                    file.additions += 1;
+                    (LineKind::Added, None, Some(line_numbers.1))
+                }
+                Some('\\') => {
+                    if let Some(last) = hunk.lines.last_mut() {
+                        last.no_newline = true;
+                    }
+                    continue;
+                }
+                _ => return Err(AppError::other(format!("Unexpected line in hunk: {}", line))),
+            };
+
+            hunk.lines.push(DiffLine {
+                kind,
+                content: line.get(1..).unwrap_or("").to_string(),
+                old_line,
+                new_line,
+                changes: Vec::new(),
+                no_newline: false,
+            });
+            continue;
+        }
+
+        if let Some(rest) = line.strip_prefix("diff --git ") {
+            finish_file(files.last_mut(), file_bytes);
+            file_bytes = 0;
+            let (old_path, new_path) = parse_git_header_paths(rest);
+            files.push(FileDiff {
+                path: new_path.or_else(|| old_path.clone()).unwrap_or_default(),
+                orig_path: None,
+                status: FileStatus::Modified,
+                similarity: None,
+                old_mode: None,
+                new_mode: None,
+                binary: false,
+                too_large: false,
+                additions: 0,
+                deletions: 0,
+                hunks: Vec::new(),
+            });
+            continue;
+        }
+
+        let file = match files.last_mut() {
+            Some(file) => file,
+            None => continue,
+        };
+
+        if line.starts_with("@@ ") {
+            let hunk = parse_hunk_header(line)?;
+            remaining = (hunk.old_lines, hunk.new_lines);
+            line_numbers = (hunk.old_start.saturating_sub(1), hunk.new_start.saturating_sub(1));
+            // A zero-length side starts *after* the given line
+            if hunk.old_lines == 0 {
+                line_numbers.0 = hunk.old_start;
+            }
+            if hunk.new_lines == 0 {
+                line_numbers.1 = hunk.new_start;
+            }
+            file.hunks.push(hunk);
+        } else if line.starts_with('\\') {
+            if let Some(last) = file.hunks.last_mut().and_then(|hunk| hunk.lines.last_mut()) {
+                last.no_newline = true;
+            }
+        } else if let Some(mode) = line.strip_prefix("new file mode ") {
+            file.status = FileStatus::Added;
+            file.new_mode = Some(mode.to_string());
+        } else if let Some(mode) = line.strip_prefix("deleted file mode ") {
+            file.status = FileStatus::Deleted;
+            file.old_mode = Some(mode.to_string());
+        } else if let Some(mode) = line.strip_prefix("old mode ") {
+            file.old_mode = Some(mode.to_string());
+        } else if let Some(mode) = line.strip_prefix("new mode ") {
+            file.new_mode = Some(mode.to_string());
+        } else if let Some(score) = line.strip_prefix("similarity index ") {
+            file.similarity = score.trim_end_matches('%').parse().ok();
+        } else if let Some(path) = line.strip_prefix("rename from ") {
+            file.status = FileStatus::Renamed;
+            file.orig_path = Some(unquote(path));
+        } else if let Some(path) = line.strip_prefix("copy from ") {
+            file.status = FileStatus::Copied;
+            file.orig_path = Some(unquote(path));
+        } else if let Some(path) = line.strip_prefix("rename to ").or_else(|| line.strip_prefix("copy to ")) {
+            file.path = unquote(path);
+        } else if let Some(path) = line.strip_prefix("+++ ") {
+            if let Some(path) = strip_patch_path(path, "b/") {
+                file.path = path;
+            }
+        } else if line.starts_with("Binary files ") || line == "GIT binary patch" {
+            file.binary = true;
+        }
+    }
+
+    finish_file(files.last_mut(), file_bytes);
+    Ok(files)
+}
+
+/// Either drop an oversized file's hunks or annotate its changed words
+fn finish_file(file: Option<&mut FileDiff>, bytes: usize) {
+    let file = match file {
+        Some(file) => file,
+        None => return,
+    };
+    if bytes > MAX_PATCH_BYTES {
+        file.too_large = true;
+        file.hunks.clear();
+        return;
+    }
+    for hunk in &mut file.hunks {
+        mark_word_changes(&mut hunk.lines);
+    }
+}
+
+/// `@@ -old_start[,old_lines] +new_start[,new_lines] @@ section`
+fn parse_hunk_header(line: &str) -> Result<DiffHunk, AppError> {
+    let invalid = || AppError::other(format!("Invalid hunk header: {}", line));
+    let ranges = line.strip_prefix("@@ ").and_then(|rest| rest.split(" @@").next()).ok_or_else(invalid)?;
+    let (old, new) = ranges.split_once(' ').ok_or_else(invalid)?;
+
+    let parse_range = |range: Option<&str>| -> Result<(u32, u32), AppError> {
+        let range = range.ok_or_else(invalid)?;
+        let (start, len) = range.split_once(',').unwrap_or((range, "1"));
+        Ok((start.parse().map_err(|_| invalid())?, len.parse().map_err(|_| invalid())?))
+    };
+    let (old_start, old_lines) = parse_range(old.strip_prefix('-'))?;
+    let (new_start, new_lines) = parse_range(new.strip_prefix('+'))?;
+
+    Ok(DiffHunk {
+        header: line.to_string(),
+        old_start,
+        old_lines,
+        new_start,
+        new_lines,
+        lines: Vec::new(),
+    })
+}
+
+/// Paths from `diff --git a/<old> b/<new>`. Only reliable when git quoted them or both are
+/// the same; renames and regular changes get their paths from later header lines anyway.
+fn parse_git_header_paths(rest: &str) -> (Option<String>, Option<String>) {
+    if rest.starts_with('"') {
+        let (old, after) = split_quoted(rest);
+        return (strip_patch_path(&old, "a/"), strip_patch_path(after.trim_start(), "b/"));
+    }
+    if let Some(index) = rest.find(" \"b/") {
+        return (strip_patch_path(&rest[..index], "a/"), strip_patch_path(&rest[index + 1..], "b/"));
+    }
+
+    // Unquoted: "a/<path> b/<path>" with the same path on both sides
+    let half = rest.len().saturating_sub(1) / 2;
+    match (rest.get(..half), rest.get(half + 1..)) {
+        (Some(old), Some(new)) if old.strip_prefix("a/").is_some() && old.get(2..) == new.get(2..) => {
+            (strip_patch_path(old, "a/"), strip_patch_path(new, "b/"))
+        }
+        _ => (None, None),
+    }
+}
+
+/// `a/path`, `"a/quoted\tpath"` or `/dev/null` from a patch header
+fn strip_patch_path(path: &str, prefix: &str) -> Option<String> {
+    // git appends a tab to ---/+++ paths containing spaces
+    let path = unquote(path.trim_end_matches('\t'));
+    if path == "/dev/null" {
+        return None;
+    }
+    Some(path.strip_prefix(prefix).map(str::to_string).unwrap_or(path))
+}
+
+/// Undo git's C-style quoting, leaving unquoted paths as they are
+fn unquote(path: &str) -> String {
+    if path.starts_with('"') {
+        split_quoted(path).0
+    } else {
+        path.to_string()
+    }
+}
+
+/// Decode a leading `"..."` and return it with whatever follows the closing quote
+fn split_quoted(input: &str) -> (String, &str) {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::new();
+    let mut i = 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' => return (String::from_utf8_lossy(&decoded).into_owned(), &input[i + 1..]),
+            b'\\' if i + 1 < bytes.len() => {
+                i += 1;
+                match bytes[i] {
+                    b'n' => decoded.push(b'\n'),
+                    b't' => decoded.push(b'\t'),
+                    b'r' => decoded.push(b'\r'),
+                    b'a' => decoded.push(0x07),
+                    b'b' => decoded.push(0x08),
+                    b'f' => decoded.push(0x0c),
+                    b'v' => decoded.push(0x0b),
+                    b'0'..=b'7' => {
+                        let digits = &input[i..(i + 3).min(input.len())];
+                        decoded.push(u8::from_str_radix(digits, 8).unwrap_or(b'?'));
+                        i += 2;
+                    }
+                    other => decoded.push(other),
+                }
+            }
+            other => decoded.push(other),
+        }
+        i += 1;
+    }
+    (String::from_utf8_lossy(&decoded).into_owned(), "")
+}
+
+/// Pair each run of removed lines with the added lines that follow it and mark the
+/// words that differ within each pair
+fn mark_word_changes(lines: &mut [DiffLine]) {
+    let mut i = 0;
+    while i < lines.len() {
+        if lines[i].kind != LineKind::Removed {
+            i += 1;
+            continue;
+        }
+        let removed_start = i;
+        while i < lines.len() && lines[i].kind == LineKind::Removed {
+            i += 1;
+        }
+        let added_start = i;
+        while i < lines.len() && lines[i].kind == LineKind::Added {
+            i += 1;
+        }
+
+        let pairs = (added_start - removed_start).min(i - added_start);
+        for offset in 0..pairs {
+            let (old_changes, new_changes) = word_diff(
+                &lines[removed_start + offset].content,
+                &lines[added_start + offset].content,
+            );
+            lines[removed_start + offset].changes = old_changes;
+            lines[added_start + offset].changes = new_changes;
+        }
+    }
+}
+
+/// Byte spans of `old` and `new` that aren't part of their longest common token sequence
+fn word_diff(old: &str, new: &str) -> (Vec<Span>, Vec<Span>) {
+    let old_tokens = tokenize(old);
+    let new_tokens = tokenize(new);
+    if old_tokens.len() > MAX_WORD_DIFF_TOKENS || new_tokens.len() > MAX_WORD_DIFF_TOKENS {
+        return (Vec::new(), Vec::new());
+    }
+
+    // lcs[i][j] = common tokens in old_tokens[i..] and new_tokens[j..]
+    let (n, m) = (old_tokens.len(), new_tokens.len());
+    let mut lcs = vec![vec![0u16; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if old_tokens[i].1 == new_tokens[j].1 {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut old_changes = Vec::new();
+    let mut new_changes = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && old_tokens[i].1 == new_tokens[j].1 {
+            i += 1;
+            j += 1;
+        } else if j < m && (i == n || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            push_span(&mut new_changes, new_tokens[j].0, new_tokens[j].0 + new_tokens[j].1.len());
+            j += 1;
+        } else {
+            push_span(&mut old_changes, old_tokens[i].0, old_tokens[i].0 + old_tokens[i].1.len());
+            i += 1;
+        }
+    }
+
+    (old_changes, new_changes)
+}
+
+/// Extend the last span when the new one touches it
+fn push_span(spans: &mut Vec<Span>, start: usize, end: usize) {
+    match spans.last_mut() {
+        Some(last) if last.end == start => last.end = end,
+        _ => spans.push(Span { start, end }),
+    }
+}
+
+/// Runs of word characters, runs of whitespace, and single other characters, with their byte offsets
+fn tokenize(line: &str) -> Vec<(usize, &str)> {
+    let class = |c: char| {
+        if c.is_alphanumeric() || c == '_' {
+            0
+        } else if c.is_whitespace() {
+            1
+        } else {
+            2
+        }
+    };
+
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut previous: Option<u8> = None;
+    for (index, c) in line.char_indices() {
+        let current = class(c);
+        if let Some(previous) = previous {
+            if previous != current || current == 2 {
+                tokens.push((start, &line[start..index]));
+                start = index;
+            }
+        }
+        previous = Some(current);
+    }
+    if start < line.len() {
+        tokens.push((start, &line[start..]));
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::fixture::TempRepo;
+
+    const PATCH: &str = "\
+diff --git a/src/lib.rs b/src/lib.rs
+index 1111111..2222222 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,4 +1,4 @@ mod tests
+ use std::fs;
+-let answer = compute(41);
++let answer = compute(42);
++let extra = true;
+ fn main() {}
+-- a/not-a-header
+@@ -10,0 +12 @@
++appended
+diff --git a/sp ace.txt b/sp ace.txt
+index 422c2b7..0f7bc76 100644
+--- a/sp ace.txt\t
++++ b/sp ace.txt\t
+@@ -1 +1 @@
+-x
+\\ No newline at end of file
++y
+\\ No newline at end of file
+diff --git \"a/q\\\"uo\\\\ttab.txt\" b/new q.txt
+similarity index 91%
+rename from \"q\\\"uo\\\\ttab.txt\"
+rename to new q.txt
+diff --git a/logo.png b/logo.png
+new file mode 100644
+index 0000000..8835708
+Binary files /dev/null and b/logo.png differ
+";
+
+    #[test]
+    fn test_parse_diff_fixture() {
+        let files = parse_diff(PATCH.as_bytes()).unwrap();
+        assert_eq!(files.len(), 4);
+
+        let lib = &files[0];
+        assert_eq!(lib.path, "src/lib.rs");
+        assert_eq!((lib.additions, lib.deletions), (3, 2));
+        assert_eq!(lib.hunks.len(), 2);
+        let lines = &lib.hunks[0].lines;
+        assert_eq!(lines[1].kind, LineKind::Removed);
+        assert_eq!(lines[1].old_line, Some(2));
+        assert_eq!(lines[2].new_line, Some(2));
+        assert_eq!(lines[3].new_line, Some(3));
+        assert_eq!(lines[4].kind, LineKind::Context);
+        assert_eq!((lines[4].old_line, lines[4].new_line), (Some(3), Some(4)));
+        // A removed line that looks like a header is still part of the hunk
+        assert_eq!(lines[5].content, "- a/not-a-header");
+        assert_eq!(lines[5].old_line, Some(4));
+        // Only the changed number is highlighted
+        assert_eq!(lines[1].changes, vec![Span { start: 21, end: 23 }]);
+        assert_eq!(&lines[2].content[21..23], "42");
+        assert!(lines[3].changes.is_empty());
+        assert_eq!(lib.hunks[1].lines[0].new_line, Some(12));
+
+        let spaced = &files[1];
+        assert_eq!(spaced.path, "sp ace.txt");
+        assert!(spaced.hunks[0].lines.iter().all(|line| line.no_newline));
+
+        let renamed = &files[2];
+        assert_eq!(renamed.status, FileStatus::Renamed);
+        assert_eq!(renamed.orig_path.as_deref(), Some("q\"uo\\ttab.txt"));
+        assert_eq!(renamed.path, "new q.txt");
+        assert_eq!(renamed.similarity, Some(91));
+
+        let binary = &files[3];
+        assert_eq!(binary.path, "logo.png");
+        assert_eq!(binary.status, FileStatus::Added);
+        assert!(binary.binary);
+        assert!(binary.hunks.is_empty());
+    }
+
+    #[test]
+    fn test_word_diff_marks_changed_words() {
+        let (old, new) = word_diff("let value = old_name(1);", "let value = new_name(1, 2);");
+        assert_eq!(old, vec![Span { start: 12, end: 20 }]);
+        assert_eq!(&"let value = new_name(1, 2);"[new[0].start..new[0].end], "new_name");
+        assert_eq!(&"let value = new_name(1, 2);"[new[1].start..new[1].end], ", 2");
+    }
+
+    #[test]
+    fn test_diffs_from_repo() {
+        let repo = TempRepo::new();
+        repo.write("notes.txt", "one\ntwo\nthree\n");
+        repo.write("moved.txt", "content that survives a rename\nacross several\nlines\n");
+        repo.commit_all("initial");
+
+        repo.write("notes.txt", "one\n2\nthree\n");
+        repo.git(&["add", "notes.txt"]);
+        repo.write("notes.txt", "one\n2\nthree\nfour\n");
+        repo.git(&["mv", "moved.txt", "renamed.txt"]);
+        repo.write("new.txt", "brand new\n");
+
+        let staged = file_diff(repo.path(), "notes.txt", true, DEFAULT_CONTEXT_LINES).unwrap().unwrap();
+        assert_eq!((staged.additions, staged.deletions), (1, 1));
+        let unstaged = file_diff(repo.path(), "notes.txt", false, 0).unwrap().unwrap();
+        assert_eq!(unstaged.hunks[0].lines.len(), 1);
+        assert_eq!(unstaged.hunks[0].lines[0].new_line, Some(4));
+
+        let untracked = file_diff(repo.path(), "new.txt", false, DEFAULT_CONTEXT_LINES).unwrap().unwrap();
+        assert_eq!(untracked.status, FileStatus::Added);
+        assert_eq!(untracked.path, "new.txt");
+
+        assert_eq!(file_diff(repo.path(), "moved.txt", false, DEFAULT_CONTEXT_LINES).unwrap(), None);
+
+        let diff = worktree_diff(repo.path(), DEFAULT_CONTEXT_LINES, &CancellationToken::new()).unwrap();
+        let renamed = diff.staged.iter().find(|file| file.status == FileStatus::Renamed).unwrap();
+        assert_eq!(renamed.orig_path.as_deref(), Some("moved.txt"));
+        assert_eq!(renamed.path, "renamed.txt");
+        let unstaged_paths: Vec<_> = diff.unstaged.iter().map(|file| file.path.as_str()).collect();
+        assert_eq!(unstaged_paths, vec!["notes.txt", "new.txt"]);
+    }
+
+    #[test]
+    fn test_untracked_files_match_git() {
+        let repo = TempRepo::new();
+        repo.write("lines.txt", "one\ntwo\n");
+        repo.write("single.txt", "no newline");
+        repo.write("empty.txt", "");
+        repo.write("image.bin", "PNG\0\x01\x02");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            repo.write("run.sh", "#!/bin/sh\necho hi\n");
+            fs::set_permissions(repo.path().join("run.sh"), fs::Permissions::from_mode(0o755)).unwrap();
+            std::os::unix::fs::symlink("lines.txt", repo.path().join("link")).unwrap();
+        }
+
+        let untracked = git::run(repo.path(), &["ls-files", "-z", "--others", "--exclude-standard"]).unwrap();
+        for path in untracked.split(|b| *b == 0).filter(|path| !path.is_empty()) {
+            let path = String::from_utf8_lossy(path);
+            let mut args = diff_args("diff", "-U3");
+            args.extend(["--no-index", "--", "/dev/null", &path]);
+            let expected = parse_diff(&git::run_allowing(repo.path(), &args, &[1]).unwrap()).unwrap();
+            assert_eq!(untracked_diff(repo.path(), &path).unwrap(), expected[0], "{}", path);
+        }
+
+        repo.write("big.log", &"a line of log output\n".repeat(MAX_PATCH_BYTES / 10));
+        let big = untracked_diff(repo.path(), "big.log").unwrap();
+        assert!(big.too_large && big.hunks.is_empty());
+        assert_eq!(big.additions as usize, MAX_PATCH_BYTES / 10);
+    }
+}
@@ -0,0 +1,116 @@
+//! Throwaway repositories for tests
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+pub struct TempRepo {
+    pub path: PathBuf,
+}
+
+impl TempRepo {
+    /// An empty repository on `main` with a committer configured
+    pub fn new() -> Self {
+        let path = std::env::temp_dir().join(format!("manymany-repo-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&path).unwrap();
+        let repo = Self { path };
+        repo.git(&["init", "-q", "-b", "main"]);
+        repo.git(&["config", "user.name", "Test"]);
+        repo.git(&["config", "user.email", "test@example.com"]);
+        repo.git(&["config", "commit.gpgsign", "false"]);
+        repo
+    }
+
+    /// A plain directory, not a repository, for tests that only need somewhere to put files
+    pub fn dir() -> Self {
+        let path = std::env::temp_dir().join(format!("manymany-dir-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&path).unwrap();
+        Self { path }
+    }
+
+    /// A bare repository to use as a remote
+    pub fn new_bare() -> Self {
+        let path = std::env::temp_dir().join(format!("manymany-remote-{}.git", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&path).unwrap();
+        let repo = Self { path };
+        repo.git(&["init", "-q", "--bare", "-b", "main"]);
+        repo
+    }
+
+    /// A clone of `remote` with a committer configured
+    pub fn clone_from(remote: &TempRepo) -> Self {
+        let path = std::env::temp_dir().join(format!("manymany-repo-{}", uuid::Uuid::new_v4()));
+        remote.git(&["clone", "-q", remote.path.to_str().unwrap(), path.to_str().unwrap()]);
+        let repo = Self { path };
+        repo.git(&["config", "user.name", "Test"]);
+        repo.git(&["config", "user.email", "test@example.com"]);
+        repo.git(&["config", "commit.gpgsign", "false"]);
+        repo
+    }
+
+    /// A linked worktree on a new `branch`, removed again on drop
+    pub fn add_worktree(&self, branch: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("manymany-worktree-{}", uuid::Uuid::new_v4()));
+        self.git(&["worktree", "add", "-q", "-b", branch, path.to_str().unwrap()]);
+        Self { path }
+    }
+
+    /// Run git, panicking with stderr on failure
+    pub fn git(&self, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(&self.path)
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "git {:?} failed: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    }
+
+    pub fn write(&self, relative: &str, content: &str) {
+        let file = self.path.join(relative);
+        if let Some(parent) = file.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        fs::write(file, content).unwrap();
+    }
+
+    pub fn read(&self, relative: &str) -> String {
+        fs::read_to_string(self.path.join(relative)).unwrap()
+    }
+
+    pub fn commit_all(&self, message: &str) {
+        self.git(&["add", "-A"]);
+        self.git(&["commit", "-q", "-m", message]);
+    }
+
+    /// Commit everything dated `seconds` since the epoch, in a +01:00 zone
+    pub fn commit_all_at(&self, message: &str, seconds: i64) {
+        self.git(&["add", "-A"]);
+        let date = format!("@{} +0100", seconds);
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(&self.path)
+            .args(["commit", "-q", "-m", message])
+            .env("GIT_AUTHOR_DATE", &date)
+            .env("GIT_COMMITTER_DATE", &date)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git commit failed: {}", String::from_utf8_lossy(&output.stderr));
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempRepo {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
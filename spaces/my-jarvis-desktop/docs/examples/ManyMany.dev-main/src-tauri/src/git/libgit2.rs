@@ -0,0 +1,683 @@
+//! [`GitBackend`] running in-process on libgit2, for machines without a `git` binary.
+//!
+//! Diffs are printed as patches and go through the same parser as the CLI's, so both backends
+//! produce identical hunks and word changes.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, TimeZone, Utc};
+use git2::{
+    BranchType, Commit, Delta, Diff, DiffDelta, DiffFindOptions, DiffFormat, DiffOptions, ErrorCode, Oid,
+    Patch, ReferenceType, Repository, Sort, StatusOptions, WorktreeLockStatus,
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::error::{AppError, ErrorKind};
+use crate::git;
+use crate::git::backend::{BackendKind, GitBackend};
+use crate::git::branch::BranchInfo;
+use crate::git::diff::{parse_diff, WorktreeDiff};
+use crate::git::log::{layout_graph, CommitInfo, CommitLog, FileStat, LogQuery, DEFAULT_PAGE_SIZE};
+use crate::git::operation::operation_in_git_dir;
+use crate::git::status::{
+    ConflictInfo, ConflictKind, DefaultBranchDivergence, FileStatus, GitFile, GitStatus, UpstreamState,
+};
+use crate::git::worktree::{sort_worktrees, WorktreeInfo};
+
+pub struct Libgit2Backend;
+
+impl GitBackend for Libgit2Backend {
+    fn kind(&self) -> BackendKind {
+        BackendKind::Libgit2
+    }
+
+    fn status(&self, worktree_path: &Path) -> Result<GitStatus, AppError> {
+        let repo = open(worktree_path)?;
+        let mut status = GitStatus::default();
+
+        let head = head_commit(&repo)?;
+        status.head_oid = head.map(|oid| oid.to_string());
+        if repo.head_detached().map_err(error("read HEAD"))? {
+            status.branch = "HEAD".to_string();
+            status.detached = true;
+        } else if let Some(branch_ref) = head_branch(&repo) {
+            status.branch = branch_ref.trim_start_matches("refs/heads/").to_string();
+            if let Ok(upstream) = repo.branch_upstream_name(&branch_ref) {
+                let upstream = upstream.as_str().unwrap_or_default();
+                status.upstream = Some(short_ref(upstream));
+                status.upstream_state = UpstreamState::Gone;
+                if let (Some(head), Ok(target)) = (head, repo.refname_to_id(upstream)) {
+                    let (ahead, behind) = repo.graph_ahead_behind(head, target).map_err(error("count commits"))?;
+                    status.upstream_state = UpstreamState::Tracking;
+                    status.ahead = ahead as u32;
+                    status.behind = behind as u32;
+                }
+            }
+        }
+
+        let mut options = StatusOptions::new();
+        options.include_untracked(true).recurse_untracked_dirs(true).renames_head_to_index(true);
+        let entries = repo.statuses(Some(&mut options)).map_err(error("get status"))?;
+        let conflicts = conflicts(&repo)?;
+        for entry in entries.iter() {
+            let flags = entry.status();
+            let path = entry.path().unwrap_or_default().to_string();
+            if flags.is_conflicted() {
+                status.conflicted.push(GitFile {
+                    conflict: conflicts.get(&path).cloned(),
+                    ..file(path, FileStatus::Unmerged)
+                });
+                continue;
+            }
+
+            let staged = match flags {
+                _ if flags.is_index_new() => Some(FileStatus::Added),
+                _ if flags.is_index_modified() => Some(FileStatus::Modified),
+                _ if flags.is_index_deleted() => Some(FileStatus::Deleted),
+                _ if flags.is_index_renamed() => Some(FileStatus::Renamed),
+                _ if flags.is_index_typechange() => Some(FileStatus::TypeChanged),
+                _ => None,
+            };
+            if let (Some(file_status), Some(delta)) = (staged, entry.head_to_index()) {
+                let new_path = delta_path(&delta);
+                status.staged.push(GitFile {
+                    orig_path: (file_status == FileStatus::Renamed)
+                        .then(|| delta.old_file().path().map(|path| path.to_string_lossy().into_owned()))
+                        .flatten(),
+                    ..file(new_path, file_status)
+                });
+            }
+
+            // The index side of a staged rename is its new path
+            let path = entry.index_to_workdir().map(|delta| delta_path(&delta)).unwrap_or(path);
+            if flags.is_wt_new() {
+                status.untracked.push(file(path, FileStatus::Untracked));
+            } else if let Some(file_status) = match flags {
+                _ if flags.is_wt_modified() => Some(FileStatus::Modified),
+                _ if flags.is_wt_deleted() => Some(FileStatus::Deleted),
+                _ if flags.is_wt_typechange() => Some(FileStatus::TypeChanged),
+                _ => None,
+            } {
+                status.unstaged.push(file(path, file_status));
+            }
+        }
+
+        // libgit2 doesn't expose rename scores on status entries, but prints them in patches
+        if status.staged.iter().any(|file| file.status == FileStatus::Renamed) {
+            let scores: HashMap<String, Option<u8>> = parse_diff(&patch(&index_diff(&repo, 0)?, |_| true)?)?
+                .into_iter()
+                .map(|diff| (diff.path, diff.similarity))
+                .collect();
+            for file in status.staged.iter_mut().filter(|file| file.status == FileStatus::Renamed) {
+                file.similarity = scores.get(&file.path).copied().flatten();
+            }
+        }
+        for list in [&mut status.staged, &mut status.unstaged, &mut status.untracked, &mut status.conflicted] {
+            list.sort_by(|a, b| a.path.cmp(&b.path));
+        }
+
+        status.operation_in_progress = operation_in_git_dir(repo.path());
+        Ok(status)
+    }
+
+    fn default_branch_divergence(&self, worktree_path: &Path) -> Result<DefaultBranchDivergence, AppError> {
+        let repo = open(worktree_path)?;
+        let head = head_commit(&repo)?.ok_or_else(|| AppError::other("HEAD has no commits yet"))?;
+        let name = default_branch(&repo);
+        let (candidate, target) = resolve_branch(&repo, &name).ok_or_else(|| AppError::new(
+            ErrorKind::BranchNotFound { branch: name.clone() },
+            format!("Default branch '{}' not found", name),
+        ))?;
+        let (ahead, behind) = repo.graph_ahead_behind(head, target).map_err(error("count commits"))?;
+        Ok(DefaultBranchDivergence {
+            name: short_ref(&candidate),
+            ahead: ahead as u32,
+            behind: behind as u32,
+        })
+    }
+
+    fn branches(&self, repo_path: &Path) -> Result<Vec<BranchInfo>, AppError> {
+        let repo = open(repo_path)?;
+        let checked_out: HashMap<String, String> = self.worktrees(repo_path)?
+            .into_iter()
+            .filter_map(|worktree| Some((worktree.branch?, worktree.path)))
+            .collect();
+
+        let mut branches = Vec::new();
+        for item in repo.branches(None).map_err(error("list branches"))? {
+            let (branch, branch_type) = item.map_err(error("list branches"))?;
+            let reference = branch.get();
+            // Skip symbolic refs like origin/HEAD
+            if reference.kind() == Some(ReferenceType::Symbolic) {
+                continue;
+            }
+            let (Some(full_name), Some(oid)) = (reference.name(), reference.target()) else {
+                continue;
+            };
+            let commit = repo.find_commit(oid).map_err(error("read commit"))?;
+            let name = short_ref(full_name);
+
+            let mut info = BranchInfo {
+                name: name.clone(),
+                is_remote: branch_type == BranchType::Remote,
+                sha: oid.to_string(),
+                upstream: None,
+                upstream_gone: false,
+                ahead: 0,
+                behind: 0,
+                last_commit_date: format_time(commit.committer().when()),
+                last_commit_author: String::from_utf8_lossy(commit.author().name_bytes()).into_owned(),
+                last_commit_subject: commit.summary().unwrap_or_default().to_string(),
+                worktree_path: None,
+            };
+            if branch_type == BranchType::Local {
+                info.worktree_path = checked_out.get(&name).cloned();
+                if let Ok(upstream) = repo.branch_upstream_name(full_name) {
+                    let upstream = upstream.as_str().unwrap_or_default();
+                    info.upstream = Some(short_ref(upstream));
+                    match repo.refname_to_id(upstream) {
+                        Ok(target) => {
+                            let (ahead, behind) = repo.graph_ahead_behind(oid, target).map_err(error("count commits"))?;
+                            info.ahead = ahead as u32;
+                            info.behind = behind as u32;
+                        }
+                        Err(_) => info.upstream_gone = true,
+                    }
+                }
+            }
+            branches.push((commit.committer().when().seconds(), full_name.to_string(), info));
+        }
+
+        // Like `git for-each-ref --sort=-committerdate`, ties broken by ref name
+        branches.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+        Ok(branches.into_iter().map(|(_, _, info)| info).collect())
+    }
+
+    fn worktrees(&self, repo_path: &Path) -> Result<Vec<WorktreeInfo>, AppError> {
+        let repo = open(repo_path)?;
+        let main = Repository::open(repo.commondir()).map_err(error("open the main worktree"))?;
+
+        let bare = main.is_bare();
+        let main_path = match main.workdir() {
+            Some(workdir) if !bare => workdir,
+            _ => main.path(),
+        };
+        let (head, branch) = read_head(&main, main.path());
+        let mut worktrees = vec![WorktreeInfo {
+            path: display_path(main_path),
+            head,
+            branch,
+            is_main: true,
+            bare,
+            locked: false,
+            prunable: false,
+        }];
+
+        for name in main.worktrees().map_err(error("list worktrees"))?.iter().flatten() {
+            let worktree = main.find_worktree(name).map_err(error("open worktree"))?;
+            let (head, branch) = read_head(&main, &main.path().join("worktrees").join(name));
+            worktrees.push(WorktreeInfo {
+                path: display_path(worktree.path()),
+                head,
+                branch,
+                is_main: false,
+                bare: false,
+                locked: matches!(worktree.is_locked(), Ok(WorktreeLockStatus::Locked(_))),
+                prunable: !worktree.path().exists(),
+            });
+        }
+
+        sort_worktrees(&mut worktrees);
+        Ok(worktrees)
+    }
+
+    fn diff(&self, worktree_path: &Path, context_lines: u32, cancel: &CancellationToken) -> Result<WorktreeDiff, AppError> {
+        let repo = open(worktree_path)?;
+        let staged = parse_diff(&patch(&index_diff(&repo, context_lines)?, |_| true)?)?;
+        git::check_cancelled(cancel, "diff")?;
+
+        let mut options = diff_options(context_lines);
+        let tracked = repo.diff_index_to_workdir(None, Some(&mut options)).map_err(error("diff the worktree"))?;
+        let mut unstaged = parse_diff(&patch(&tracked, |_| true)?)?;
+        git::check_cancelled(cancel, "diff")?;
+
+        // Untracked files come last, as additions
+        options.include_untracked(true).recurse_untracked_dirs(true).show_untracked_content(true);
+        let untracked = repo.diff_index_to_workdir(None, Some(&mut options)).map_err(error("diff the worktree"))?;
+        unstaged.extend(parse_diff(&patch(&untracked, |delta| delta.status() == Delta::Untracked)?)?);
+
+        Ok(WorktreeDiff { staged, unstaged })
+    }
+
+    fn log(&self, worktree_path: &Path, query: &LogQuery, cancel: &CancellationToken) -> Result<CommitLog, AppError> {
+        let repo = open(worktree_path)?;
+        let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).max(1) as usize;
+        let skip = query.skip as usize;
+        let reference = query.reference.as_deref().filter(|reference| !reference.is_empty()).unwrap_or("HEAD");
+        if reference.starts_with('-') {
+            return Err(AppError::invalid(format!("Invalid ref: {}", reference)));
+        }
+        let start = repo.revparse_single(reference)
+            .and_then(|object| object.peel_to_commit())
+            .map_err(|e| match e.code() {
+                ErrorCode::NotFound => AppError::invalid(format!("Unknown revision: {}", reference)),
+                _ => AppError::other(format!("Failed to resolve {}: {}", reference, e.message())),
+            })?;
+        let since = query.since.as_deref().map(parse_since).transpose()?;
+
+        // Same order as `git log --date-order`
+        let mut walk = repo.revwalk().map_err(error("walk history"))?;
+        walk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME).map_err(error("walk history"))?;
+        walk.push(start.id()).map_err(error("walk history"))?;
+
+        let mut matching = Vec::new();
+        for oid in walk {
+            git::check_cancelled(cancel, "log")?;
+            let commit = repo.find_commit(oid.map_err(error("walk history"))?).map_err(error("read commit"))?;
+            // Newest first, so everything after the first older commit is older too
+            if since.is_some_and(|since| commit.committer().when().seconds() < since) {
+                break;
+            }
+            if let Some(author) = &query.author {
+                let signature = commit.author();
+                let line = format!(
+                    "{} <{}>",
+                    String::from_utf8_lossy(signature.name_bytes()),
+                    String::from_utf8_lossy(signature.email_bytes())
+                );
+                if !line.contains(author.as_str()) {
+                    continue;
+                }
+            }
+            if let Some(path) = &query.path_filter {
+                if !touches_path(&repo, &commit, path)? {
+                    continue;
+                }
+            }
+            matching.push(commit);
+            if matching.len() > skip + limit {
+                break;
+            }
+        }
+
+        // Unlike `git rev-list`, parents aren't rewritten to skip commits a path filter hides
+        let graph_input: Vec<(String, Vec<String>)> = matching.iter()
+            .take(skip + limit)
+            .map(|commit| (commit.id().to_string(), commit.parent_ids().map(|parent| parent.to_string()).collect()))
+            .collect();
+        let graph = layout_graph(&graph_input);
+
+        let has_more = matching.len() > skip + limit;
+        let decorations = decorations(&repo)?;
+        let commits = matching.iter()
+            .skip(skip)
+            .take(limit)
+            .map(|commit| {
+                git::check_cancelled(cancel, "log")?;
+                let mut info = commit_info(&repo, commit, &decorations)?;
+                info.graph = graph.get(&info.sha).cloned();
+                Ok(info)
+            })
+            .collect::<Result<Vec<_>, AppError>>()?;
+
+        Ok(CommitLog { commits, has_more })
+    }
+}
+
+fn open(path: &Path) -> Result<Repository, AppError> {
+    Repository::discover(path).map_err(|e| {
+        if !path.exists() {
+            AppError::path_not_found(path.display())
+        } else if e.code() == ErrorCode::NotFound {
+            AppError::new(
+                ErrorKind::NotAGitRepo { path: path.display().to_string() },
+                format!("{} is not a git repository", path.display()),
+            )
+        } else {
+            AppError::other(format!("Failed to open repository at {}: {}", path.display(), e.message()))
+        }
+    })
+}
+
+fn error(action: &'static str) -> impl Fn(git2::Error) -> AppError {
+    move |e| AppError::other(format!("Failed to {}: {}", action, e.message()))
+}
+
+fn file(path: String, status: FileStatus) -> GitFile {
+    GitFile {
+        path,
+        status,
+        orig_path: None,
+        similarity: None,
+        submodule: None,
+        conflict: None,
+    }
+}
+
+fn delta_path(delta: &DiffDelta) -> String {
+    delta.new_file().path()
+        .or_else(|| delta.old_file().path())
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// `None` before the first commit
+fn head_commit(repo: &Repository) -> Result<Option<Oid>, AppError> {
+    match repo.head() {
+        Ok(head) => Ok(head.target()),
+        Err(e) if e.code() == ErrorCode::UnbornBranch || e.code() == ErrorCode::NotFound => Ok(None),
+        Err(e) => Err(AppError::other(format!("Failed to read HEAD: {}", e.message()))),
+    }
+}
+
+/// The branch `HEAD` points at, e.g. `refs/heads/main`, even before its first commit
+fn head_branch(repo: &Repository) -> Option<String> {
+    let head = repo.find_reference("HEAD").ok()?;
+    head.symbolic_target().filter(|target| target.starts_with("refs/heads/")).map(str::to_string)
+}
+
+/// `refs/remotes/origin/main` -> `origin/main`, `refs/heads/main` -> `main`
+fn short_ref(name: &str) -> String {
+    name.strip_prefix("refs/heads/")
+        .or_else(|| name.strip_prefix("refs/remotes/"))
+        .unwrap_or(name)
+        .to_string()
+}
+
+fn display_path(path: &Path) -> String {
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    path.to_string_lossy().trim_end_matches('/').to_string()
+}
+
+/// A worktree's checked out commit and branch from the `HEAD` file in its git directory
+fn read_head(main: &Repository, git_dir: &Path) -> (Option<String>, Option<String>) {
+    let content = fs::read_to_string(git_dir.join("HEAD")).unwrap_or_default();
+    match content.trim().strip_prefix("ref: ") {
+        Some(target) => (
+            main.refname_to_id(target).ok().map(|oid| oid.to_string()),
+            Some(target.trim_start_matches("refs/heads/").to_string()),
+        ),
+        None => (Oid::from_str(content.trim()).ok().map(|oid| oid.to_string()), None),
+    }
+}
+
+fn conflicts(repo: &Repository) -> Result<HashMap<String, ConflictInfo>, AppError> {
+    let index = repo.index().map_err(error("read the index"))?;
+    let mut conflicts = HashMap::new();
+    if !index.has_conflicts() {
+        return Ok(conflicts);
+    }
+    for conflict in index.conflicts().map_err(error("read conflicts"))? {
+        let conflict = conflict.map_err(error("read conflicts"))?;
+        let Some(path) = [&conflict.ancestor, &conflict.our, &conflict.their]
+            .into_iter()
+            .flatten()
+            .next()
+            .map(|entry| String::from_utf8_lossy(&entry.path).into_owned())
+        else {
+            continue;
+        };
+        let kind = match (conflict.ancestor.is_some(), conflict.our.is_some(), conflict.their.is_some()) {
+            (true, true, true) => ConflictKind::BothModified,
+            (false, true, true) => ConflictKind::BothAdded,
+            (true, false, false) => ConflictKind::BothDeleted,
+            (false, true, false) => ConflictKind::AddedByUs,
+            (false, false, true) => ConflictKind::AddedByThem,
+            (true, false, true) => ConflictKind::DeletedByUs,
+            (true, true, false) => ConflictKind::DeletedByThem,
+            (false, false, false) => continue,
+        };
+        let id = |entry: &Option<git2::IndexEntry>| entry.as_ref().map(|entry| entry.id.to_string());
+        conflicts.insert(path, ConflictInfo {
+            kind,
+            base: id(&conflict.ancestor),
+            ours: id(&conflict.our),
+            theirs: id(&conflict.their),
+        });
+    }
+    Ok(conflicts)
+}
+
+/// Mirrors [`crate::git::branch::default_branch`]: origin's `HEAD`, else the first of
+/// `init.defaultBranch`, `main` and `master` that exists
+fn default_branch(repo: &Repository) -> String {
+    let origin_head = repo.find_reference("refs/remotes/origin/HEAD").ok()
+        .and_then(|origin_head| origin_head.symbolic_target().map(str::to_string))
+        .and_then(|target| target.strip_prefix("refs/remotes/origin/").map(str::to_string))
+        .filter(|name| !name.is_empty());
+    if let Some(name) = origin_head {
+        return name;
+    }
+
+    let configured = repo.config().ok().and_then(|config| config.get_string("init.defaultBranch").ok());
+    let candidates = configured.iter().map(String::as_str).chain(["main", "master"]);
+    for name in candidates.filter(|name| !name.is_empty()) {
+        if resolve_branch(repo, name).is_some() {
+            return name.to_string();
+        }
+    }
+    "main".to_string()
+}
+
+/// `refs/heads/name` or `refs/remotes/origin/name`, whichever exists first, and its commit
+fn resolve_branch(repo: &Repository, name: &str) -> Option<(String, Oid)> {
+    [format!("refs/heads/{}", name), format!("refs/remotes/origin/{}", name)]
+        .into_iter()
+        .find_map(|candidate| {
+            let target = repo.refname_to_id(&candidate).ok()?;
+            Some((candidate, target))
+        })
+}
+
+fn diff_options(context_lines: u32) -> DiffOptions {
+    let mut options = DiffOptions::new();
+    options.context_lines(context_lines);
+    options
+}
+
+/// Staged changes, with renames detected
+fn index_diff(repo: &Repository, context_lines: u32) -> Result<Diff<'_>, AppError> {
+    let head_tree = match head_commit(repo)? {
+        Some(head) => Some(repo.find_commit(head).and_then(|commit| commit.tree()).map_err(error("read HEAD"))?),
+        None => None,
+    };
+    let mut diff = repo.diff_tree_to_index(head_tree.as_ref(), None, Some(&mut diff_options(context_lines)))
+        .map_err(error("diff the index"))?;
+    diff.find_similar(Some(DiffFindOptions::new().renames(true))).map_err(error("detect renames"))?;
+    Ok(diff)
+}
+
+/// `diff` as `git diff` would print it, limited to the deltas `include` accepts
+fn patch(diff: &Diff, include: impl Fn(&DiffDelta) -> bool) -> Result<Vec<u8>, AppError> {
+    let mut patch = Vec::new();
+    diff.print(DiffFormat::Patch, |delta, _hunk, line| {
+        if include(&delta) {
+            // Content lines come without their prefix; headers and EOF markers are complete
+            if matches!(line.origin(), '+' | '-' | ' ') {
+                patch.push(line.origin() as u8);
+            }
+            patch.extend_from_slice(line.content());
+        }
+        true
+    })
+    .map_err(error("print diff"))?;
+    Ok(patch)
+}
+
+/// Whether the commit changes `path`. Like git's history simplification, a merge matching any parent there doesn't.
+fn touches_path(repo: &Repository, commit: &Commit, path: &str) -> Result<bool, AppError> {
+    let tree = commit.tree().map_err(error("read commit"))?;
+    let mut options = DiffOptions::new();
+    options.pathspec(path);
+    let changed = |parent_tree: Option<&git2::Tree>, options: &mut DiffOptions| -> Result<bool, AppError> {
+        let diff = repo.diff_tree_to_tree(parent_tree, Some(&tree), Some(options)).map_err(error("diff commit"))?;
+        Ok(diff.deltas().len() > 0)
+    };
+
+    if commit.parent_count() == 0 {
+        return changed(None, &mut options);
+    }
+    for parent in commit.parents() {
+        let parent_tree = parent.tree().map_err(error("read commit"))?;
+        if !changed(Some(&parent_tree), &mut options)? {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Ref names per commit, as `git log --format=%D` shows them
+fn decorations(repo: &Repository) -> Result<HashMap<Oid, Vec<String>>, AppError> {
+    let head_branch = if repo.head_detached().unwrap_or(false) { None } else { head_branch(repo) };
+    let mut labels: Vec<(u8, String, Oid)> = Vec::new();
+    if let Some(head) = head_commit(repo)? {
+        let label = match &head_branch {
+            Some(branch) => format!("HEAD -> {}", short_ref(branch)),
+            None => "HEAD".to_string(),
+        };
+        labels.push((0, label, head));
+    }
+
+    for reference in repo.references().map_err(error("list refs"))? {
+        let reference = reference.map_err(error("list refs"))?;
+        let Some(name) = reference.name() else {
+            continue;
+        };
+        let (group, label) = if name.starts_with("refs/heads/") {
+            if head_branch.as_deref() == Some(name) {
+                continue;
+            }
+            (1, short_ref(name))
+        } else if name.starts_with("refs/remotes/") {
+            (2, short_ref(name))
+        } else if let Some(tag) = name.strip_prefix("refs/tags/") {
+            (3, format!("tag: {}", tag))
+        } else {
+            continue;
+        };
+        if let Ok(commit) = reference.peel_to_commit() {
+            labels.push((group, label, commit.id()));
+        }
+    }
+
+    labels.sort();
+    let mut decorations: HashMap<Oid, Vec<String>> = HashMap::new();
+    for (_, label, oid) in labels {
+        decorations.entry(oid).or_default().push(label);
+    }
+    Ok(decorations)
+}
+
+fn commit_info(repo: &Repository, commit: &Commit, decorations: &HashMap<Oid, Vec<String>>) -> Result<CommitInfo, AppError> {
+    let author = commit.author();
+    let committer = commit.committer();
+    let short_sha = commit.as_object().short_id().map_err(error("abbreviate commit"))?;
+    let files = file_stats(repo, commit)?;
+
+    Ok(CommitInfo {
+        sha: commit.id().to_string(),
+        short_sha: short_sha.as_str().unwrap_or_default().to_string(),
+        parents: commit.parent_ids().map(|parent| parent.to_string()).collect(),
+        author_name: String::from_utf8_lossy(author.name_bytes()).into_owned(),
+        author_email: String::from_utf8_lossy(author.email_bytes()).into_owned(),
+        author_date: format_time(author.when()),
+        committer_name: String::from_utf8_lossy(committer.name_bytes()).into_owned(),
+        committer_email: String::from_utf8_lossy(committer.email_bytes()).into_owned(),
+        committer_date: format_time(committer.when()),
+        subject: commit.summary().unwrap_or_default().to_string(),
+        body: commit.body().unwrap_or_default().trim_end().to_string(),
+        refs: decorations.get(&commit.id()).cloned().unwrap_or_default(),
+        additions: files.iter().filter_map(|file| file.additions).sum(),
+        deletions: files.iter().filter_map(|file| file.deletions).sum(),
+        files,
+        graph: None,
+    })
+}
+
+/// Per-file line counts against the parent, like `git log --numstat` (which skips merges)
+fn file_stats(repo: &Repository, commit: &Commit) -> Result<Vec<FileStat>, AppError> {
+    if commit.parent_count() > 1 {
+        return Ok(Vec::new());
+    }
+    let tree = commit.tree().map_err(error("read commit"))?;
+    let parent_tree = match commit.parent(0) {
+        Ok(parent) => Some(parent.tree().map_err(error("read commit"))?),
+        Err(_) => None,
+    };
+    let mut diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None).map_err(error("diff commit"))?;
+    diff.find_similar(Some(DiffFindOptions::new().renames(true))).map_err(error("detect renames"))?;
+
+    let mut files = Vec::new();
+    for index in 0..diff.deltas().len() {
+        let Some(patch) = Patch::from_diff(&diff, index).map_err(error("diff commit"))? else {
+            continue;
+        };
+        let delta = patch.delta();
+        let binary = delta.flags().is_binary();
+        let (_, additions, deletions) = patch.line_stats().map_err(error("count lines"))?;
+        files.push(FileStat {
+            path: delta_path(&delta),
+            orig_path: (delta.status() == Delta::Renamed)
+                .then(|| delta.old_file().path().map(|path| path.to_string_lossy().into_owned()))
+                .flatten(),
+            additions: (!binary).then_some(additions as u32),
+            deletions: (!binary).then_some(deletions as u32),
+        });
+    }
+    Ok(files)
+}
+
+/// RFC 3339 in the signature's own offset, like `%aI`
+fn format_time(time: git2::Time) -> String {
+    let offset = FixedOffset::east_opt(time.offset_minutes() * 60);
+    match (DateTime::from_timestamp(time.seconds(), 0), offset) {
+        (Some(time), Some(offset)) => time.with_timezone(&offset).to_rfc3339(),
+        _ => time.seconds().to_string(),
+    }
+}
+
+/// Unix seconds for the `--since` forms the app uses: RFC 3339, `YYYY-MM-DD` and `<n> <unit>s ago`
+fn parse_since(since: &str) -> Result<i64, AppError> {
+    let since = since.trim();
+    if let Ok(time) = DateTime::parse_from_rfc3339(since) {
+        return Ok(time.timestamp());
+    }
+    if let Some(midnight) = NaiveDate::parse_from_str(since, "%Y-%m-%d").ok().and_then(|date| date.and_hms_opt(0, 0, 0)) {
+        if let Some(time) = Local.from_local_datetime(&midnight).earliest() {
+            return Ok(time.timestamp());
+        }
+    }
+
+    let words: Vec<&str> = since.split_whitespace().collect();
+    if let [count, unit, "ago"] = words.as_slice() {
+        let count: i64 = count.parse().map_err(|_| AppError::invalid(format!("Unsupported date: {}", since)))?;
+        let seconds = match unit.trim_end_matches('s') {
+            "second" => 1,
+            "minute" => 60,
+            "hour" => 3600,
+            "day" => 86400,
+            "week" => 7 * 86400,
+            "month" => 30 * 86400,
+            "year" => 365 * 86400,
+            _ => return Err(AppError::invalid(format!("Unsupported date: {}", since))),
+        };
+        return Ok(Utc::now().timestamp() - count * seconds);
+    }
+    Err(AppError::invalid(format!("Unsupported date: {}", since)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_since() {
+        assert_eq!(parse_since("2024-01-31T10:00:00+02:00").unwrap(), 1706688000);
+        let week = Utc::now().timestamp() - 7 * 86400;
+        assert!((parse_since("1 week ago").unwrap() - week).abs() < 5);
+        assert!((parse_since("2 weeks ago").unwrap() - (week - 7 * 86400)).abs() < 5);
+        assert!(parse_since("2024-01-31").is_ok());
+        assert!(parse_since("last tuesday").unwrap_err().to_string().contains("Unsupported"));
+    }
+}
@@ -0,0 +1,386 @@
+use std::collections::HashMap;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
+
+use crate::error::{AppError, ErrorKind};
+use crate::git;
+use crate::git::diff::{commit_diff, FileDiff, DEFAULT_CONTEXT_LINES};
+
+pub const DEFAULT_PAGE_SIZE: u32 = 100;
+
+/// Fields of the log format, in order
+const COMMIT_FORMAT: &str = "%x1e%H%x00%h%x00%P%x00%an%x00%ae%x00%aI%x00%cn%x00%ce%x00%cI%x00%D%x00%s%x00%b%x1f";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LogQuery {
+    /// Branch, tag or commit to start from; `HEAD` when empty
+    #[serde(default)]
+    pub reference: Option<String>,
+    #[serde(default)]
+    pub path_filter: Option<String>,
+    #[serde(default)]
+    pub author: Option<String>,
+    /// Anything `git log --since` understands, e.g. `2 weeks ago` or `2024-01-31`
+    #[serde(default)]
+    pub since: Option<String>,
+    #[serde(default)]
+    pub skip: u32,
+    #[serde(default)]
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommitInfo {
+    pub sha: String,
+    pub short_sha: String,
+    pub parents: Vec<String>,
+    pub author_name: String,
+    pub author_email: String,
+    pub author_date: String,
+    pub committer_name: String,
+    pub committer_email: String,
+    pub committer_date: String,
+    pub subject: String,
+    pub body: String,
+    /// Branches, remote branches and tags pointing here, e.g. `HEAD -> main`, `tag: v1.0`
+    pub refs: Vec<String>,
+    pub files: Vec<FileStat>,
+    pub additions: u32,
+    pub deletions: u32,
+    /// Only set for commits returned by `commit_log`
+    pub graph: Option<GraphRow>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileStat {
+    pub path: String,
+    pub orig_path: Option<String>,
+    /// `None` for binary files
+    pub additions: Option<u32>,
+    pub deletions: Option<u32>,
+}
+
+/// Where to draw a commit in a branch graph. Lanes are columns, stable across pages.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GraphRow {
+    /// Lane of the commit's dot
+    pub lane: usize,
+    /// Other lanes coming from above that end at this commit (branches merging in)
+    pub merging_lanes: Vec<usize>,
+    /// Lanes that continue down to each parent, in parent order
+    pub parent_lanes: Vec<usize>,
+    /// Lanes passing straight through this row
+    pub passing_lanes: Vec<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommitLog {
+    pub commits: Vec<CommitInfo>,
+    /// More commits exist past this page
+    pub has_more: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommitDetails {
+    pub commit: CommitInfo,
+    /// Changes against the first parent
+    pub diff: Vec<FileDiff>,
+}
+
+/// One page of history with file stats and graph lanes; git is killed if `cancel` fires
+pub fn commit_log(worktree_path: &Path, query: &LogQuery, cancel: &CancellationToken) -> Result<CommitLog, AppError> {
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).max(1);
+    let reference = query.reference.as_deref().filter(|reference| !reference.is_empty()).unwrap_or("HEAD");
+    if reference.starts_with('-') {
+        return Err(AppError::invalid(format!("Invalid ref: {}", reference)));
+    }
+
+    let mut filters = vec!["--date-order".to_string()];
+    if let Some(author) = &query.author {
+        filters.push(format!("--author={}", author));
+    }
+    if let Some(since) = &query.since {
+        filters.push(format!("--since={}", since));
+    }
+    filters.push(reference.to_string());
+    filters.push("--".to_string());
+    if let Some(path) = &query.path_filter {
+        filters.push(path.clone());
+    }
+
+    // Lanes depend on everything above the page, so lay out the graph from the top
+    let graph = graph_rows(worktree_path, &filters, query.skip + limit, cancel)?;
+
+    let mut args = vec![
+        "-c".to_string(), "core.quotePath=false".to_string(),
+        "log".to_string(), "-z".to_string(), "--numstat".to_string(), "--find-renames".to_string(),
+        format!("--format={}", COMMIT_FORMAT),
+        format!("--skip={}", query.skip),
+        format!("--max-count={}", limit + 1),
+    ];
+    args.extend(filters);
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let output = git::run_cancellable(worktree_path, &args, cancel)?;
+
+    let mut commits = parse_log(&String::from_utf8_lossy(&output));
+    let has_more = commits.len() > limit as usize;
+    commits.truncate(limit as usize);
+    for commit in &mut commits {
+        commit.graph = graph.get(&commit.sha).cloned();
+    }
+
+    Ok(CommitLog { commits, has_more })
+}
+
+/// A commit with its diff against the first parent
+pub fn commit_details(worktree_path: &Path, sha: &str) -> Result<CommitDetails, AppError> {
+    if sha.starts_with('-') {
+        return Err(AppError::invalid(format!("Invalid commit: {}", sha)));
+    }
+    let output = git::run(worktree_path, &[
+        "-c", "core.quotePath=false",
+        "show", "-z", "--numstat", "--find-renames", "--diff-merges=first-parent",
+        &format!("--format={}", COMMIT_FORMAT), sha, "--",
+    ])?;
+    let commit = parse_log(&String::from_utf8_lossy(&output))
+        .into_iter()
+        .next()
+        .ok_or_else(|| AppError::new(
+            ErrorKind::CommitNotFound { sha: sha.to_string() },
+            format!("Commit not found: {}", sha),
+        ))?;
+    let diff = commit_diff(worktree_path, &commit.sha, DEFAULT_CONTEXT_LINES)?;
+
+    Ok(CommitDetails { commit, diff })
+}
+
+/// Parse `git log -z --numstat` output in [`COMMIT_FORMAT`]
+fn parse_log(output: &str) -> Vec<CommitInfo> {
+    output
+        .split('\x1e')
+        .filter_map(|record| {
+            let (header, stats) = record.split_once('\x1f')?;
+            let fields: Vec<&str> = header.split('\0').collect();
+            if fields.len() != 12 {
+                return None;
+            }
+
+            let files = parse_numstat(stats);
+            Some(CommitInfo {
+                sha: fields[0].to_string(),
+                short_sha: fields[1].to_string(),
+                parents: fields[2].split_whitespace().map(str::to_string).collect(),
+                author_name: fields[3].to_string(),
+                author_email: fields[4].to_string(),
+                author_date: fields[5].to_string(),
+                committer_name: fields[6].to_string(),
+                committer_email: fields[7].to_string(),
+                committer_date: fields[8].to_string(),
+                refs: fields[9].split(", ").filter(|r| !r.is_empty()).map(str::to_string).collect(),
+                subject: fields[10].to_string(),
+                body: fields[11].trim_end().to_string(),
+                additions: files.iter().filter_map(|file| file.additions).sum(),
+                deletions: files.iter().filter_map(|file| file.deletions).sum(),
+                files,
+                graph: None,
+            })
+        })
+        .collect()
+}
+
+/// `--numstat -z` entries: `adds\tdels\tpath`, or `adds\tdels\t` followed by the old and new path
+fn parse_numstat(stats: &str) -> Vec<FileStat> {
+    let mut entries = stats.trim_start_matches(['\0', '\n']).split('\0');
+    let mut files = Vec::new();
+
+    while let Some(entry) = entries.next() {
+        let mut parts = entry.splitn(3, '\t');
+        let (additions, deletions, path) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(additions), Some(deletions), Some(path)) => (additions, deletions, path),
+            _ => continue,
+        };
+
+        let (path, orig_path) = if path.is_empty() {
+            let orig_path = entries.next().unwrap_or_default().to_string();
+            (entries.next().unwrap_or_default().to_string(), Some(orig_path))
+        } else {
+            (path.to_string(), None)
+        };
+
+        files.push(FileStat {
+            path,
+            orig_path,
+            additions: additions.parse().ok(),
+            deletions: deletions.parse().ok(),
+        });
+    }
+
+    files
+}
+
+/// Graph rows for the first `count` commits matching `filters`
+fn graph_rows(
+    worktree_path: &Path,
+    filters: &[String],
+    count: u32,
+    cancel: &CancellationToken,
+) -> Result<HashMap<String, GraphRow>, AppError> {
+    let mut args = vec!["rev-list".to_string(), "--parents".to_string(), format!("--max-count={}", count)];
+    args.extend(filters.iter().cloned());
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let output = String::from_utf8_lossy(&git::run_cancellable(worktree_path, &args, cancel)?).into_owned();
+
+    let commits: Vec<(String, Vec<String>)> = output
+        .lines()
+        .filter_map(|line| {
+            let mut shas = line.split_whitespace().map(str::to_string);
+            Some((shas.next()?, shas.collect()))
+        })
+        .collect();
+
+    Ok(layout_graph(&commits))
+}
+
+/// Assign lanes to commits listed children-first
+pub fn layout_graph(commits: &[(String, Vec<String>)]) -> HashMap<String, GraphRow> {
+    // Each lane holds the commit it's waiting for
+    let mut lanes: Vec<Option<String>> = Vec::new();
+    let mut rows = HashMap::new();
+
+    for (sha, parents) in commits {
+        let waiting: Vec<usize> = lanes.iter()
+            .enumerate()
+            .filter(|(_, expected)| expected.as_deref() == Some(sha.as_str()))
+            .map(|(lane, _)| lane)
+            .collect();
+
+        let lane = match waiting.first() {
+            Some(lane) => *lane,
+            None => free_lane(&mut lanes),
+        };
+        let merging_lanes: Vec<usize> = waiting.iter().copied().filter(|l| *l != lane).collect();
+        for merged in &merging_lanes {
+            lanes[*merged] = None;
+        }
+
+        let passing_lanes: Vec<usize> = lanes.iter()
+            .enumerate()
+            .filter(|(l, expected)| *l != lane && expected.is_some())
+            .map(|(l, _)| l)
+            .collect();
+
+        let mut parent_lanes = Vec::new();
+        lanes[lane] = None;
+        for (index, parent) in parents.iter().enumerate() {
+            // The first parent continues straight down (lanes waiting for the same commit
+            // merge where it's drawn); other parents join a lane already heading there
+            // or branch off into a free one
+            let existing = lanes.iter().position(|expected| expected.as_deref() == Some(parent.as_str()));
+            let parent_lane = match existing {
+                _ if index == 0 => lane,
+                Some(existing) => existing,
+                None => free_lane(&mut lanes),
+            };
+            lanes[parent_lane] = Some(parent.clone());
+            parent_lanes.push(parent_lane);
+        }
+
+        while lanes.last().is_some_and(|expected| expected.is_none()) {
+            lanes.pop();
+        }
+
+        rows.insert(sha.clone(), GraphRow {
+            lane,
+            merging_lanes,
+            parent_lanes,
+            passing_lanes,
+        });
+    }
+
+    rows
+}
+
+fn free_lane(lanes: &mut Vec<Option<String>>) -> usize {
+    match lanes.iter().position(Option::is_none) {
+        Some(lane) => lane,
+        None => {
+            lanes.push(None);
+            lanes.len() - 1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::fixture::TempRepo;
+
+    #[test]
+    fn test_layout_graph_branch_and_merge() {
+        let commit = |sha: &str, parents: &[&str]| {
+            (sha.to_string(), parents.iter().map(|p| p.to_string()).collect::<Vec<_>>())
+        };
+        // m merges f into main; f branched from a
+        let rows = layout_graph(&[
+            commit("m", &["c", "f"]),
+            commit("f", &["a"]),
+            commit("c", &["b"]),
+            commit("b", &["a"]),
+            commit("a", &[]),
+        ]);
+
+        assert_eq!(rows["m"].lane, 0);
+        assert_eq!(rows["m"].parent_lanes, vec![0, 1]);
+        assert_eq!(rows["f"].lane, 1);
+        assert_eq!(rows["f"].passing_lanes, vec![0]);
+        assert_eq!(rows["c"].lane, 0);
+        assert_eq!(rows["c"].passing_lanes, vec![1]);
+        assert_eq!(rows["a"].lane, 0);
+        assert_eq!(rows["a"].merging_lanes, vec![1]);
+        assert!(rows["a"].parent_lanes.is_empty());
+    }
+
+    #[test]
+    fn test_commit_log_paging_and_details() {
+        let repo = TempRepo::new();
+        for i in 1..=5 {
+            repo.write("counter.txt", &format!("{}\n", i));
+            repo.write(&format!("file{}.txt", i), "x\ny\n");
+            repo.commit_all(&format!("Commit {}\n\nBody of {}", i, i));
+        }
+        repo.git(&["mv", "file1.txt", "renamed.txt"]);
+        repo.commit_all("Rename");
+        repo.git(&["tag", "v1"]);
+        let cancel = CancellationToken::new();
+
+        let first = commit_log(repo.path(), &LogQuery { limit: Some(2), ..Default::default() }, &cancel).unwrap();
+        assert!(first.has_more);
+        assert_eq!(first.commits.len(), 2);
+        let head = &first.commits[0];
+        assert_eq!(head.subject, "Rename");
+        assert!(head.refs.iter().any(|r| r == "tag: v1"));
+        assert_eq!(head.files[0].orig_path.as_deref(), Some("file1.txt"));
+        assert_eq!(head.files[0].path, "renamed.txt");
+        assert_eq!(first.commits[1].body, "Body of 5");
+        assert_eq!((first.commits[1].additions, first.commits[1].deletions), (3, 1));
+        assert_eq!(first.commits[1].graph.as_ref().unwrap().lane, 0);
+
+        let last = commit_log(repo.path(), &LogQuery { skip: 4, limit: Some(2), ..Default::default() }, &cancel).unwrap();
+        assert!(!last.has_more);
+        assert_eq!(last.commits[1].subject, "Commit 1");
+        assert!(last.commits[1].parents.is_empty());
+
+        let filtered = commit_log(repo.path(), &LogQuery {
+            path_filter: Some("file3.txt".to_string()),
+            ..Default::default()
+        }, &cancel).unwrap();
+        assert_eq!(filtered.commits.len(), 1);
+        assert_eq!(filtered.commits[0].subject, "Commit 3");
+
+        let details = commit_details(repo.path(), &filtered.commits[0].sha).unwrap();
+        assert_eq!(details.commit.subject, "Commit 3");
+        assert_eq!(details.diff.len(), 2);
+        assert!(commit_details(repo.path(), "--output=/tmp/x").is_err());
+    }
+}
@@ -0,0 +1,139 @@
+pub mod backend;
+pub mod blame;
+pub mod branch;
+pub mod commit;
+pub mod compare;
+pub mod conflict;
+pub mod diff;
+pub mod libgit2;
+pub mod log;
+pub mod operation;
+pub mod remote;
+pub mod revision;
+pub mod snapshot;
+pub mod stage;
+pub mod stash;
+pub mod transfer;
+pub mod status;
+pub mod tag;
+pub mod watch;
+pub mod worktree;
+
+#[cfg(test)]
+pub mod fixture;
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+use tokio_util::sync::CancellationToken;
+
+use crate::error::{AppError, ErrorKind};
+use crate::process::{self, RunOptions};
+
+/// Run git in `repo` and return raw stdout, or a [`ErrorKind::GitFailed`] with its stderr
+pub fn run(repo: &Path, args: &[&str]) -> Result<Vec<u8>, AppError> {
+    run_allowing(repo, args, &[])
+}
+
+/// Like [`run`], also treating the given non-zero exit codes as success
+pub fn run_allowing(repo: &Path, args: &[&str], exit_codes: &[i32]) -> Result<Vec<u8>, AppError> {
+    run_with_options(repo, args, exit_codes, RunOptions::default())
+}
+
+/// Like [`run`], writing `input` to git's stdin
+pub fn run_with_input(repo: &Path, args: &[&str], input: &[u8]) -> Result<Vec<u8>, AppError> {
+    let options = RunOptions { stdin: Some(input.to_vec()), ..Default::default() };
+    run_with_options(repo, args, &[], options)
+}
+
+/// Like [`run`], with `GIT_INDEX_FILE` pointing at a scratch index
+pub fn run_with_index(repo: &Path, index: &Path, args: &[&str]) -> Result<Vec<u8>, AppError> {
+    let options = RunOptions {
+        env: vec![("GIT_INDEX_FILE".to_string(), index.to_string_lossy().into_owned())],
+        ..Default::default()
+    };
+    run_with_options(repo, args, &[], options)
+}
+
+/// Like [`run`], killing git when `cancel` fires
+pub fn run_cancellable(repo: &Path, args: &[&str], cancel: &CancellationToken) -> Result<Vec<u8>, AppError> {
+    let options = RunOptions { cancel: Some(cancel.clone()), ..Default::default() };
+    run_with_options(repo, args, &[], options)
+}
+
+/// For in-process work between git calls: stop once `cancel` has fired
+pub fn check_cancelled(cancel: &CancellationToken, what: &str) -> Result<(), AppError> {
+    if cancel.is_cancelled() {
+        return Err(AppError::cancelled(what));
+    }
+    Ok(())
+}
+
+fn run_with_options(repo: &Path, args: &[&str], exit_codes: &[i32], options: RunOptions) -> Result<Vec<u8>, AppError> {
+    check_output(output(repo, args, options)?, repo, args, exit_codes)
+}
+
+/// Run git in `repo` and return its output whatever the exit code, for callers that inspect failures.
+/// Blocks until git exits; on the runtime the worker is handed off meanwhile.
+pub fn output(repo: &Path, args: &[&str], options: RunOptions) -> Result<Output, AppError> {
+    process::block_on(process::git(repo, args, options))
+}
+
+/// Git couldn't be started at all
+pub fn spawn_error(error: io::Error) -> AppError {
+    if error.kind() == io::ErrorKind::NotFound {
+        return AppError::new(ErrorKind::GitNotFound, "Git is not installed or not on PATH");
+    }
+    AppError::io("Failed to run git", error)
+}
+
+/// Stdout of a finished git command, or the error its failure maps to
+pub fn check_output(output: Output, repo: &Path, args: &[&str], exit_codes: &[i32]) -> Result<Vec<u8>, AppError> {
+    let allowed = output.status.code().is_some_and(|code| exit_codes.contains(&code));
+    if !output.status.success() && !allowed {
+        // Name the subcommand, skipping options like `-c key=value`
+        let subcommand = args.iter()
+            .find(|arg| !arg.starts_with('-') && !arg.contains('='))
+            .unwrap_or(&"");
+        return Err(git_error(repo, subcommand, &String::from_utf8_lossy(&output.stderr), output.status.code()));
+    }
+
+    Ok(output.stdout)
+}
+
+/// Classify a failed git command; the common failures get their own codes
+pub fn git_error(repo: &Path, subcommand: &str, stderr: &str, exit_code: Option<i32>) -> AppError {
+    let stderr = stderr.trim();
+    if !repo.exists() {
+        return AppError::path_not_found(repo.display());
+    }
+    if stderr.contains("not a git repository") {
+        return AppError::new(
+            ErrorKind::NotAGitRepo { path: repo.display().to_string() },
+            format!("{} is not a git repository", repo.display()),
+        );
+    }
+    AppError::new(
+        ErrorKind::GitFailed {
+            command: subcommand.to_string(),
+            stderr: stderr.to_string(),
+            exit_code,
+        },
+        format!("git {} failed: {}", subcommand, stderr),
+    )
+}
+
+/// Like [`run`], for commands that print a single value
+pub fn run_text(repo: &Path, args: &[&str]) -> Result<String, AppError> {
+    run(repo, args).map(|stdout| String::from_utf8_lossy(&stdout).trim().to_string())
+}
+
+/// The git directory shared by all worktrees of the repository `repo` belongs to
+pub fn common_dir(repo: &Path) -> Result<PathBuf, AppError> {
+    run_text(repo, &["rev-parse", "--path-format=absolute", "--git-common-dir"]).map(PathBuf::from)
+}
+
+/// Same heuristic as git: a NUL byte in the first 8000 bytes
+pub fn is_binary(content: &[u8]) -> bool {
+    content.iter().take(8000).any(|b| *b == 0)
+}
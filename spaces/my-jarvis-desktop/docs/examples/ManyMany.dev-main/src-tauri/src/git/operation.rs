@@ -0,0 +1,394 @@
+//! Merge, rebase and cherry-pick, and the multi-step state they leave behind in `.git`.
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AppError, ErrorKind};
+use crate::git;
+use crate::process::RunOptions;
+
+/// A merge, rebase, cherry-pick, revert or bisect that stopped part way
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum OperationInProgress {
+    Merge {
+        /// The commit being merged in
+        head: Option<String>,
+    },
+    Rebase {
+        /// 1-based step currently being applied, of `total`
+        step: Option<u32>,
+        total: Option<u32>,
+        /// Branch being rebased, e.g. `feature`
+        branch: Option<String>,
+        onto: Option<String>,
+    },
+    CherryPick {
+        commit: Option<String>,
+    },
+    Revert {
+        commit: Option<String>,
+    },
+    Bisect,
+}
+
+impl fmt::Display for OperationInProgress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let short = |oid: &Option<String>| oid.as_deref().map(|oid| format!(" of {}", &oid[..oid.len().min(8)]));
+        match self {
+            OperationInProgress::Merge { head } => write!(f, "a merge{}", short(head).unwrap_or_default()),
+            OperationInProgress::Rebase { step, total, branch, .. } => {
+                write!(f, "a rebase")?;
+                if let Some(branch) = branch {
+                    write!(f, " of {}", branch)?;
+                }
+                if let (Some(step), Some(total)) = (step, total) {
+                    write!(f, " (step {} of {})", step, total)?;
+                }
+                Ok(())
+            }
+            OperationInProgress::CherryPick { commit } => write!(f, "a cherry-pick{}", short(commit).unwrap_or_default()),
+            OperationInProgress::Revert { commit } => write!(f, "a revert{}", short(commit).unwrap_or_default()),
+            OperationInProgress::Bisect => write!(f, "a bisect"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FastForward {
+    /// Fast-forward when possible, otherwise create a merge commit
+    Allow,
+    /// Always create a merge commit
+    Never,
+    /// Fail unless the merge is a fast-forward
+    Only,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergeRequest {
+    /// Branch or commit to merge into the current branch
+    pub reference: String,
+    #[serde(default = "default_fast_forward")]
+    pub fast_forward: FastForward,
+    /// Merge commit message; git's default when `None`
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+fn default_fast_forward() -> FastForward {
+    FastForward::Allow
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RebaseRequest {
+    /// Upstream to rebase the current branch onto
+    pub onto: String,
+    /// Fold `fixup!`/`squash!` commits into their targets
+    #[serde(default)]
+    pub autosquash: bool,
+}
+
+/// Where a merge, rebase or cherry-pick ended up
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OperationResult {
+    /// `None` when the operation finished; otherwise it stopped, usually on conflicts
+    pub in_progress: Option<OperationInProgress>,
+    /// Paths with unresolved conflicts
+    pub conflicts: Vec<String>,
+    pub output: String,
+}
+
+pub fn merge(worktree_path: &Path, request: &MergeRequest) -> Result<OperationResult, AppError> {
+    ensure_idle(worktree_path)?;
+    let mut args = vec!["merge".to_string(), "--no-edit".to_string()];
+    args.push(match request.fast_forward {
+        FastForward::Allow => "--ff".to_string(),
+        FastForward::Never => "--no-ff".to_string(),
+        FastForward::Only => "--ff-only".to_string(),
+    });
+    if let Some(message) = &request.message {
+        args.extend(["-m".to_string(), message.clone()]);
+    }
+    args.extend(["--".to_string(), request.reference.clone()]);
+
+    run_step(worktree_path, &args)
+}
+
+/// Rebase the current branch without stopping for an editor
+pub fn rebase(worktree_path: &Path, request: &RebaseRequest) -> Result<OperationResult, AppError> {
+    ensure_idle(worktree_path)?;
+    if request.onto.starts_with('-') {
+        return Err(AppError::invalid(format!("Invalid upstream: {}", request.onto)));
+    }
+    let mut args = vec!["rebase".to_string()];
+    if request.autosquash {
+        // Older git only autosquashes interactive rebases; the todo list is accepted as generated
+        args.extend(["--interactive".to_string(), "--autosquash".to_string()]);
+    }
+    args.push(request.onto.clone());
+
+    run_step(worktree_path, &args)
+}
+
+/// Apply `commits` in order on top of the current branch
+pub fn cherry_pick(worktree_path: &Path, commits: &[String]) -> Result<OperationResult, AppError> {
+    ensure_idle(worktree_path)?;
+    if commits.is_empty() {
+        return Err(AppError::invalid("No commits selected"));
+    }
+    if let Some(commit) = commits.iter().find(|commit| commit.starts_with('-')) {
+        return Err(AppError::invalid(format!("Invalid commit: {}", commit)));
+    }
+    let mut args = vec!["cherry-pick".to_string()];
+    args.extend(commits.iter().cloned());
+
+    run_step(worktree_path, &args)
+}
+
+/// Resume the stopped operation once its conflicts are resolved and staged
+pub fn continue_operation(worktree_path: &Path) -> Result<OperationResult, AppError> {
+    let command = match require_operation(worktree_path)? {
+        // A merge is concluded by committing it
+        OperationInProgress::Merge { .. } => "merge",
+        OperationInProgress::Rebase { .. } => "rebase",
+        OperationInProgress::CherryPick { .. } => "cherry-pick",
+        OperationInProgress::Revert { .. } => "revert",
+        OperationInProgress::Bisect => return Err(AppError::invalid("A bisect can't be continued, only aborted")),
+    };
+    run_step(worktree_path, &[command.to_string(), "--continue".to_string()])
+}
+
+/// Skip the commit the operation stopped on
+pub fn skip_operation(worktree_path: &Path) -> Result<OperationResult, AppError> {
+    let command = match require_operation(worktree_path)? {
+        OperationInProgress::Rebase { .. } => "rebase",
+        OperationInProgress::CherryPick { .. } => "cherry-pick",
+        OperationInProgress::Revert { .. } => "revert",
+        OperationInProgress::Merge { .. } | OperationInProgress::Bisect => {
+            return Err(AppError::invalid("Only a rebase, cherry-pick or revert can skip a commit"))
+        }
+    };
+    run_step(worktree_path, &[command.to_string(), "--skip".to_string()])
+}
+
+/// Give up on the operation and return to where it started
+pub fn abort_operation(worktree_path: &Path) -> Result<OperationResult, AppError> {
+    let args = match require_operation(worktree_path)? {
+        OperationInProgress::Merge { .. } => ["merge", "--abort"],
+        OperationInProgress::Rebase { .. } => ["rebase", "--abort"],
+        OperationInProgress::CherryPick { .. } => ["cherry-pick", "--abort"],
+        OperationInProgress::Revert { .. } => ["revert", "--abort"],
+        OperationInProgress::Bisect => ["bisect", "reset"],
+    };
+    run_step(worktree_path, &args.map(String::from))
+}
+
+/// Read the operation in progress from the worktree's git directory
+pub fn detect_operation(worktree_path: &Path) -> Result<Option<OperationInProgress>, AppError> {
+    let git_dir = PathBuf::from(git::run_text(worktree_path, &["rev-parse", "--absolute-git-dir"])?);
+    Ok(operation_in_git_dir(&git_dir))
+}
+
+/// Like [`detect_operation`], given the worktree's own git directory
+pub fn operation_in_git_dir(git_dir: &Path) -> Option<OperationInProgress> {
+    let read = |name: &str| {
+        fs::read_to_string(git_dir.join(name))
+            .ok()
+            .map(|content| content.trim().to_string())
+            .filter(|content| !content.is_empty())
+    };
+    let number = |name: &str| read(name).and_then(|value| value.parse().ok());
+    let branch = |name: &str| read(name).map(|head| head.trim_start_matches("refs/heads/").to_string());
+
+    let operation = if git_dir.join("rebase-merge").is_dir() {
+        OperationInProgress::Rebase {
+            step: number("rebase-merge/msgnum"),
+            total: number("rebase-merge/end"),
+            branch: branch("rebase-merge/head-name"),
+            onto: read("rebase-merge/onto"),
+        }
+    } else if git_dir.join("rebase-apply").is_dir() {
+        OperationInProgress::Rebase {
+            step: number("rebase-apply/next"),
+            total: number("rebase-apply/last"),
+            branch: branch("rebase-apply/head-name"),
+            onto: read("rebase-apply/onto"),
+        }
+    } else if git_dir.join("MERGE_HEAD").is_file() {
+        OperationInProgress::Merge { head: read("MERGE_HEAD") }
+    } else if git_dir.join("CHERRY_PICK_HEAD").is_file() {
+        OperationInProgress::CherryPick { commit: read("CHERRY_PICK_HEAD") }
+    } else if git_dir.join("REVERT_HEAD").is_file() {
+        OperationInProgress::Revert { commit: read("REVERT_HEAD") }
+    } else if git_dir.join("BISECT_LOG").is_file() {
+        OperationInProgress::Bisect
+    } else {
+        return None;
+    };
+    Some(operation)
+}
+
+fn ensure_idle(worktree_path: &Path) -> Result<(), AppError> {
+    match detect_operation(worktree_path)? {
+        Some(operation) => Err(AppError::new(
+            ErrorKind::OperationInProgress { operation: operation.clone() },
+            format!("Finish or abort {} before starting another operation", operation),
+        )),
+        None => Ok(()),
+    }
+}
+
+fn require_operation(worktree_path: &Path) -> Result<OperationInProgress, AppError> {
+    detect_operation(worktree_path)?.ok_or_else(|| AppError::invalid("No merge, rebase or cherry-pick in progress"))
+}
+
+/// Run a step that may stop on conflicts. Stopping isn't an error; any other failure is.
+fn run_step(worktree_path: &Path, args: &[String]) -> Result<OperationResult, AppError> {
+    let options = RunOptions {
+        // Accept generated messages and todo lists instead of opening an editor
+        env: vec![
+            ("GIT_EDITOR".to_string(), "true".to_string()),
+            ("GIT_SEQUENCE_EDITOR".to_string(), "true".to_string()),
+        ],
+        ..Default::default()
+    };
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let result = git::output(worktree_path, &args, options)?;
+
+    let output = format!(
+        "{}{}",
+        String::from_utf8_lossy(&result.stdout),
+        String::from_utf8_lossy(&result.stderr)
+    )
+    .trim_end()
+    .to_string();
+    let in_progress = detect_operation(worktree_path)?;
+    let conflicts = conflicted_paths(worktree_path)?;
+
+    if !result.status.success() && in_progress.is_none() && conflicts.is_empty() {
+        return Err(git::git_error(worktree_path, args[0], &output, result.status.code()));
+    }
+    Ok(OperationResult {
+        in_progress,
+        conflicts,
+        output,
+    })
+}
+
+/// Paths with unresolved conflicts
+pub fn conflicted_paths(worktree_path: &Path) -> Result<Vec<String>, AppError> {
+    let output = git::run(worktree_path, &["diff", "--name-only", "--diff-filter=U", "-z"])?;
+    Ok(output
+        .split(|b| *b == 0)
+        .filter(|path| !path.is_empty())
+        .map(|path| String::from_utf8_lossy(path).into_owned())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::fixture::TempRepo;
+
+    /// `main` and `topic` both change a.txt after the initial commit
+    fn diverged_repo() -> TempRepo {
+        let repo = TempRepo::new();
+        repo.write("a.txt", "base\n");
+        repo.commit_all("initial");
+        repo.git(&["checkout", "-q", "-b", "topic"]);
+        repo.write("a.txt", "topic\n");
+        repo.commit_all("topic change");
+        repo.write("b.txt", "b\n");
+        repo.commit_all("topic adds b");
+        repo.git(&["checkout", "-q", "main"]);
+        repo.write("a.txt", "main\n");
+        repo.commit_all("main change");
+        repo
+    }
+
+    #[test]
+    fn test_merge_conflict_continue_and_abort() {
+        let repo = diverged_repo();
+        let request = MergeRequest {
+            reference: "topic".to_string(),
+            fast_forward: FastForward::Allow,
+            message: None,
+        };
+
+        let stopped = merge(repo.path(), &request).unwrap();
+        assert!(matches!(stopped.in_progress, Some(OperationInProgress::Merge { head: Some(_) })));
+        assert_eq!(stopped.conflicts, vec!["a.txt"]);
+        let busy = merge(repo.path(), &request).unwrap_err();
+        assert!(matches!(busy.kind, ErrorKind::OperationInProgress { .. }));
+        assert!(busy.to_string().starts_with("Finish or abort a merge of "), "{}", busy);
+        assert!(skip_operation(repo.path()).is_err());
+
+        abort_operation(repo.path()).unwrap();
+        assert_eq!(detect_operation(repo.path()).unwrap(), None);
+        assert_eq!(repo.read("a.txt"), "main\n");
+
+        merge(repo.path(), &request).unwrap();
+        repo.write("a.txt", "resolved\n");
+        repo.git(&["add", "a.txt"]);
+        let finished = continue_operation(repo.path()).unwrap();
+        assert_eq!(finished.in_progress, None);
+        assert_eq!(repo.git(&["rev-list", "--count", "--merges", "HEAD"]).trim(), "1");
+
+        let error = merge(repo.path(), &MergeRequest { reference: "no-such-branch".to_string(), ..request }).unwrap_err();
+        assert!(matches!(&error.kind, ErrorKind::GitFailed { command, .. } if command == "merge"), "{:?}", error.kind);
+        assert!(error.to_string().contains("no-such-branch"), "{}", error);
+    }
+
+    #[test]
+    fn test_rebase_steps_and_skip() {
+        let repo = diverged_repo();
+        repo.git(&["checkout", "-q", "topic"]);
+
+        let stopped = rebase(repo.path(), &RebaseRequest { onto: "main".to_string(), autosquash: false }).unwrap();
+        match stopped.in_progress {
+            Some(OperationInProgress::Rebase { step, total, branch, .. }) => {
+                assert_eq!((step, total), (Some(1), Some(2)));
+                assert_eq!(branch.as_deref(), Some("topic"));
+            }
+            other => panic!("expected a rebase, got {:?}", other),
+        }
+
+        // Dropping the conflicting commit lets the rest apply
+        let finished = skip_operation(repo.path()).unwrap();
+        assert_eq!(finished.in_progress, None);
+        assert_eq!(repo.read("a.txt"), "main\n");
+        assert_eq!(repo.git(&["log", "-1", "--format=%s"]).trim(), "topic adds b");
+    }
+
+    #[test]
+    fn test_autosquash_and_cherry_pick() {
+        let repo = TempRepo::new();
+        repo.write("a.txt", "a\n");
+        repo.commit_all("initial");
+        repo.git(&["checkout", "-q", "-b", "topic"]);
+        repo.write("b.txt", "b\n");
+        repo.commit_all("add b");
+        repo.write("b.txt", "b fixed\n");
+        repo.commit_all("fixup! add b");
+
+        let finished = rebase(repo.path(), &RebaseRequest { onto: "main".to_string(), autosquash: true }).unwrap();
+        assert_eq!(finished.in_progress, None);
+        assert_eq!(repo.git(&["log", "--format=%s", "main..topic"]).trim(), "add b");
+        assert_eq!(repo.read("b.txt"), "b fixed\n");
+
+        repo.git(&["checkout", "-q", "main"]);
+        let picked = cherry_pick(repo.path(), &["topic".to_string()]).unwrap();
+        assert_eq!(picked.in_progress, None);
+        assert_eq!(repo.read("b.txt"), "b fixed\n");
+
+        repo.write("b.txt", "conflicting\n");
+        repo.commit_all("conflicting b");
+        let stopped = cherry_pick(repo.path(), &["topic".to_string()]).unwrap();
+        assert!(matches!(stopped.in_progress, Some(OperationInProgress::CherryPick { commit: Some(_) })));
+        abort_operation(repo.path()).unwrap();
+        assert_eq!(repo.read("b.txt"), "conflicting\n");
+    }
+}
@@ -0,0 +1,466 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncReadExt;
+use tokio_util::sync::CancellationToken;
+
+use crate::process::{self, RunOptions};
+
+/// Why a fetch, pull or push failed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RemoteError {
+    /// Credentials were missing or rejected
+    AuthFailed { message: String },
+    HostKeyVerificationFailed { message: String },
+    RepositoryNotFound { message: String },
+    Network { message: String },
+    /// The remote refused the update, e.g. not a fast-forward or a stale lease
+    Rejected { message: String },
+    NoUpstream { message: String },
+    Cancelled,
+    Failed { message: String, exit_code: Option<i32> },
+}
+
+impl fmt::Display for RemoteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RemoteError::AuthFailed { message } => write!(f, "Authentication failed: {}", message),
+            RemoteError::HostKeyVerificationFailed { message } => write!(f, "Host key verification failed: {}", message),
+            RemoteError::RepositoryNotFound { message } => write!(f, "Repository not found: {}", message),
+            RemoteError::Network { message } => write!(f, "Network error: {}", message),
+            RemoteError::Rejected { message } => write!(f, "Rejected by remote: {}", message),
+            RemoteError::NoUpstream { message } => write!(f, "No upstream branch: {}", message),
+            RemoteError::Cancelled => write!(f, "Cancelled"),
+            RemoteError::Failed { message, .. } => write!(f, "{}", message),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PullMode {
+    Merge,
+    Rebase,
+    FfOnly,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PushOptions {
+    /// Defaults to the branch's upstream remote, then `origin`
+    #[serde(default)]
+    pub remote: Option<String>,
+    /// Defaults to the current branch
+    #[serde(default)]
+    pub branch: Option<String>,
+    #[serde(default)]
+    pub force_with_lease: bool,
+    #[serde(default)]
+    pub set_upstream: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RemoteResult {
+    /// git's final (non-progress) output
+    pub output: String,
+}
+
+/// One line of `--progress` output
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GitProgress {
+    pub operation_id: String,
+    pub line: String,
+    /// e.g. `Receiving objects`
+    pub phase: Option<String>,
+    pub percent: Option<u8>,
+}
+
+/// Cancellation handles of running remote operations, by operation id
+#[derive(Debug, Default)]
+pub struct GitOperations {
+    running: Mutex<HashMap<String, CancellationToken>>,
+}
+
+impl GitOperations {
+    pub fn register(&self, operation_id: &str) -> CancellationToken {
+        let cancel = CancellationToken::new();
+        self.running.lock().unwrap().insert(operation_id.to_string(), cancel.clone());
+        cancel
+    }
+
+    pub fn finish(&self, operation_id: &str) {
+        self.running.lock().unwrap().remove(operation_id);
+    }
+
+    /// Returns false if no such operation is running
+    pub fn cancel(&self, operation_id: &str) -> bool {
+        match self.running.lock().unwrap().remove(operation_id) {
+            Some(cancel) => {
+                cancel.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Fetch `remote`, or every remote when `None`
+pub async fn fetch(
+    repo: &Path,
+    remote: Option<&str>,
+    prune: bool,
+    on_progress: &mut (dyn FnMut(&str) + Send),
+    cancel: CancellationToken,
+) -> Result<RemoteResult, RemoteError> {
+    let mut args = vec!["fetch".to_string(), "--progress".to_string()];
+    if prune {
+        args.push("--prune".to_string());
+    }
+    match remote {
+        Some(remote) => {
+            args.push("--".to_string());
+            args.push(remote.to_string());
+        }
+        None => args.push("--all".to_string()),
+    }
+    run_remote(repo, &args, on_progress, cancel).await
+}
+
+/// Pull the current branch's upstream; `None` follows the user's `pull.rebase`/`pull.ff` config
+pub async fn pull(
+    repo: &Path,
+    mode: Option<PullMode>,
+    on_progress: &mut (dyn FnMut(&str) + Send),
+    cancel: CancellationToken,
+) -> Result<RemoteResult, RemoteError> {
+    let mut args = vec!["pull".to_string(), "--progress".to_string()];
+    match mode {
+        Some(PullMode::Merge) => args.push("--no-rebase".to_string()),
+        Some(PullMode::Rebase) => args.push("--rebase".to_string()),
+        Some(PullMode::FfOnly) => args.push("--ff-only".to_string()),
+        None => {}
+    }
+    run_remote(repo, &args, on_progress, cancel).await
+}
+
+pub async fn push(
+    repo: &Path,
+    options: &PushOptions,
+    on_progress: &mut (dyn FnMut(&str) + Send),
+    cancel: CancellationToken,
+) -> Result<RemoteResult, RemoteError> {
+    let mut args = vec!["push".to_string(), "--progress".to_string()];
+    if options.force_with_lease {
+        args.push("--force-with-lease".to_string());
+    }
+    if options.set_upstream {
+        args.push("--set-upstream".to_string());
+    }
+
+    if options.remote.is_some() || options.branch.is_some() || options.set_upstream {
+        let branch = match &options.branch {
+            Some(branch) => branch.clone(),
+            None => current_branch(repo).await?,
+        };
+        let remote = match &options.remote {
+            Some(remote) => remote.clone(),
+            None => upstream_remote(repo, &branch).await.unwrap_or_else(|| "origin".to_string()),
+        };
+        args.extend(["--".to_string(), remote, branch]);
+    }
+
+    run_remote(repo, &args, on_progress, cancel).await
+}
+
+/// Push a single tag, or delete it on the remote
+pub async fn push_tag(
+    repo: &Path,
+    remote: Option<&str>,
+    tag: &str,
+    delete: bool,
+    on_progress: &mut (dyn FnMut(&str) + Send),
+    cancel: CancellationToken,
+) -> Result<RemoteResult, RemoteError> {
+    let refspec = if delete {
+        format!(":refs/tags/{}", tag)
+    } else {
+        format!("refs/tags/{0}:refs/tags/{0}", tag)
+    };
+    let args = ["push", "--progress", "--", remote.unwrap_or("origin"), &refspec].map(String::from);
+    run_remote(repo, &args, on_progress, cancel).await
+}
+
+async fn current_branch(repo: &Path) -> Result<String, RemoteError> {
+    let output = process::git(repo, &["symbolic-ref", "--short", "-q", "HEAD"], RunOptions::default())
+        .await
+        .map_err(|e| RemoteError::Failed { message: e.to_string(), exit_code: None })?;
+    let branch = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if branch.is_empty() {
+        return Err(RemoteError::Failed { message: "HEAD is detached".to_string(), exit_code: None });
+    }
+    Ok(branch)
+}
+
+async fn upstream_remote(repo: &Path, branch: &str) -> Option<String> {
+    let output = process::git(repo, &["config", "--get", &format!("branch.{}.remote", branch)], RunOptions::default())
+        .await
+        .ok()?;
+    let remote = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!remote.is_empty()).then_some(remote)
+}
+
+/// Run a remote git command, reporting each progress line, until it exits or is cancelled
+async fn run_remote(
+    repo: &Path,
+    args: &[String],
+    on_progress: &mut (dyn FnMut(&str) + Send),
+    cancel: CancellationToken,
+) -> Result<RemoteResult, RemoteError> {
+    let _permit = process::repo_permit(repo).await;
+    // Prompts off and messages in English, which error classification matches
+    let mut child = process::git_command(repo)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| RemoteError::Failed { message: format!("Failed to run git: {}", e), exit_code: None })?;
+
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let stdout_task = tokio::spawn(async move {
+        let mut output = Vec::new();
+        let _ = stdout.read_to_end(&mut output).await;
+        output
+    });
+    let mut stderr = child.stderr.take().expect("stderr is piped");
+
+    let mut transcript = String::new();
+    let finished = async {
+        let mut buffer = [0u8; 4096];
+        let mut pending = Vec::new();
+        loop {
+            let read = stderr.read(&mut buffer).await.unwrap_or(0);
+            if read == 0 {
+                break;
+            }
+            for &byte in &buffer[..read] {
+                // Progress updates end in \r, everything else in \n
+                if byte != b'\r' && byte != b'\n' {
+                    pending.push(byte);
+                    continue;
+                }
+                let line = String::from_utf8_lossy(&pending).trim_end().to_string();
+                pending.clear();
+                if line.is_empty() {
+                    continue;
+                }
+                on_progress(&line);
+                if byte == b'\n' {
+                    transcript.push_str(&line);
+                    transcript.push('\n');
+                }
+            }
+        }
+        if !pending.is_empty() {
+            let line = String::from_utf8_lossy(&pending).trim_end().to_string();
+            on_progress(&line);
+            transcript.push_str(&line);
+        }
+        child.wait().await
+    };
+
+    let status = tokio::select! {
+        status = finished => status,
+        // Dropping the child kills git
+        _ = cancel.cancelled() => return Err(RemoteError::Cancelled),
+    }
+    .map_err(|e| RemoteError::Failed { message: format!("Failed to run git: {}", e), exit_code: None })?;
+
+    let stdout = String::from_utf8_lossy(&stdout_task.await.unwrap_or_default()).into_owned();
+    if !status.success() {
+        return Err(classify_error(&format!("{}{}", transcript, stdout), status.code()));
+    }
+
+    Ok(RemoteResult {
+        output: format!("{}{}", transcript, stdout).trim_end().to_string(),
+    })
+}
+
+/// Map git's error output to a [`RemoteError`]
+pub fn classify_error(output: &str, exit_code: Option<i32>) -> RemoteError {
+    let message = output.trim().to_string();
+    let lower = message.to_lowercase();
+    let mentions = |needles: &[&str]| needles.iter().any(|needle| lower.contains(needle));
+
+    if mentions(&[
+        "authentication failed",
+        "could not read username",
+        "could not read password",
+        "terminal prompts disabled",
+        "permission denied (publickey",
+        "invalid username or password",
+        "the requested url returned error: 401",
+        "the requested url returned error: 403",
+    ]) {
+        RemoteError::AuthFailed { message }
+    } else if mentions(&["host key verification failed"]) {
+        RemoteError::HostKeyVerificationFailed { message }
+    } else if mentions(&["repository not found", "does not appear to be a git repository", "the requested url returned error: 404"]) {
+        RemoteError::RepositoryNotFound { message }
+    } else if mentions(&[
+        "could not resolve host",
+        "connection refused",
+        "connection timed out",
+        "network is unreachable",
+        "operation timed out",
+    ]) {
+        RemoteError::Network { message }
+    } else if mentions(&["[rejected]", "stale info", "failed to push some refs", "not possible to fast-forward"]) {
+        RemoteError::Rejected { message }
+    } else if mentions(&["has no upstream branch", "no tracking information"]) {
+        RemoteError::NoUpstream { message }
+    } else {
+        RemoteError::Failed { message, exit_code }
+    }
+}
+
+/// Split a progress line like `remote: Counting objects:  45% (9/20)` into phase and percent
+pub fn parse_progress(operation_id: &str, line: &str) -> GitProgress {
+    let text = line.strip_prefix("remote: ").unwrap_or(line);
+    let (phase, percent) = match text.split_once(':') {
+        Some((phase, rest)) => {
+            let percent = rest.split_once('%')
+                .and_then(|(number, _)| number.trim().parse().ok());
+            (percent.map(|_| phase.trim().to_string()), percent)
+        }
+        None => (None, None),
+    };
+
+    GitProgress {
+        operation_id: operation_id.to_string(),
+        line: line.to_string(),
+        phase,
+        percent,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::fixture::TempRepo;
+
+    fn no_cancel() -> CancellationToken {
+        CancellationToken::new()
+    }
+
+    #[test]
+    fn test_classify_and_parse_progress() {
+        let auth = "fatal: could not read Username for 'https://github.com': terminal prompts disabled";
+        assert!(matches!(classify_error(auth, Some(128)), RemoteError::AuthFailed { .. }));
+        let ssh = "git@github.com: Permission denied (publickey).\nfatal: Could not read from remote repository.";
+        assert!(matches!(classify_error(ssh, Some(128)), RemoteError::AuthFailed { .. }));
+        let offline = "fatal: unable to access 'https://example.com/x.git/': Could not resolve host: example.com";
+        assert!(matches!(classify_error(offline, Some(128)), RemoteError::Network { .. }));
+        assert!(matches!(classify_error("boom", Some(1)), RemoteError::Failed { exit_code: Some(1), .. }));
+
+        let progress = parse_progress("op", "remote: Counting objects:  45% (9/20)");
+        assert_eq!(progress.phase.as_deref(), Some("Counting objects"));
+        assert_eq!(progress.percent, Some(45));
+        assert_eq!(parse_progress("op", "To /tmp/remote.git").percent, None);
+    }
+
+    #[tokio::test]
+    async fn test_push_fetch_pull_against_bare_remote() {
+        let remote = TempRepo::new_bare();
+        let alice = TempRepo::new();
+        alice.write("a.txt", "1\n");
+        alice.commit_all("first");
+        alice.git(&["remote", "add", "origin", remote.path().to_str().unwrap()]);
+
+        let mut lines = Vec::new();
+        let pushed = push(alice.path(), &PushOptions { set_upstream: true, ..Default::default() }, &mut |line| {
+            lines.push(line.to_string())
+        }, no_cancel())
+        .await
+        .unwrap();
+        assert!(pushed.output.contains("main -> main"), "{}", pushed.output);
+        assert!(!lines.is_empty());
+        assert_eq!(alice.git(&["rev-parse", "--abbrev-ref", "main@{upstream}"]).trim(), "origin/main");
+
+        let bob = TempRepo::clone_from(&remote);
+        alice.write("a.txt", "2\n");
+        alice.commit_all("second");
+        push(alice.path(), &PushOptions::default(), &mut |_| {}, no_cancel()).await.unwrap();
+
+        fetch(bob.path(), Some("origin"), true, &mut |_| {}, no_cancel()).await.unwrap();
+        assert_eq!(bob.git(&["rev-list", "--count", "main..origin/main"]).trim(), "1");
+        pull(bob.path(), Some(PullMode::FfOnly), &mut |_| {}, no_cancel()).await.unwrap();
+        assert_eq!(bob.read("a.txt"), "2\n");
+
+        // Alice rewrites history; Bob's lease on origin/main is now stale
+        alice.write("a.txt", "rewritten\n");
+        alice.git(&["commit", "-q", "-a", "--amend", "-m", "second, rewritten"]);
+        push(alice.path(), &PushOptions { force_with_lease: true, ..Default::default() }, &mut |_| {}, no_cancel())
+            .await
+            .unwrap();
+        bob.write("b.txt", "b\n");
+        bob.commit_all("bob");
+        let error = push(bob.path(), &PushOptions { force_with_lease: true, ..Default::default() }, &mut |_| {}, no_cancel())
+            .await
+            .unwrap_err();
+        assert!(matches!(error, RemoteError::Rejected { .. }), "{:?}", error);
+
+        let error = pull(bob.path(), Some(PullMode::FfOnly), &mut |_| {}, no_cancel()).await.unwrap_err();
+        assert!(matches!(error, RemoteError::Rejected { .. }), "{:?}", error);
+
+        bob.git(&["remote", "set-url", "origin", "/nonexistent/remote.git"]);
+        let error = fetch(bob.path(), Some("origin"), false, &mut |_| {}, no_cancel()).await.unwrap_err();
+        assert!(matches!(error, RemoteError::RepositoryNotFound { .. }), "{:?}", error);
+    }
+
+    #[tokio::test]
+    async fn test_push_and_delete_tag() {
+        let remote = TempRepo::new_bare();
+        let repo = TempRepo::new();
+        repo.write("a.txt", "1\n");
+        repo.commit_all("first");
+        repo.git(&["remote", "add", "origin", remote.path().to_str().unwrap()]);
+        repo.git(&["tag", "v1.0.0"]);
+
+        push_tag(repo.path(), None, "v1.0.0", false, &mut |_| {}, no_cancel()).await.unwrap();
+        assert_eq!(remote.git(&["tag", "--list"]).trim(), "v1.0.0");
+        push_tag(repo.path(), Some("origin"), "v1.0.0", true, &mut |_| {}, no_cancel()).await.unwrap();
+        assert_eq!(remote.git(&["tag", "--list"]).trim(), "");
+    }
+
+    #[tokio::test]
+    async fn test_cancel_running_operation() {
+        let repo = TempRepo::new();
+        let operations = GitOperations::default();
+        let cancel = operations.register("slow");
+
+        // A remote helper that never answers
+        let args: Vec<String> = ["-c", "protocol.ext.allow=always", "fetch", "ext::sleep 30"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        let mut on_progress = |_: &str| {};
+        let started = std::time::Instant::now();
+        let (result, cancelled) = tokio::join!(
+            run_remote(repo.path(), &args, &mut on_progress, cancel),
+            async {
+                tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+                operations.cancel("slow")
+            }
+        );
+
+        assert!(cancelled);
+        assert_eq!(result.unwrap_err(), RemoteError::Cancelled);
+        assert!(started.elapsed() < std::time::Duration::from_secs(10));
+        assert!(!operations.cancel("slow"));
+    }
+}
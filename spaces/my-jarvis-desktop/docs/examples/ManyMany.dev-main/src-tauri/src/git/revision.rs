@@ -0,0 +1,92 @@
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AppError, ErrorKind};
+use crate::git::{self, is_binary};
+
+/// Files bigger than this are reported without their contents
+pub const MAX_FILE_BYTES: u64 = 5 * 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileAtRevision {
+    pub path: String,
+    pub revision: String,
+    /// Blob id
+    pub sha: String,
+    pub size: u64,
+    pub binary: bool,
+    pub too_large: bool,
+    /// `None` for binary and oversized files
+    pub content: Option<String>,
+}
+
+/// Contents of `path` as of `revision` (any commit-ish, e.g. `HEAD~2` or a branch)
+pub fn file_at_revision(worktree_path: &Path, path: &str, revision: &str) -> Result<FileAtRevision, AppError> {
+    if revision.starts_with('-') {
+        return Err(AppError::invalid(format!("Invalid revision: {}", revision)));
+    }
+    let spec = format!("{}:{}", revision, path);
+    let sha = git::run_text(worktree_path, &["rev-parse", "--verify", "-q", &spec]).map_err(|e| match e.kind {
+        ErrorKind::GitFailed { .. } => AppError::new(
+            ErrorKind::PathNotFound { path: path.to_string() },
+            format!("{} does not exist at {}", path, revision),
+        ),
+        _ => e,
+    })?;
+    if git::run_text(worktree_path, &["cat-file", "-t", &sha])? != "blob" {
+        return Err(AppError::invalid(format!("{} is not a file at {}", path, revision)));
+    }
+    let size: u64 = git::run_text(worktree_path, &["cat-file", "-s", &sha])?
+        .parse()
+        .map_err(|e| AppError::other(format!("Failed to read size of {}: {}", path, e)))?;
+
+    let mut file = FileAtRevision {
+        path: path.to_string(),
+        revision: revision.to_string(),
+        sha,
+        size,
+        binary: false,
+        too_large: size > MAX_FILE_BYTES,
+        content: None,
+    };
+    if file.too_large {
+        return Ok(file);
+    }
+
+    let blob = git::run(worktree_path, &["cat-file", "blob", &file.sha])?;
+    file.binary = is_binary(&blob);
+    if !file.binary {
+        file.content = Some(String::from_utf8_lossy(&blob).into_owned());
+    }
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::fixture::TempRepo;
+
+    #[test]
+    fn test_file_at_revision() {
+        let repo = TempRepo::new();
+        repo.write("src/a.txt", "old\n");
+        std::fs::write(repo.path().join("blob.bin"), [1u8, 0, 2]).unwrap();
+        repo.commit_all("first");
+        repo.write("src/a.txt", "new\n");
+        repo.commit_all("second");
+
+        let old = file_at_revision(repo.path(), "src/a.txt", "HEAD~1").unwrap();
+        assert_eq!(old.content.as_deref(), Some("old\n"));
+        assert_eq!(old.size, 4);
+        assert_eq!(file_at_revision(repo.path(), "src/a.txt", "main").unwrap().content.as_deref(), Some("new\n"));
+
+        let binary = file_at_revision(repo.path(), "blob.bin", "HEAD").unwrap();
+        assert!(binary.binary);
+        assert_eq!(binary.content, None);
+
+        let missing = file_at_revision(repo.path(), "missing.txt", "HEAD").unwrap_err();
+        assert_eq!(missing.kind, ErrorKind::PathNotFound { path: "missing.txt".to_string() });
+        assert!(missing.to_string().contains("does not exist"));
+        assert!(file_at_revision(repo.path(), "src", "HEAD").unwrap_err().to_string().contains("not a file"));
+    }
+}
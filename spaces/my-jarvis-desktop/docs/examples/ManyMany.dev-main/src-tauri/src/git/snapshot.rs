@@ -0,0 +1,204 @@
+//! Safety snapshots taken before anything that rewrites the index or throws work away.
+//!
+//! A snapshot is a commit shaped like a stash entry: its tree is the worktree state of the
+//! affected paths (untracked files included), its first parent is `HEAD` and its second
+//! parent a commit of the index. It lives under `refs/manymany/snapshots/` so `git gc`
+//! keeps it, and the message records the operation, worktree and paths.
+
+use std::fs;
+use std::path::Path;
+use chrono::{TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AppError, ErrorKind};
+use crate::git;
+
+const SNAPSHOT_REFS: &str = "refs/manymany/snapshots";
+
+/// Oldest snapshots beyond this many are deleted
+const MAX_SNAPSHOTS: usize = 50;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub id: String,
+    pub commit: String,
+    pub operation: String,
+    pub worktree_path: String,
+    pub paths: Vec<String>,
+    pub created_at: String,
+}
+
+/// Record the index and the worktree state of `paths` before `operation` touches them
+pub fn create_snapshot(worktree_path: &Path, operation: &str, paths: &[String]) -> Result<Snapshot, AppError> {
+    let index_tree = git::run_text(worktree_path, &["write-tree"])
+        .map_err(|e| e.context("Failed to snapshot index"))?;
+    let worktree_tree = worktree_tree(worktree_path, paths)?;
+    let head = git::run_text(worktree_path, &["rev-parse", "--verify", "-q", "HEAD"]).ok();
+
+    let mut index_args = vec!["commit-tree", index_tree.as_str(), "-m", "index"];
+    if let Some(head) = &head {
+        index_args.extend(["-p", head.as_str()]);
+    }
+    let index_commit = git::run_text(worktree_path, &index_args)?;
+
+    let mut message = format!("Snapshot before {}\n\nWorktree: {}\n", operation, worktree_path.display());
+    for path in paths {
+        message.push_str(&format!("Path: {}\n", path));
+    }
+    let mut args = vec!["commit-tree", worktree_tree.as_str()];
+    if let Some(head) = &head {
+        args.extend(["-p", head.as_str()]);
+    }
+    args.extend(["-p", index_commit.as_str(), "-m", message.as_str()]);
+    let commit = git::run_text(worktree_path, &args)?;
+
+    let now = Utc::now();
+    // Nanosecond timestamps keep ids unique and sorted by age
+    let id = now.timestamp_nanos_opt().unwrap_or_default().to_string();
+    git::run(worktree_path, &["update-ref", &format!("{}/{}", SNAPSHOT_REFS, id), &commit])?;
+    prune_snapshots(worktree_path)?;
+
+    Ok(Snapshot {
+        id,
+        commit,
+        operation: operation.to_string(),
+        worktree_path: worktree_path.display().to_string(),
+        paths: paths.to_vec(),
+        created_at: now.to_rfc3339(),
+    })
+}
+
+/// Snapshots of the repository, newest first
+pub fn list_snapshots(worktree_path: &Path) -> Result<Vec<Snapshot>, AppError> {
+    let output = git::run_text(worktree_path, &[
+        "for-each-ref",
+        "--sort=-refname",
+        "--format=%(refname:lstrip=3)%00%(objectname)%00%(creatordate:unix)%00%(contents)%1e",
+        SNAPSHOT_REFS,
+    ])?;
+
+    Ok(output
+        .split('\x1e')
+        .filter_map(|record| {
+            let mut fields = record.trim_start_matches('\n').splitn(4, '\0');
+            let id = fields.next().filter(|id| !id.is_empty())?;
+            let commit = fields.next()?;
+            let timestamp = fields.next()?.parse().ok()?;
+            let message = fields.next()?;
+            Some(parse_snapshot(id, commit, timestamp, message))
+        })
+        .collect())
+}
+
+/// Put the snapshotted paths back into the worktree and index as they were
+pub fn restore_snapshot(worktree_path: &Path, id: &str) -> Result<Snapshot, AppError> {
+    let snapshot = list_snapshots(worktree_path)?
+        .into_iter()
+        .find(|snapshot| snapshot.id == id)
+        .ok_or_else(|| AppError::new(
+            ErrorKind::SnapshotNotFound { id: id.to_string() },
+            format!("Snapshot not found: {}", id),
+        ))?;
+    let index_commit = format!("{}^2", snapshot.commit);
+
+    for path in &snapshot.paths {
+        let spec = format!("{}:{}", snapshot.commit, path);
+        if git::run(worktree_path, &["cat-file", "-e", &spec]).is_ok() {
+            git::run(worktree_path, &["restore", "--source", &snapshot.commit, "--worktree", "--", path])?;
+        } else {
+            let file = worktree_path.join(path);
+            if file.exists() {
+                fs::remove_file(&file).map_err(|e| AppError::io(format!("Failed to remove {}", path), e))?;
+            }
+        }
+        git::run(worktree_path, &["reset", "-q", &index_commit, "--", path])?;
+    }
+
+    Ok(snapshot)
+}
+
+fn parse_snapshot(id: &str, commit: &str, timestamp: i64, message: &str) -> Snapshot {
+    let mut lines = message.lines();
+    let operation = lines.next()
+        .and_then(|subject| subject.strip_prefix("Snapshot before "))
+        .unwrap_or_default()
+        .to_string();
+
+    let mut worktree_path = String::new();
+    let mut paths = Vec::new();
+    for line in lines {
+        if let Some(path) = line.strip_prefix("Worktree: ") {
+            worktree_path = path.to_string();
+        } else if let Some(path) = line.strip_prefix("Path: ") {
+            paths.push(path.to_string());
+        }
+    }
+
+    Snapshot {
+        id: id.to_string(),
+        commit: commit.to_string(),
+        operation,
+        worktree_path,
+        paths,
+        created_at: Utc.timestamp_opt(timestamp, 0)
+            .single()
+            .map(|time| time.to_rfc3339())
+            .unwrap_or_default(),
+    }
+}
+
+/// Tree of the current index with `paths` (every path when empty) updated from the worktree, built in a scratch index
+pub fn worktree_tree(worktree_path: &Path, paths: &[String]) -> Result<String, AppError> {
+    let index_path = git::run_text(worktree_path, &["rev-parse", "--path-format=absolute", "--git-path", "index"])?;
+    let scratch = std::env::temp_dir().join(format!("manymany-index-{}", uuid::Uuid::new_v4()));
+    if Path::new(&index_path).exists() {
+        fs::copy(&index_path, &scratch).map_err(|e| AppError::io("Failed to copy index", e))?;
+    }
+
+    let result = present_paths(worktree_path, paths)
+        .and_then(|present| {
+            // Paths that exist nowhere would make `git add` fail; with none left there's nothing to add
+            if !paths.is_empty() && present.is_empty() {
+                return Ok(Vec::new());
+            }
+            let mut add_args = vec!["add", "-A", "--"];
+            add_args.extend(present.iter().map(String::as_str));
+            git::run_with_index(worktree_path, &scratch, &add_args)
+        })
+        .and_then(|_| git::run_with_index(worktree_path, &scratch, &["write-tree"]))
+        .map(|tree| String::from_utf8_lossy(&tree).trim().to_string());
+    let _ = fs::remove_file(&scratch);
+
+    result.map_err(|e| e.context("Failed to snapshot worktree"))
+}
+
+/// The `paths` that are in the worktree or the index
+fn present_paths(worktree_path: &Path, paths: &[String]) -> Result<Vec<String>, AppError> {
+    if paths.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut ls_args = vec!["ls-files", "-z", "--"];
+    ls_args.extend(paths.iter().map(String::as_str));
+    let output = git::run(worktree_path, &ls_args)?;
+    let indexed: Vec<String> = output
+        .split(|b| *b == 0)
+        .filter(|path| !path.is_empty())
+        .map(|path| String::from_utf8_lossy(path).into_owned())
+        .collect();
+
+    Ok(paths
+        .iter()
+        .filter(|path| {
+            worktree_path.join(path).exists()
+                || indexed.iter().any(|entry| entry == *path || entry.starts_with(&format!("{}/", path)))
+        })
+        .cloned()
+        .collect())
+}
+
+fn prune_snapshots(worktree_path: &Path) -> Result<(), AppError> {
+    for snapshot in list_snapshots(worktree_path)?.into_iter().skip(MAX_SNAPSHOTS) {
+        git::run(worktree_path, &["update-ref", "-d", &format!("{}/{}", SNAPSHOT_REFS, snapshot.id)])?;
+    }
+    Ok(())
+}
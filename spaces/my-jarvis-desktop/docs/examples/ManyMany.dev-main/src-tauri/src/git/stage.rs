@@ -0,0 +1,374 @@
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppError;
+use crate::git;
+use crate::git::diff::{file_diff, DiffLine, FileDiff, LineKind, DEFAULT_CONTEXT_LINES};
+use crate::git::snapshot::{create_snapshot, Snapshot};
+use crate::git::status::FileStatus;
+
+/// Part of a file's diff, as returned by `get_file_diff`. Hunks are matched by their line
+/// ranges, so selections stay valid whatever context size the diff was shown with.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DiffSelection {
+    /// Every change inside the hunk
+    Hunk {
+        old_start: u32,
+        old_lines: u32,
+        new_start: u32,
+        new_lines: u32,
+    },
+    /// One changed line: a removed line by `old_line`, an added line by `new_line`
+    Line {
+        old_line: Option<u32>,
+        new_line: Option<u32>,
+    },
+}
+
+impl DiffSelection {
+    fn contains(&self, line: &DiffLine) -> bool {
+        let in_range = |number: Option<u32>, start: u32, len: u32| {
+            number.is_some_and(|number| number >= start && number < start + len)
+        };
+
+        match (self, line.kind) {
+            (_, LineKind::Context) => false,
+            (DiffSelection::Hunk { old_start, old_lines, .. }, LineKind::Removed) => {
+                in_range(line.old_line, *old_start, *old_lines)
+            }
+            (DiffSelection::Hunk { new_start, new_lines, .. }, LineKind::Added) => {
+                in_range(line.new_line, *new_start, *new_lines)
+            }
+            (DiffSelection::Line { old_line, new_line }, _) => {
+                line.old_line == *old_line && line.new_line == *new_line
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Target {
+    /// Apply to the index
+    Stage,
+    /// Reverse-apply to the index
+    Unstage,
+    /// Reverse-apply to the worktree
+    Discard,
+}
+
+impl Target {
+    fn name(self) -> &'static str {
+        match self {
+            Target::Stage => "stage_hunks",
+            Target::Unstage => "unstage_hunks",
+            Target::Discard => "discard_hunks",
+        }
+    }
+}
+
+/// Stage the selected unstaged changes of `path`
+pub fn stage_hunks(worktree_path: &Path, path: &str, selections: &[DiffSelection]) -> Result<Snapshot, AppError> {
+    apply_selection(worktree_path, path, selections, Target::Stage)
+}
+
+/// Move the selected staged changes of `path` back to the worktree
+pub fn unstage_hunks(worktree_path: &Path, path: &str, selections: &[DiffSelection]) -> Result<Snapshot, AppError> {
+    apply_selection(worktree_path, path, selections, Target::Unstage)
+}
+
+/// Throw away the selected unstaged changes of `path`
+pub fn discard_hunks(worktree_path: &Path, path: &str, selections: &[DiffSelection]) -> Result<Snapshot, AppError> {
+    apply_selection(worktree_path, path, selections, Target::Discard)
+}
+
+/// Throw away all unstaged changes to `paths`, restoring them from the index
+pub fn discard_file_changes(worktree_path: &Path, paths: &[String]) -> Result<Snapshot, AppError> {
+    if paths.is_empty() {
+        return Err(AppError::invalid("No files selected"));
+    }
+    let snapshot = create_snapshot(worktree_path, "discard_file_changes", paths)?;
+
+    let mut args = vec!["restore", "--worktree", "--"];
+    args.extend(paths.iter().map(String::as_str));
+    git::run(worktree_path, &args)?;
+    Ok(snapshot)
+}
+
+/// Delete untracked (and not ignored) files in `paths`; directories are deleted recursively
+pub fn delete_untracked(worktree_path: &Path, paths: &[String]) -> Result<Snapshot, AppError> {
+    if paths.is_empty() {
+        return Err(AppError::invalid("No files selected"));
+    }
+
+    let mut list_args = vec!["ls-files", "-z", "--others", "--exclude-standard", "--"];
+    list_args.extend(paths.iter().map(String::as_str));
+    let untracked: Vec<String> = git::run(worktree_path, &list_args)?
+        .split(|b| *b == 0)
+        .filter(|path| !path.is_empty())
+        .map(|path| String::from_utf8_lossy(path).into_owned())
+        .collect();
+    if untracked.is_empty() {
+        return Err(AppError::invalid("No untracked files to delete"));
+    }
+
+    let snapshot = create_snapshot(worktree_path, "delete_untracked", &untracked)?;
+
+    // clean never touches tracked or ignored files
+    let mut args = vec!["clean", "-f", "-d", "-q", "--"];
+    args.extend(paths.iter().map(String::as_str));
+    git::run(worktree_path, &args)?;
+    Ok(snapshot)
+}
+
+fn apply_selection(worktree_path: &Path, path: &str, selections: &[DiffSelection], target: Target) -> Result<Snapshot, AppError> {
+    let staged = target == Target::Unstage;
+    let file = file_diff(worktree_path, path, staged, DEFAULT_CONTEXT_LINES)?
+        .ok_or_else(|| AppError::invalid(format!("No {} changes to {}", if staged { "staged" } else { "unstaged" }, path)))?;
+
+    if file.binary {
+        return Err(AppError::invalid(format!("{} is binary; stage or discard it as a whole", path)));
+    }
+    if target == Target::Discard && file.status == FileStatus::Added {
+        return Err(AppError::invalid(format!("{} is untracked; use delete_untracked instead", path)));
+    }
+
+    let reverse = target != Target::Stage;
+    let patch = build_patch(&file, selections, reverse)
+        .ok_or_else(|| AppError::invalid("No changes selected"))?;
+
+    let snapshot = create_snapshot(worktree_path, target.name(), &[path.to_string()])?;
+
+    let mut args = vec!["apply", "--recount", "--whitespace=nowarn"];
+    if target != Target::Discard {
+        args.push("--cached");
+    }
+    if reverse {
+        args.push("--reverse");
+    }
+    args.push("-");
+    git::run_with_input(worktree_path, &args, patch.as_bytes())
+        .map_err(|e| e.context("Failed to apply selected changes"))?;
+
+    Ok(snapshot)
+}
+
+/// A patch with only the selected changes of `file`, or `None` if nothing is selected.
+///
+/// Forward patches (for staging) apply to the old side, so unselected removals become
+/// context and unselected additions are left out. Reverse patches (for unstaging and
+/// discarding) are applied backwards to the new side, so it's the other way around.
+pub fn build_patch(file: &FileDiff, selections: &[DiffSelection], reverse: bool) -> Option<String> {
+    let selected = |line: &DiffLine| selections.iter().any(|selection| selection.contains(line));
+    let mut changes = file.hunks.iter()
+        .flat_map(|hunk| &hunk.lines)
+        .filter(|line| line.kind != LineKind::Context);
+    let all_selected = changes.all(selected);
+
+    let mut body = String::new();
+    // Lines added minus lines removed by the hunks written so far
+    let mut delta: i64 = 0;
+
+    for hunk in &file.hunks {
+        if !hunk.lines.iter().any(|line| line.kind != LineKind::Context && selected(line)) {
+            continue;
+        }
+
+        let mut lines = String::new();
+        let (mut old_count, mut new_count) = (0i64, 0i64);
+        for line in &hunk.lines {
+            let prefix = match (line.kind, selected(line), reverse) {
+                (LineKind::Context, _, _) => ' ',
+                (LineKind::Removed, true, _) => '-',
+                (LineKind::Added, true, _) => '+',
+                (LineKind::Removed, false, false) | (LineKind::Added, false, true) => ' ',
+                (LineKind::Removed, false, true) | (LineKind::Added, false, false) => continue,
+            };
+            match prefix {
+                ' ' => {
+                    old_count += 1;
+                    new_count += 1;
+                }
+                '-' => old_count += 1,
+                _ => new_count += 1,
+            }
+            lines.push(prefix);
+            lines.push_str(&line.content);
+            lines.push('\n');
+            if line.no_newline {
+                lines.push_str("\\ No newline at end of file\n");
+            }
+        }
+
+        // The side the patch is applied to keeps its original position; the other
+        // side shifts by what earlier hunks changed. A zero-length side points at
+        // the line before.
+        let (anchor_start, anchor_count, other_count) = if reverse {
+            (hunk.new_start as i64, new_count, old_count)
+        } else {
+            (hunk.old_start as i64, old_count, new_count)
+        };
+        let first_line = anchor_start + if anchor_count == 0 { 1 } else { 0 };
+        let other_first = if reverse { first_line - delta } else { first_line + delta };
+        let other_start = if other_count == 0 { other_first - 1 } else { other_first };
+        let (old_start, new_start) = if reverse {
+            (other_start, anchor_start)
+        } else {
+            (anchor_start, other_start)
+        };
+
+        body.push_str(&format!("@@ -{},{} +{},{} @@\n", old_start, old_count, new_start, new_count));
+        body.push_str(&lines);
+        delta += new_count - old_count;
+    }
+
+    if body.is_empty() {
+        return None;
+    }
+
+    let creating = file.status == FileStatus::Added && (!reverse || all_selected);
+    let deleting = file.status == FileStatus::Deleted && (reverse || all_selected);
+    let old_path = quote_path("a/", &file.path);
+    let new_path = quote_path("b/", &file.path);
+
+    let mut patch = format!("diff --git {} {}\n", old_path, new_path);
+    if creating {
+        let mode = file.new_mode.as_deref().unwrap_or("100644");
+        patch.push_str(&format!("new file mode {}\n--- /dev/null\n+++ {}\n", mode, new_path));
+    } else if deleting {
+        let mode = file.old_mode.as_deref().unwrap_or("100644");
+        patch.push_str(&format!("deleted file mode {}\n--- {}\n+++ /dev/null\n", mode, old_path));
+    } else {
+        patch.push_str(&format!("--- {}\n+++ {}\n", old_path, new_path));
+    }
+    patch.push_str(&body);
+    Some(patch)
+}
+
+/// `prefix` + `path`, C-quoted the way git does when the path needs it
+fn quote_path(prefix: &str, path: &str) -> String {
+    let needs_quoting = path.chars().any(|c| matches!(c, ' ' | '"' | '\\') || c.is_control());
+    if !needs_quoting {
+        return format!("{}{}", prefix, path);
+    }
+
+    let mut quoted = String::from("\"");
+    for c in prefix.chars().chain(path.chars()) {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\{:03o}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::fixture::TempRepo;
+    use crate::git::snapshot::{list_snapshots, restore_snapshot};
+
+    const ORIGINAL: &str = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n13\n14\n15\n";
+    const EDITED: &str = "1\ntwo\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n13\nfourteen\n15\n";
+
+    fn hunks(repo: &TempRepo, staged: bool) -> Vec<DiffSelection> {
+        file_diff(repo.path(), "numbers.txt", staged, DEFAULT_CONTEXT_LINES)
+            .unwrap()
+            .map(|file| file.hunks.iter().map(|hunk| DiffSelection::Hunk {
+                old_start: hunk.old_start,
+                old_lines: hunk.old_lines,
+                new_start: hunk.new_start,
+                new_lines: hunk.new_lines,
+            }).collect())
+            .unwrap_or_default()
+    }
+
+    fn staged_content(repo: &TempRepo) -> String {
+        repo.git(&["show", ":numbers.txt"])
+    }
+
+    #[test]
+    fn test_stage_and_unstage_hunks() {
+        let repo = TempRepo::new();
+        repo.write("numbers.txt", ORIGINAL);
+        repo.commit_all("initial");
+        repo.write("numbers.txt", EDITED);
+
+        let unstaged = hunks(&repo, false);
+        assert_eq!(unstaged.len(), 2);
+
+        stage_hunks(repo.path(), "numbers.txt", &unstaged[1..]).unwrap();
+        assert_eq!(staged_content(&repo), ORIGINAL.replace("14\n", "fourteen\n"));
+        assert_eq!(hunks(&repo, false).len(), 1);
+
+        stage_hunks(repo.path(), "numbers.txt", &hunks(&repo, false)).unwrap();
+        assert_eq!(staged_content(&repo), EDITED);
+
+        unstage_hunks(repo.path(), "numbers.txt", &hunks(&repo, true)[..1]).unwrap();
+        assert_eq!(staged_content(&repo), ORIGINAL.replace("14\n", "fourteen\n"));
+        assert_eq!(repo.read("numbers.txt"), EDITED);
+    }
+
+    #[test]
+    fn test_stage_single_lines() {
+        let repo = TempRepo::new();
+        repo.write("numbers.txt", "a\nb\nc\n");
+        repo.commit_all("initial");
+        repo.write("numbers.txt", "a\nB\nc\nd\ne\n");
+
+        // Stage the replacement of b and the first appended line, leaving "e" unstaged
+        let selections = [
+            DiffSelection::Line { old_line: Some(2), new_line: None },
+            DiffSelection::Line { old_line: None, new_line: Some(2) },
+            DiffSelection::Line { old_line: None, new_line: Some(4) },
+        ];
+        stage_hunks(repo.path(), "numbers.txt", &selections).unwrap();
+        assert_eq!(staged_content(&repo), "a\nB\nc\nd\n");
+
+        // Unstage only the removal of "b": the index keeps both lines
+        let selections = [DiffSelection::Line { old_line: Some(2), new_line: None }];
+        unstage_hunks(repo.path(), "numbers.txt", &selections).unwrap();
+        assert_eq!(staged_content(&repo), "a\nb\nB\nc\nd\n");
+    }
+
+    #[test]
+    fn test_discard_and_restore() {
+        let repo = TempRepo::new();
+        repo.write("numbers.txt", ORIGINAL);
+        repo.commit_all("initial");
+        repo.write("numbers.txt", EDITED);
+        repo.write("scratch/notes.md", "keep me?\n");
+
+        let snapshot = discard_hunks(repo.path(), "numbers.txt", &hunks(&repo, false)[..1]).unwrap();
+        assert_eq!(repo.read("numbers.txt"), ORIGINAL.replace("14\n", "fourteen\n"));
+        assert!(discard_hunks(repo.path(), "scratch/notes.md", &[]).is_err());
+
+        delete_untracked(repo.path(), &["scratch".to_string()]).unwrap();
+        assert!(!repo.path().join("scratch/notes.md").exists());
+
+        discard_file_changes(repo.path(), &["numbers.txt".to_string()]).unwrap();
+        assert_eq!(repo.read("numbers.txt"), ORIGINAL);
+
+        let snapshots = list_snapshots(repo.path()).unwrap();
+        assert_eq!(snapshots.len(), 3);
+        assert_eq!(snapshots[1].operation, "delete_untracked");
+        assert_eq!(snapshots[1].paths, vec!["scratch/notes.md".to_string()]);
+
+        restore_snapshot(repo.path(), &snapshots[1].id).unwrap();
+        assert_eq!(repo.read("scratch/notes.md"), "keep me?\n");
+        restore_snapshot(repo.path(), &snapshot.id).unwrap();
+        assert_eq!(repo.read("numbers.txt"), EDITED);
+        assert!(repo.git(&["status", "--porcelain"]).contains("?? scratch/"));
+    }
+
+    #[test]
+    fn test_quote_path() {
+        assert_eq!(quote_path("a/", "src/main.rs"), "a/src/main.rs");
+        assert_eq!(quote_path("b/", "sp ace\"q.txt"), "\"b/sp ace\\\"q.txt\"");
+    }
+}
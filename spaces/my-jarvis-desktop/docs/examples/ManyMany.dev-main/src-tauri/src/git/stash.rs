@@ -0,0 +1,315 @@
+//! Stashes are shared by every worktree of a repository. The worktree a stash was made in is
+//! recorded in a git note on the stash commit, so it's known wherever the stash is applied.
+
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AppError, ErrorKind};
+use crate::git;
+use crate::git::operation::conflicted_paths;
+use crate::process::RunOptions;
+
+const ORIGIN_NOTES: &str = "refs/notes/manymany-stash";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StashEntry {
+    pub index: u32,
+    /// e.g. `stash@{0}`
+    pub name: String,
+    pub sha: String,
+    /// Subject as git shows it, e.g. `On main: half-done login`
+    pub message: String,
+    /// Branch the stash was made on; `None` on a detached `HEAD`
+    pub branch: Option<String>,
+    /// Worktree the stash was made in; `None` for stashes made outside the app
+    pub worktree_path: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StashPushRequest {
+    #[serde(default)]
+    pub message: Option<String>,
+    /// Leave staged changes in the index as well as stashing them
+    #[serde(default)]
+    pub keep_index: bool,
+    #[serde(default)]
+    pub include_untracked: bool,
+    /// Stash only these paths
+    #[serde(default)]
+    pub paths: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StashApplyResult {
+    pub stash: StashEntry,
+    /// Whether the stash was dropped; a pop that conflicts keeps it
+    pub dropped: bool,
+    pub conflicts: Vec<String>,
+    pub output: String,
+}
+
+/// Stashes of the repository, newest first
+pub fn list_stashes(worktree_path: &Path) -> Result<Vec<StashEntry>, AppError> {
+    let notes = format!("--notes={}", ORIGIN_NOTES);
+    let output = git::run_text(worktree_path, &[
+        "stash",
+        "list",
+        "--no-notes",
+        &notes,
+        "--format=%H%x1f%gd%x1f%gs%x1f%cI%x1f%N%x1e",
+    ])?;
+
+    Ok(output
+        .split('\x1e')
+        .filter_map(|record| {
+            let fields: Vec<&str> = record.trim_start_matches('\n').split('\x1f').collect();
+            let [sha, name, message, created_at, note] = fields.as_slice() else {
+                return None;
+            };
+            let index = name.strip_prefix("stash@{")?.strip_suffix('}')?.parse().ok()?;
+            Some(StashEntry {
+                index,
+                name: name.to_string(),
+                sha: sha.to_string(),
+                message: message.to_string(),
+                branch: parse_branch(message),
+                worktree_path: note.lines()
+                    .find_map(|line| line.strip_prefix("Worktree: "))
+                    .map(str::to_string),
+                created_at: created_at.to_string(),
+            })
+        })
+        .collect())
+}
+
+/// `WIP on <branch>: ...` or `On <branch>: ...`
+fn parse_branch(message: &str) -> Option<String> {
+    let rest = message.strip_prefix("WIP on ").or_else(|| message.strip_prefix("On "))?;
+    let (branch, _) = rest.split_once(": ")?;
+    (branch != "(no branch)").then(|| branch.to_string())
+}
+
+/// Stash changes in `worktree_path` and record which worktree they came from
+pub fn push_stash(worktree_path: &Path, request: &StashPushRequest) -> Result<StashEntry, AppError> {
+    let before = git::run_text(worktree_path, &["rev-parse", "--verify", "-q", "refs/stash"]).ok();
+
+    let mut args = vec!["stash".to_string(), "push".to_string()];
+    if let Some(message) = request.message.as_ref().filter(|message| !message.trim().is_empty()) {
+        args.extend(["--message".to_string(), message.clone()]);
+    }
+    if request.keep_index {
+        args.push("--keep-index".to_string());
+    }
+    if request.include_untracked {
+        args.push("--include-untracked".to_string());
+    }
+    if let Some(paths) = &request.paths {
+        if paths.is_empty() {
+            return Err(AppError::invalid("No paths selected"));
+        }
+        args.push("--".to_string());
+        args.extend(paths.iter().cloned());
+    }
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    git::run(worktree_path, &args).map_err(|e| e.context("Failed to stash"))?;
+
+    let after = git::run_text(worktree_path, &["rev-parse", "--verify", "-q", "refs/stash"]).ok();
+    let sha = match after {
+        Some(sha) if Some(&sha) != before.as_ref() => sha,
+        _ => return Err(AppError::invalid("No local changes to stash")),
+    };
+
+    let note = format!("Worktree: {}", worktree_path.display());
+    git::run(worktree_path, &["notes", "--ref", ORIGIN_NOTES, "add", "-f", "-m", &note, &sha])?;
+
+    let stash = find_stash(worktree_path, &sha)?;
+    Ok(stash)
+}
+
+/// Apply a stash (by name or sha) to `worktree_path`, which may differ from the one it came from
+pub fn apply_stash(worktree_path: &Path, stash: &str, restore_index: bool) -> Result<StashApplyResult, AppError> {
+    apply(worktree_path, stash, restore_index, false)
+}
+
+/// Apply a stash and drop it, unless applying it conflicted
+pub fn pop_stash(worktree_path: &Path, stash: &str, restore_index: bool) -> Result<StashApplyResult, AppError> {
+    apply(worktree_path, stash, restore_index, true)
+}
+
+fn apply(worktree_path: &Path, stash: &str, restore_index: bool, pop: bool) -> Result<StashApplyResult, AppError> {
+    let entry = find_stash(worktree_path, stash)?;
+    // By sha, so a stash pushed or dropped meanwhile can't shift another one into `stash@{n}`
+    let mut args = vec!["stash", "apply"];
+    if restore_index {
+        args.push("--index");
+    }
+    args.push(&entry.sha);
+
+    let result = git::output(worktree_path, &args, RunOptions::default())
+        .map_err(|e| e.context(format!("Failed to apply {}", entry.name)))?;
+    let output = format!(
+        "{}{}",
+        String::from_utf8_lossy(&result.stdout),
+        String::from_utf8_lossy(&result.stderr)
+    )
+    .trim_end()
+    .to_string();
+
+    let conflicts = conflicted_paths(worktree_path)?;
+    if !result.status.success() && conflicts.is_empty() {
+        return Err(git::git_error(worktree_path, "stash", &output, result.status.code())
+            .context(format!("Failed to apply {}", entry.name)));
+    }
+
+    // Like `git stash pop`, a stash that conflicted is kept
+    let dropped = pop && result.status.success();
+    if dropped {
+        let current = git::run_text(worktree_path, &["rev-parse", "--verify", "-q", &entry.name]).ok();
+        if current.as_deref() != Some(entry.sha.as_str()) {
+            return Err(AppError::other(format!(
+                "Applied {} but didn't drop it: the stash list changed in the meantime",
+                &entry.sha[..entry.sha.len().min(8)]
+            )));
+        }
+        git::run(worktree_path, &["stash", "drop", "-q", &entry.name])
+            .map_err(|e| e.context(format!("Applied {} but failed to drop it", entry.name)))?;
+        forget_origin(worktree_path, &entry.sha);
+    }
+    Ok(StashApplyResult {
+        stash: entry,
+        dropped,
+        conflicts,
+        output,
+    })
+}
+
+/// Delete a stash. Its commit stays recoverable by sha until git garbage collects it.
+pub fn drop_stash(worktree_path: &Path, stash: &str) -> Result<StashEntry, AppError> {
+    let entry = find_stash(worktree_path, stash)?;
+    git::run(worktree_path, &["stash", "drop", "-q", &entry.name])
+        .map_err(|e| e.context(format!("Failed to drop {}", entry.name)))?;
+    forget_origin(worktree_path, &entry.sha);
+    Ok(entry)
+}
+
+/// Look a stash up by `stash@{n}` or sha
+pub fn find_stash(worktree_path: &Path, stash: &str) -> Result<StashEntry, AppError> {
+    list_stashes(worktree_path)?
+        .into_iter()
+        .find(|entry| entry.name == stash || (stash.len() >= 7 && entry.sha.starts_with(stash)))
+        .ok_or_else(|| AppError::new(
+            ErrorKind::StashNotFound { stash: stash.to_string() },
+            format!("Stash not found: {}", stash),
+        ))
+}
+
+fn forget_origin(worktree_path: &Path, sha: &str) {
+    let _ = git::run(worktree_path, &["notes", "--ref", ORIGIN_NOTES, "remove", "--ignore-missing", sha]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::diff::stash_diff;
+    use crate::git::fixture::TempRepo;
+
+    #[test]
+    fn test_parse_branch() {
+        assert_eq!(parse_branch("WIP on main: 1234567 initial").as_deref(), Some("main"));
+        assert_eq!(parse_branch("On feature/x: my message").as_deref(), Some("feature/x"));
+        assert_eq!(parse_branch("WIP on (no branch): 1234567 initial"), None);
+    }
+
+    #[test]
+    fn test_stash_between_worktrees() {
+        let repo = TempRepo::new();
+        repo.write("a.txt", "a\n");
+        repo.write("b.txt", "b\n");
+        repo.commit_all("initial");
+        let other = repo.add_worktree("other");
+
+        repo.write("a.txt", "changed a\n");
+        repo.write("b.txt", "changed b\n");
+        repo.write("new.txt", "new\n");
+        let stash = push_stash(repo.path(), &StashPushRequest {
+            message: Some("parked".to_string()),
+            include_untracked: true,
+            paths: Some(vec!["a.txt".to_string(), "new.txt".to_string()]),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(stash.name, "stash@{0}");
+        assert_eq!(stash.message, "On main: parked");
+        assert_eq!(stash.branch.as_deref(), Some("main"));
+        assert_eq!(stash.worktree_path.as_deref().map(Path::new), Some(repo.path()));
+        assert_eq!(repo.read("a.txt"), "a\n");
+        assert_eq!(repo.read("b.txt"), "changed b\n");
+        assert!(!repo.path().join("new.txt").exists());
+
+        let diff = stash_diff(repo.path(), &stash.sha, 3).unwrap();
+        let paths: Vec<_> = diff.iter().map(|file| file.path.as_str()).collect();
+        assert_eq!(paths, vec!["a.txt", "new.txt"]);
+
+        // Visible from the other worktree, which pops it
+        let listed = list_stashes(other.path()).unwrap();
+        assert_eq!(listed, vec![stash.clone()]);
+        let popped = pop_stash(other.path(), &stash.sha[..10], false).unwrap();
+        assert!(popped.dropped);
+        assert!(popped.conflicts.is_empty());
+        assert_eq!(other.read("a.txt"), "changed a\n");
+        assert_eq!(other.read("new.txt"), "new\n");
+        assert!(list_stashes(repo.path()).unwrap().is_empty());
+
+        assert!(push_stash(repo.path(), &StashPushRequest { paths: Some(vec!["a.txt".to_string()]), ..Default::default() })
+            .unwrap_err()
+            .to_string()
+            .contains("No local changes"));
+    }
+
+    #[test]
+    fn test_apply_conflict_keeps_stash() {
+        let repo = TempRepo::new();
+        repo.write("a.txt", "a\n");
+        repo.commit_all("initial");
+        repo.write("a.txt", "stashed\n");
+        repo.write("b.txt", "b\n");
+        repo.git(&["add", "b.txt"]);
+        let stash = push_stash(repo.path(), &StashPushRequest { keep_index: true, ..Default::default() }).unwrap();
+        assert!(stash.message.starts_with("WIP on main: "), "{}", stash.message);
+        // --keep-index leaves the staged file in place
+        assert_eq!(repo.read("b.txt"), "b\n");
+        repo.git(&["reset", "-q", "--hard"]);
+
+        repo.write("a.txt", "committed\n");
+        repo.commit_all("conflicting");
+        let result = pop_stash(repo.path(), "stash@{0}", false).unwrap();
+        assert!(!result.dropped);
+        assert_eq!(result.conflicts, vec!["a.txt"]);
+        assert_eq!(list_stashes(repo.path()).unwrap().len(), 1);
+
+        repo.git(&["reset", "-q", "--hard"]);
+        let dropped = drop_stash(repo.path(), "stash@{0}").unwrap();
+        assert_eq!(dropped.sha, stash.sha);
+        assert!(matches!(drop_stash(repo.path(), "stash@{0}").unwrap_err().kind, ErrorKind::StashNotFound { .. }));
+    }
+
+    #[test]
+    fn test_pop_drops_the_applied_stash() {
+        let repo = TempRepo::new();
+        repo.write("a.txt", "a\n");
+        repo.write("b.txt", "b\n");
+        repo.commit_all("initial");
+        repo.write("a.txt", "older\n");
+        let older = push_stash(repo.path(), &StashPushRequest::default()).unwrap();
+        repo.write("b.txt", "newer\n");
+        let newer = push_stash(repo.path(), &StashPushRequest::default()).unwrap();
+
+        let popped = pop_stash(repo.path(), "stash@{1}", false).unwrap();
+        assert!(popped.dropped);
+        assert_eq!(popped.stash.sha, older.sha);
+        assert_eq!(repo.read("a.txt"), "older\n");
+        let remaining: Vec<String> = list_stashes(repo.path()).unwrap().into_iter().map(|entry| entry.sha).collect();
+        assert_eq!(remaining, vec![newer.sha]);
+    }
+}
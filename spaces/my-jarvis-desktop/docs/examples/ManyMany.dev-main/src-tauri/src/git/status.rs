@@ -0,0 +1,466 @@
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppError;
+use crate::git::{self, branch};
+use crate::git::operation::{detect_operation, OperationInProgress};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GitStatus {
+    /// Current branch, or `HEAD` when detached
+    pub branch: String,
+    pub detached: bool,
+    /// `None` before the first commit
+    pub head_oid: Option<String>,
+    pub upstream: Option<String>,
+    pub upstream_state: UpstreamState,
+    /// Relative to the upstream; both 0 unless `upstream_state` is `tracking`
+    pub ahead: u32,
+    pub behind: u32,
+    /// A merge, rebase, cherry-pick, revert or bisect that hasn't finished
+    pub operation_in_progress: Option<OperationInProgress>,
+    pub staged: Vec<GitFile>,
+    pub unstaged: Vec<GitFile>,
+    pub untracked: Vec<GitFile>,
+    pub conflicted: Vec<GitFile>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpstreamState {
+    /// No upstream is configured (or `HEAD` is detached)
+    #[default]
+    NoUpstream,
+    Tracking,
+    /// An upstream is configured but its remote branch no longer exists
+    Gone,
+}
+
+/// How far `HEAD` has diverged from the project's default branch
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DefaultBranchDivergence {
+    /// e.g. `main` or `origin/main`
+    pub name: String,
+    /// Commits on `HEAD` that aren't on the default branch
+    pub ahead: u32,
+    /// Commits on the default branch that aren't on `HEAD`
+    pub behind: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GitFile {
+    pub path: String,
+    pub status: FileStatus,
+    /// Source path of a rename or copy
+    pub orig_path: Option<String>,
+    /// Rename/copy similarity, 0-100
+    pub similarity: Option<u8>,
+    pub submodule: Option<SubmoduleState>,
+    pub conflict: Option<ConflictInfo>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileStatus {
+    Modified,
+    TypeChanged,
+    Added,
+    Deleted,
+    Renamed,
+    Copied,
+    Untracked,
+    Unmerged,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubmoduleState {
+    /// The submodule's checked out commit differs from the recorded one
+    pub commit_changed: bool,
+    pub has_tracked_changes: bool,
+    pub has_untracked_changes: bool,
+}
+
+/// How each side changed a conflicted path
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictKind {
+    BothModified,
+    BothAdded,
+    BothDeleted,
+    AddedByUs,
+    AddedByThem,
+    DeletedByUs,
+    DeletedByThem,
+}
+
+/// Object ids of the index stages of a conflicted path; `None` when that side has no file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConflictInfo {
+    pub kind: ConflictKind,
+    pub base: Option<String>,
+    pub ours: Option<String>,
+    pub theirs: Option<String>,
+}
+
+/// Arguments of the `git status` call; untracked files are listed individually
+pub const STATUS_ARGS: [&str; 5] = ["status", "--porcelain=v2", "-z", "--branch", "--untracked-files=all"];
+
+/// Status of a worktree: branch, upstream, ahead/behind and files from one `git status` call
+pub fn read_status(worktree_path: &Path) -> Result<GitStatus, AppError> {
+    let output = git::run(worktree_path, &STATUS_ARGS)
+        .map_err(|e| e.context("Failed to get status"))?;
+    let mut status = parse_status(&output)?;
+    status.operation_in_progress = detect_operation(worktree_path)?;
+    Ok(status)
+}
+
+/// Commits `HEAD` and the default branch each have that the other doesn't. Kept out of
+/// [`read_status`] because resolving the default branch costs several more git calls.
+pub fn default_branch_divergence(worktree_path: &Path) -> Result<DefaultBranchDivergence, AppError> {
+    let name = branch::default_branch_ref(worktree_path)?;
+    let counts = git::run_text(worktree_path, &[
+        "rev-list", "--left-right", "--count", &format!("HEAD...{}", name), "--",
+    ])
+    .map_err(|e| e.context(format!("Failed to compare HEAD with {}", name)))?;
+    let (ahead, behind) = counts.split_once('\t')
+        .and_then(|(ahead, behind)| Some((ahead.trim().parse().ok()?, behind.trim().parse().ok()?)))
+        .ok_or_else(|| AppError::other(format!("Unexpected rev-list output: {}", counts)))?;
+    Ok(DefaultBranchDivergence { name, ahead, behind })
+}
+
+/// Parse `git status --porcelain=v2 -z --branch`
+pub fn parse_status(output: &[u8]) -> Result<GitStatus, AppError> {
+    let mut status = GitStatus {
+        branch: String::new(),
+        detached: false,
+        head_oid: None,
+        upstream: None,
+        upstream_state: UpstreamState::NoUpstream,
+        ahead: 0,
+        behind: 0,
+        operation_in_progress: None,
+        staged: Vec::new(),
+        unstaged: Vec::new(),
+        untracked: Vec::new(),
+        conflicted: Vec::new(),
+    };
+
+    let mut records = output
+        .split(|b| *b == 0)
+        .filter(|record| !record.is_empty())
+        .map(|record| String::from_utf8_lossy(record).into_owned());
+
+    while let Some(record) = records.next() {
+        let (kind, rest) = record.split_once(' ')
+            .ok_or_else(|| AppError::other(format!("Unexpected status line: {}", record)))?;
+
+        match kind {
+            "#" => parse_header(rest, &mut status),
+            "1" => {
+                // <XY> <sub> <mH> <mI> <mW> <hH> <hI> <path>
+                let fields = split_fields(rest, 8, &record)?;
+                push_changes(&mut status, fields[0], fields[1], fields[7], None, None)?;
+            }
+            "2" => {
+                // <XY> <sub> <mH> <mI> <mW> <hH> <hI> <Xscore> <path>, then NUL and the original path
+                let fields = split_fields(rest, 9, &record)?;
+                let orig_path = records.next()
+                    .ok_or_else(|| AppError::other(format!("Missing original path for {}", fields[8])))?;
+                let similarity = fields[7].get(1..).and_then(|score| score.parse().ok());
+                push_changes(&mut status, fields[0], fields[1], fields[8], Some(orig_path), similarity)?;
+            }
+            "u" => {
+                // <XY> <sub> <m1> <m2> <m3> <mW> <h1> <h2> <h3> <path>
+                let fields = split_fields(rest, 10, &record)?;
+                let stage = |mode: &str, oid: &str| (mode != "000000").then(|| oid.to_string());
+                status.conflicted.push(GitFile {
+                    path: fields[9].to_string(),
+                    status: FileStatus::Unmerged,
+                    orig_path: None,
+                    similarity: None,
+                    submodule: parse_submodule(fields[1])?,
+                    conflict: Some(ConflictInfo {
+                        kind: parse_conflict_kind(fields[0])?,
+                        base: stage(fields[2], fields[6]),
+                        ours: stage(fields[3], fields[7]),
+                        theirs: stage(fields[4], fields[8]),
+                    }),
+                });
+            }
+            "?" => status.untracked.push(GitFile {
+                path: rest.to_string(),
+                status: FileStatus::Untracked,
+                orig_path: None,
+                similarity: None,
+                submodule: None,
+                conflict: None,
+            }),
+            // Ignored files only show up with --ignored
+            "!" => {}
+            _ => return Err(AppError::other(format!("Unexpected status line: {}", record))),
+        }
+    }
+
+    Ok(status)
+}
+
+fn parse_header(header: &str, status: &mut GitStatus) {
+    let (key, value) = header.split_once(' ').unwrap_or((header, ""));
+    match key {
+        "branch.oid" if value != "(initial)" => status.head_oid = Some(value.to_string()),
+        "branch.head" if value == "(detached)" => {
+            status.branch = "HEAD".to_string();
+            status.detached = true;
+        }
+        "branch.head" => status.branch = value.to_string(),
+        // Git only prints branch.ab when the upstream ref exists
+        "branch.upstream" => {
+            status.upstream = Some(value.to_string());
+            status.upstream_state = UpstreamState::Gone;
+        }
+        "branch.ab" => {
+            status.upstream_state = UpstreamState::Tracking;
+            // +<ahead> -<behind>
+            for part in value.split(' ') {
+                if let Some(ahead) = part.strip_prefix('+') {
+                    status.ahead = ahead.parse().unwrap_or(0);
+                } else if let Some(behind) = part.strip_prefix('-') {
+                    status.behind = behind.parse().unwrap_or(0);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Split `n` space separated fields; the last one (a path) may itself contain spaces
+fn split_fields<'a>(rest: &'a str, n: usize, record: &str) -> Result<Vec<&'a str>, AppError> {
+    let fields: Vec<&str> = rest.splitn(n, ' ').collect();
+    if fields.len() != n {
+        return Err(AppError::other(format!("Unexpected status line: {}", record)));
+    }
+    Ok(fields)
+}
+
+fn push_changes(
+    status: &mut GitStatus,
+    xy: &str,
+    submodule: &str,
+    path: &str,
+    orig_path: Option<String>,
+    similarity: Option<u8>,
+) -> Result<(), AppError> {
+    let mut codes = xy.chars();
+    let (index, worktree) = match (codes.next(), codes.next()) {
+        (Some(index), Some(worktree)) => (index, worktree),
+        _ => return Err(AppError::other(format!("Unexpected status code: {}", xy))),
+    };
+    let submodule = parse_submodule(submodule)?;
+
+    for (code, list) in [(index, &mut status.staged), (worktree, &mut status.unstaged)] {
+        if let Some(file_status) = parse_change(code)? {
+            let renamed = matches!(file_status, FileStatus::Renamed | FileStatus::Copied);
+            list.push(GitFile {
+                path: path.to_string(),
+                status: file_status,
+                orig_path: if renamed { orig_path.clone() } else { None },
+                similarity: if renamed { similarity } else { None },
+                submodule,
+                conflict: None,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_change(code: char) -> Result<Option<FileStatus>, AppError> {
+    Ok(Some(match code {
+        '.' => return Ok(None),
+        'M' => FileStatus::Modified,
+        'T' => FileStatus::TypeChanged,
+        'A' => FileStatus::Added,
+        'D' => FileStatus::Deleted,
+        'R' => FileStatus::Renamed,
+        'C' => FileStatus::Copied,
+        _ => return Err(AppError::other(format!("Unexpected status code: {}", code))),
+    }))
+}
+
+/// `N...` for regular files, otherwise `S<c><m><u>`
+fn parse_submodule(field: &str) -> Result<Option<SubmoduleState>, AppError> {
+    if field.starts_with('N') {
+        return Ok(None);
+    }
+    match field.as_bytes() {
+        [b'S', commit, tracked, untracked] => Ok(Some(SubmoduleState {
+            commit_changed: *commit == b'C',
+            has_tracked_changes: *tracked == b'M',
+            has_untracked_changes: *untracked == b'U',
+        })),
+        _ => Err(AppError::other(format!("Unexpected submodule state: {}", field))),
+    }
+}
+
+fn parse_conflict_kind(xy: &str) -> Result<ConflictKind, AppError> {
+    Ok(match xy {
+        "UU" => ConflictKind::BothModified,
+        "AA" => ConflictKind::BothAdded,
+        "DD" => ConflictKind::BothDeleted,
+        "AU" => ConflictKind::AddedByUs,
+        "UA" => ConflictKind::AddedByThem,
+        "DU" => ConflictKind::DeletedByUs,
+        "UD" => ConflictKind::DeletedByThem,
+        _ => return Err(AppError::other(format!("Unexpected conflict state: {}", xy))),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::fixture::TempRepo;
+
+    const OID_A: &str = "1111111111111111111111111111111111111111";
+    const OID_B: &str = "2222222222222222222222222222222222222222";
+    const OID_C: &str = "3333333333333333333333333333333333333333";
+
+    #[test]
+    fn test_parse_status_fixture() {
+        let fixture = [
+            format!("# branch.oid {}", OID_A),
+            "# branch.head feature/login".to_string(),
+            "# branch.upstream origin/feature/login".to_string(),
+            "# branch.ab +3 -1".to_string(),
+            format!("1 M. N... 100644 100644 100644 {} {} src/main.rs", OID_A, OID_B),
+            format!("1 .M N... 100644 100644 100644 {} {} docs/read me.md", OID_A, OID_A),
+            format!("1 .T N... 100644 100644 120000 {} {} link", OID_A, OID_A),
+            format!("2 R. N... 100644 100644 100644 {} {} R87 new \"quoted\" name.txt", OID_A, OID_B),
+            "old -> name.txt".to_string(),
+            format!("1 .M SCM. 160000 160000 160000 {} {} vendor/lib", OID_A, OID_A),
+            format!("u UU N... 100644 100644 100644 100644 {} {} {} both.rs", OID_A, OID_B, OID_C),
+            format!("u DU N... 100644 000000 100644 100644 {} {} {} gone.rs", OID_A, "0".repeat(40), OID_C),
+            "? new file.txt".to_string(),
+        ]
+        .join("\0");
+
+        let status = parse_status(fixture.as_bytes()).unwrap();
+        assert_eq!(status.branch, "feature/login");
+        assert_eq!(status.head_oid.as_deref(), Some(OID_A));
+        assert_eq!(status.upstream.as_deref(), Some("origin/feature/login"));
+        assert_eq!(status.upstream_state, UpstreamState::Tracking);
+        assert_eq!((status.ahead, status.behind), (3, 1));
+
+        let staged: Vec<_> = status.staged.iter().map(|f| (f.path.as_str(), f.status)).collect();
+        assert_eq!(staged, vec![
+            ("src/main.rs", FileStatus::Modified),
+            ("new \"quoted\" name.txt", FileStatus::Renamed),
+        ]);
+        assert_eq!(status.staged[1].orig_path.as_deref(), Some("old -> name.txt"));
+        assert_eq!(status.staged[1].similarity, Some(87));
+
+        let unstaged: Vec<_> = status.unstaged.iter().map(|f| (f.path.as_str(), f.status)).collect();
+        assert_eq!(unstaged, vec![
+            ("docs/read me.md", FileStatus::Modified),
+            ("link", FileStatus::TypeChanged),
+            ("vendor/lib", FileStatus::Modified),
+        ]);
+        assert_eq!(status.unstaged[2].submodule, Some(SubmoduleState {
+            commit_changed: true,
+            has_tracked_changes: true,
+            has_untracked_changes: false,
+        }));
+
+        assert_eq!(status.conflicted.len(), 2);
+        let both = status.conflicted[0].conflict.as_ref().unwrap();
+        assert_eq!(both.kind, ConflictKind::BothModified);
+        assert_eq!(both.theirs.as_deref(), Some(OID_C));
+        let gone = status.conflicted[1].conflict.as_ref().unwrap();
+        assert_eq!(gone.kind, ConflictKind::DeletedByUs);
+        assert_eq!(gone.ours, None);
+        assert_eq!(gone.base.as_deref(), Some(OID_A));
+
+        assert_eq!(status.untracked[0].path, "new file.txt");
+    }
+
+    #[test]
+    fn test_parse_status_initial_and_detached() {
+        let initial = parse_status(b"# branch.oid (initial)\0# branch.head main\0").unwrap();
+        assert_eq!(initial.head_oid, None);
+        assert_eq!(initial.branch, "main");
+        assert_eq!(initial.upstream, None);
+        assert_eq!(initial.upstream_state, UpstreamState::NoUpstream);
+
+        let gone = parse_status(format!("# branch.oid {}\0# branch.head fork\0# branch.upstream upstream/fork\0", OID_A).as_bytes()).unwrap();
+        assert_eq!(gone.upstream.as_deref(), Some("upstream/fork"));
+        assert_eq!(gone.upstream_state, UpstreamState::Gone);
+
+        let detached = parse_status(format!("# branch.oid {}\0# branch.head (detached)\0", OID_A).as_bytes()).unwrap();
+        assert!(detached.detached);
+        assert_eq!(detached.branch, "HEAD");
+
+        assert!(parse_status(b"1 M. N... truncated").is_err());
+    }
+
+    #[test]
+    fn test_read_status_from_repo() {
+        let repo = TempRepo::new();
+        repo.write("shared.txt", "base\n");
+        repo.write("old name.txt", "a file that will be renamed\n");
+        repo.commit_all("initial");
+
+        repo.git(&["checkout", "-q", "-b", "other"]);
+        repo.write("shared.txt", "theirs\n");
+        repo.commit_all("theirs");
+        repo.git(&["checkout", "-q", "main"]);
+        repo.write("shared.txt", "ours\n");
+        repo.commit_all("ours");
+
+        let _ = std::process::Command::new("git")
+            .args(["-C", repo.path().to_str().unwrap(), "merge", "-q", "other"])
+            .output();
+        repo.git(&["mv", "old name.txt", "new name.txt"]);
+        repo.write("untracked.txt", "?\n");
+
+        let status = read_status(repo.path()).unwrap();
+        assert_eq!(status.branch, "main");
+        assert!(status.head_oid.is_some());
+        assert_eq!(status.staged.len(), 1);
+        assert_eq!(status.staged[0].path, "new name.txt");
+        assert_eq!(status.staged[0].orig_path.as_deref(), Some("old name.txt"));
+        assert_eq!(status.conflicted[0].path, "shared.txt");
+        assert_eq!(status.conflicted[0].conflict.as_ref().unwrap().kind, ConflictKind::BothModified);
+        assert_eq!(status.untracked[0].path, "untracked.txt");
+        assert!(matches!(status.operation_in_progress, Some(OperationInProgress::Merge { .. })));
+    }
+
+    #[test]
+    fn test_upstream_states_and_default_branch_divergence() {
+        let fork = TempRepo::new_bare();
+        let repo = TempRepo::new();
+        repo.write("a.txt", "a\n");
+        repo.commit_all("initial");
+        repo.git(&["remote", "add", "fork", fork.path().to_str().unwrap()]);
+
+        // A linked worktree, whose own checkout must not be mistaken for the default branch
+        let worktree = repo.add_worktree("feature");
+        worktree.write("a.txt", "feature\n");
+        worktree.commit_all("feature work");
+
+        let status = read_status(worktree.path()).unwrap();
+        assert_eq!(status.upstream_state, UpstreamState::NoUpstream);
+        let divergence = default_branch_divergence(worktree.path()).unwrap();
+        assert_eq!((divergence.name.as_str(), divergence.ahead, divergence.behind), ("main", 1, 0));
+
+        // Tracking a remote that isn't called origin
+        worktree.git(&["push", "-q", "-u", "fork", "feature:renamed"]);
+        let status = read_status(worktree.path()).unwrap();
+        assert_eq!(status.upstream.as_deref(), Some("fork/renamed"));
+        assert_eq!(status.upstream_state, UpstreamState::Tracking);
+        assert_eq!((status.ahead, status.behind), (0, 0));
+
+        fork.git(&["branch", "-D", "renamed"]);
+        repo.git(&["fetch", "-q", "--prune", "fork"]);
+        let status = read_status(worktree.path()).unwrap();
+        assert_eq!(status.upstream_state, UpstreamState::Gone);
+    }
+}
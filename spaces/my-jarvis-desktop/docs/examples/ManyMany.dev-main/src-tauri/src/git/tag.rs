@@ -0,0 +1,475 @@
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AppError, ErrorKind};
+use crate::git;
+
+const TAG_FORMAT: &str = "%(refname:short)%00%(objecttype)%00%(objectname)%00%(*objectname)%00%(creatordate:iso-strict)%00%(taggername)%00%(contents:subject)%00%(contents:body)%00%(contents:signature)%1e";
+
+/// Major, minor and patch
+type Version = (u64, u64, u64);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TagInfo {
+    pub name: String,
+    /// What the tag ref points at: the tag object for annotated tags, otherwise the commit
+    pub sha: String,
+    /// The tagged commit
+    pub target: String,
+    pub annotated: bool,
+    pub signed: bool,
+    /// Annotated tags only
+    pub message: Option<String>,
+    pub tagger: Option<String>,
+    /// When it was tagged, or for lightweight tags when the commit was made
+    pub date: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TagKind {
+    Lightweight,
+    Annotated,
+    /// Annotated and signed with the user's configured GPG/SSH key
+    Signed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateTagRequest {
+    pub name: String,
+    /// Any commit-ish; `HEAD` by default
+    pub target: Option<String>,
+    pub kind: TagKind,
+    /// Required for annotated and signed tags
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeletedTag {
+    pub name: String,
+    /// Where the tag pointed, so it can be recreated
+    pub sha: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VersionBump {
+    Patch,
+    Minor,
+    Major,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TagSuggestion {
+    /// Highest release tag, e.g. `v1.4.2`; `None` before the first release
+    pub current: Option<String>,
+    pub next: String,
+    pub bump: VersionBump,
+    /// Commits on `HEAD` since `current`
+    pub commits: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChangelogEntry {
+    pub sha: String,
+    /// Conventional commit type, e.g. `feat`; `None` when the subject doesn't follow the convention
+    pub kind: Option<String>,
+    pub scope: Option<String>,
+    pub description: String,
+    pub breaking: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChangelogSection {
+    pub title: String,
+    pub entries: Vec<ChangelogEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Changelog {
+    /// Exclusive start; `None` covers the whole history
+    pub from: Option<String>,
+    pub to: String,
+    pub sections: Vec<ChangelogSection>,
+    pub markdown: String,
+}
+
+/// Section titles by commit type, in changelog order. Other types go under "Other changes".
+const SECTIONS: [(&str, &str); 6] = [
+    ("feat", "Features"),
+    ("fix", "Bug fixes"),
+    ("perf", "Performance"),
+    ("revert", "Reverts"),
+    ("refactor", "Refactoring"),
+    ("docs", "Documentation"),
+];
+
+/// All tags, newest first
+pub fn list_tags(repo: &Path) -> Result<Vec<TagInfo>, AppError> {
+    let output = git::run(repo, &[
+        "for-each-ref",
+        "--sort=-creatordate",
+        &format!("--format={}", TAG_FORMAT),
+        "refs/tags",
+    ])?;
+
+    Ok(String::from_utf8_lossy(&output).split('\x1e').filter_map(parse_tag).collect())
+}
+
+fn parse_tag(record: &str) -> Option<TagInfo> {
+    let fields: Vec<&str> = record.trim_start_matches('\n').split('\0').collect();
+    if fields.len() != 9 {
+        return None;
+    }
+
+    let annotated = fields[1] == "tag";
+    let message = [fields[6], fields[7]].join("\n\n").trim().to_string();
+    Some(TagInfo {
+        name: fields[0].to_string(),
+        sha: fields[2].to_string(),
+        target: if annotated { fields[3] } else { fields[2] }.to_string(),
+        annotated,
+        signed: annotated && !fields[8].is_empty(),
+        message: annotated.then_some(message),
+        tagger: (annotated && !fields[5].is_empty()).then(|| fields[5].to_string()),
+        date: fields[4].to_string(),
+    })
+}
+
+pub fn create_tag(repo: &Path, request: &CreateTagRequest) -> Result<TagInfo, AppError> {
+    let name = request.name.as_str();
+    if name.starts_with('-') || git::run(repo, &["check-ref-format", &format!("refs/tags/{}", name)]).is_err() {
+        return Err(AppError::invalid(format!("'{}' is not a valid tag name", name)));
+    }
+    let target = request.target.as_deref().filter(|target| !target.is_empty()).unwrap_or("HEAD");
+    if target.starts_with('-') {
+        return Err(AppError::invalid(format!("Invalid target: {}", target)));
+    }
+    if find_tag(repo, name).is_ok() {
+        return Err(AppError::new(
+            ErrorKind::TagExists { tag: name.to_string() },
+            format!("Tag '{}' already exists", name),
+        ));
+    }
+
+    let message = request.message.as_deref().unwrap_or("").trim();
+    match request.kind {
+        TagKind::Lightweight if !message.is_empty() => {
+            return Err(AppError::invalid("Lightweight tags have no message; create an annotated tag instead"));
+        }
+        TagKind::Lightweight => git::run(repo, &["tag", name, target]),
+        TagKind::Annotated | TagKind::Signed if message.is_empty() => {
+            return Err(AppError::invalid("Annotated tags need a message"));
+        }
+        TagKind::Annotated => git::run_with_input(repo, &["tag", "-a", "-F", "-", name, target], message.as_bytes()),
+        TagKind::Signed => git::run_with_input(repo, &["tag", "-s", "-F", "-", name, target], message.as_bytes()),
+    }
+    .map_err(|e| e.context(format!("Failed to create tag '{}'", name)))?;
+
+    find_tag(repo, name)
+}
+
+pub fn delete_tag(repo: &Path, name: &str) -> Result<DeletedTag, AppError> {
+    let tag = find_tag(repo, name)?;
+    git::run(repo, &["tag", "-d", name]).map_err(|e| e.context(format!("Failed to delete tag '{}'", name)))?;
+
+    Ok(DeletedTag {
+        name: tag.name,
+        sha: tag.sha,
+    })
+}
+
+fn find_tag(repo: &Path, name: &str) -> Result<TagInfo, AppError> {
+    let output = git::run(repo, &[
+        "for-each-ref",
+        &format!("--format={}", TAG_FORMAT),
+        &format!("refs/tags/{}", name),
+    ])?;
+    String::from_utf8_lossy(&output)
+        .split('\x1e')
+        .filter_map(parse_tag)
+        .find(|tag| tag.name == name)
+        .ok_or_else(|| AppError::new(
+            ErrorKind::TagNotFound { tag: name.to_string() },
+            format!("Tag '{}' does not exist", name),
+        ))
+}
+
+/// The next semver tag after the highest release tag reachable from HEAD. Without an explicit `bump`, conventional
+/// commits since that tag decide: breaking changes bump major, features minor, anything else patch.
+pub fn suggest_next_tag(repo: &Path, bump: Option<VersionBump>) -> Result<TagSuggestion, AppError> {
+    let current = last_release(repo, "HEAD")?;
+
+    let range = match &current {
+        Some((_, tag)) => format!("refs/tags/{}..HEAD", tag),
+        None => "HEAD".to_string(),
+    };
+    let entries = log_entries(repo, &range)?;
+    let bump = bump.unwrap_or_else(|| {
+        entries.iter()
+            .map(|entry| match entry.kind.as_deref() {
+                _ if entry.breaking => VersionBump::Major,
+                Some("feat") => VersionBump::Minor,
+                _ => VersionBump::Patch,
+            })
+            .max()
+            .unwrap_or(VersionBump::Patch)
+    });
+
+    let (prefix, (major, minor, patch)) = match &current {
+        Some((version, tag)) => (if tag.starts_with('v') { "v" } else { "" }, *version),
+        None => ("v", (0, 0, 0)),
+    };
+    let next = match bump {
+        VersionBump::Major => (major + 1, 0, 0),
+        VersionBump::Minor => (major, minor + 1, 0),
+        VersionBump::Patch => (major, minor, patch + 1),
+    };
+
+    Ok(TagSuggestion {
+        current: current.map(|(_, tag)| tag),
+        next: format!("{}{}.{}.{}", prefix, next.0, next.1, next.2),
+        bump,
+        commits: entries.len(),
+    })
+}
+
+/// The highest release tag reachable from `reference`, with its version.
+/// Tags on other branches are left out, as their unrelated history would end up in the range after them.
+fn last_release(repo: &Path, reference: &str) -> Result<Option<(Version, String)>, AppError> {
+    let tags = git::run_text(repo, &["tag", "--list", "--merged", reference])?;
+    Ok(tags.lines()
+        .filter_map(|tag| parse_version(tag).map(|version| (version, tag.to_string())))
+        .max_by_key(|(version, _)| *version))
+}
+
+/// `1.2.3` or `v1.2.3`; pre-releases like `v1.2.3-rc.1` don't count as releases
+fn parse_version(tag: &str) -> Option<Version> {
+    let mut parts = tag.strip_prefix('v').unwrap_or(tag).split('.');
+    let mut next = || parts.next().filter(|part| part.chars().all(|c| c.is_ascii_digit()))?.parse().ok();
+    let version = (next()?, next()?, next()?);
+    parts.next().is_none().then_some(version)
+}
+
+/// Commits in `from..to` grouped by conventional commit type. Without `from`, starts after the
+/// latest release tag before `to`.
+pub fn changelog(repo: &Path, from: Option<&str>, to: Option<&str>) -> Result<Changelog, AppError> {
+    let to = to.filter(|to| !to.is_empty()).unwrap_or("HEAD");
+    for reference in [Some(to), from].into_iter().flatten() {
+        if reference.starts_with('-') {
+            return Err(AppError::invalid(format!("Invalid ref: {}", reference)));
+        }
+    }
+    let from = match from.filter(|from| !from.is_empty()) {
+        Some(from) => Some(from.to_string()),
+        // Start from the parent so a tagged `to` isn't its own starting point. A root `to` has no
+        // parent and so no earlier release.
+        None => last_release(repo, &format!("{}^", to)).ok().flatten().map(|(_, tag)| tag),
+    };
+
+    let range = match &from {
+        Some(from) => format!("{}..{}", from, to),
+        None => to.to_string(),
+    };
+    let entries = log_entries(repo, &range).map_err(|e| e.context("Failed to read changelog commits"))?;
+    let sections = group_entries(entries);
+
+    Ok(Changelog {
+        from,
+        to: to.to_string(),
+        markdown: render_markdown(&sections),
+        sections,
+    })
+}
+
+fn log_entries(repo: &Path, range: &str) -> Result<Vec<ChangelogEntry>, AppError> {
+    let output = git::run(repo, &["log", "--no-merges", "--format=%H%x00%s%x00%b%x1e", range, "--"])?;
+    Ok(String::from_utf8_lossy(&output)
+        .split('\x1e')
+        .filter_map(|record| {
+            let mut fields = record.trim_start_matches('\n').split('\0');
+            let (sha, subject, body) = (fields.next()?, fields.next()?, fields.next().unwrap_or(""));
+            Some(parse_entry(sha, subject, body))
+        })
+        .collect())
+}
+
+/// `type(scope)!: description`, with `BREAKING CHANGE:` footers also marking breaking changes
+fn parse_entry(sha: &str, subject: &str, body: &str) -> ChangelogEntry {
+    let breaking_footer = body.lines()
+        .any(|line| line.starts_with("BREAKING CHANGE:") || line.starts_with("BREAKING-CHANGE:"));
+    let conventional = subject.split_once(": ").and_then(|(head, description)| {
+        let (head, bang) = match head.strip_suffix('!') {
+            Some(head) => (head, true),
+            None => (head, false),
+        };
+        let (kind, scope) = match head.split_once('(') {
+            Some((kind, scope)) => (kind, Some(scope.strip_suffix(')')?.to_string())),
+            None => (head, None),
+        };
+        if kind.is_empty() || !kind.chars().all(|c| c.is_ascii_alphabetic()) {
+            return None;
+        }
+        Some((kind.to_lowercase(), scope, bang, description.trim().to_string()))
+    });
+
+    match conventional {
+        Some((kind, scope, bang, description)) => ChangelogEntry {
+            sha: sha.to_string(),
+            kind: Some(kind),
+            scope,
+            description,
+            breaking: bang || breaking_footer,
+        },
+        None => ChangelogEntry {
+            sha: sha.to_string(),
+            kind: None,
+            scope: None,
+            description: subject.trim().to_string(),
+            breaking: breaking_footer,
+        },
+    }
+}
+
+/// Breaking changes first, then the known types, then everything else; empty sections are left out
+fn group_entries(entries: Vec<ChangelogEntry>) -> Vec<ChangelogSection> {
+    let mut titles: Vec<&str> = vec!["Breaking changes"];
+    titles.extend(SECTIONS.iter().map(|(_, title)| *title));
+    titles.push("Other changes");
+    let mut sections: Vec<ChangelogSection> = titles.iter()
+        .map(|title| ChangelogSection { title: title.to_string(), entries: Vec::new() })
+        .collect();
+
+    for entry in entries {
+        let index = if entry.breaking {
+            0
+        } else {
+            SECTIONS.iter()
+                .position(|(kind, _)| entry.kind.as_deref() == Some(kind))
+                .map_or(titles.len() - 1, |position| position + 1)
+        };
+        sections[index].entries.push(entry);
+    }
+    sections.retain(|section| !section.entries.is_empty());
+    sections
+}
+
+fn render_markdown(sections: &[ChangelogSection]) -> String {
+    let mut markdown = String::new();
+    for section in sections {
+        markdown.push_str(&format!("### {}\n\n", section.title));
+        for entry in &section.entries {
+            let scope = entry.scope.as_ref().map(|scope| format!("**{}:** ", scope)).unwrap_or_default();
+            markdown.push_str(&format!("- {}{} ({})\n", scope, entry.description, &entry.sha[..entry.sha.len().min(7)]));
+        }
+        markdown.push('\n');
+    }
+    markdown.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::fixture::TempRepo;
+
+    fn request(name: &str, kind: TagKind, message: Option<&str>) -> CreateTagRequest {
+        CreateTagRequest {
+            name: name.to_string(),
+            target: None,
+            kind,
+            message: message.map(String::from),
+        }
+    }
+
+    #[test]
+    fn test_tag_lifecycle() {
+        let repo = TempRepo::new();
+        repo.write("a.txt", "a\n");
+        repo.commit_all_at("initial", 1_700_000_000);
+        let head = repo.git(&["rev-parse", "HEAD"]).trim().to_string();
+
+        let light = create_tag(repo.path(), &request("v0.1.0", TagKind::Lightweight, None)).unwrap();
+        assert_eq!((light.target.as_str(), light.annotated, light.message.as_deref()), (head.as_str(), false, None));
+
+        let annotated = create_tag(repo.path(), &request("v0.2.0", TagKind::Annotated, Some("Second\n\nMore notes"))).unwrap();
+        assert!(annotated.annotated && !annotated.signed);
+        assert_eq!(annotated.target, head);
+        assert_ne!(annotated.sha, head);
+        assert_eq!(annotated.message.as_deref(), Some("Second\n\nMore notes"));
+        assert_eq!(annotated.tagger.as_deref(), Some("Test"));
+
+        let names: Vec<String> = list_tags(repo.path()).unwrap().into_iter().map(|tag| tag.name).collect();
+        assert_eq!(names, vec!["v0.2.0", "v0.1.0"]);
+
+        let error = create_tag(repo.path(), &request("v0.1.0", TagKind::Lightweight, None)).unwrap_err();
+        assert!(matches!(error.kind, ErrorKind::TagExists { .. }), "{}", error);
+        assert!(matches!(create_tag(repo.path(), &request("bad..name", TagKind::Lightweight, None)).unwrap_err().kind, ErrorKind::InvalidInput));
+        assert!(create_tag(repo.path(), &request("v0.3.0", TagKind::Annotated, Some("  "))).is_err());
+
+        let deleted = delete_tag(repo.path(), "v0.2.0").unwrap();
+        assert_eq!(deleted.sha, annotated.sha);
+        assert!(matches!(delete_tag(repo.path(), "v0.2.0").unwrap_err().kind, ErrorKind::TagNotFound { .. }));
+    }
+
+    #[test]
+    fn test_suggest_next_tag_and_changelog() {
+        let repo = TempRepo::new();
+        repo.write("a.txt", "a\n");
+        repo.commit_all("chore: initial");
+        assert_eq!(suggest_next_tag(repo.path(), None).unwrap().next, "v0.0.1");
+
+        repo.git(&["tag", "v1.2.3"]);
+        repo.git(&["tag", "v1.10.0-rc.1"]);
+        repo.git(&["tag", "release-candidate"]);
+        repo.write("a.txt", "b\n");
+        repo.commit_all("fix(parser): handle empty input");
+        repo.write("a.txt", "c\n");
+        repo.commit_all("feat: add export");
+        repo.write("a.txt", "d\n");
+        repo.commit_all("Tidy up");
+
+        let suggestion = suggest_next_tag(repo.path(), None).unwrap();
+        assert_eq!(suggestion.current.as_deref(), Some("v1.2.3"));
+        assert_eq!((suggestion.next.as_str(), suggestion.bump, suggestion.commits), ("v1.3.0", VersionBump::Minor, 3));
+        assert_eq!(suggest_next_tag(repo.path(), Some(VersionBump::Patch)).unwrap().next, "v1.2.4");
+
+        let hotfix = repo.add_worktree("hotfix");
+        hotfix.write("b.txt", "b\n");
+        hotfix.commit_all("fix: on another branch");
+        hotfix.git(&["tag", "v5.0.0"]);
+        let suggestion = suggest_next_tag(repo.path(), None).unwrap();
+        assert_eq!(suggestion.current.as_deref(), Some("v1.2.3"));
+        assert_eq!((suggestion.next.as_str(), suggestion.commits), ("v1.3.0", 3));
+
+        repo.write("a.txt", "e\n");
+        repo.commit_all("refactor!: drop the v1 API\n\nBREAKING CHANGE: clients must migrate");
+        assert_eq!(suggest_next_tag(repo.path(), None).unwrap().next, "v2.0.0");
+
+        // Nearer than v1.2.3 but not a release, so neither command starts from it
+        repo.git(&["tag", "v1.2", "HEAD~2"]);
+        repo.git(&["tag", "v1.2.3.4", "HEAD~1"]);
+        assert_eq!(suggest_next_tag(repo.path(), None).unwrap().current.as_deref(), Some("v1.2.3"));
+        let changelog = changelog(repo.path(), None, None).unwrap();
+        assert_eq!(changelog.from.as_deref(), Some("v1.2.3"));
+        let titles: Vec<&str> = changelog.sections.iter().map(|section| section.title.as_str()).collect();
+        assert_eq!(titles, vec!["Breaking changes", "Features", "Bug fixes", "Other changes"]);
+        assert_eq!(changelog.sections[2].entries[0].scope.as_deref(), Some("parser"));
+        assert!(changelog.markdown.starts_with("### Breaking changes\n\n- drop the v1 API ("), "{}", changelog.markdown);
+        assert!(changelog.markdown.contains("- **parser:** handle empty input ("), "{}", changelog.markdown);
+        assert!(changelog.markdown.contains("### Other changes\n\n- Tidy up ("), "{}", changelog.markdown);
+    }
+
+    #[test]
+    fn test_parse_version_and_entry() {
+        assert_eq!(parse_version("v1.2.3"), Some((1, 2, 3)));
+        assert_eq!(parse_version("10.0.1"), Some((10, 0, 1)));
+        assert_eq!(parse_version("v1.2.3-rc.1"), None);
+        assert_eq!(parse_version("v1.2"), None);
+
+        let entry = parse_entry("abc", "feat(ui)!: new layout", "");
+        assert_eq!((entry.kind.as_deref(), entry.scope.as_deref(), entry.breaking), (Some("feat"), Some("ui"), true));
+        assert_eq!(entry.description, "new layout");
+        assert_eq!(parse_entry("abc", "Merge branch 'x': stuff", "").kind, None);
+        assert!(parse_entry("abc", "update docs", "BREAKING CHANGE: renamed").breaking);
+    }
+}
@@ -0,0 +1,251 @@
+use std::fs;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppError;
+use crate::git;
+use crate::git::operation::conflicted_paths;
+use crate::git::snapshot::{create_snapshot, worktree_tree, Snapshot};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferMode {
+    /// Leave the source worktree as it is
+    Copy,
+    /// Remove the changes from the source once they applied cleanly
+    Move,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransferResult {
+    pub mode: TransferMode,
+    /// Paths the patch touched
+    pub files: Vec<String>,
+    /// The patch didn't apply directly and was merged with `git apply --3way`
+    pub three_way: bool,
+    /// Paths left with conflict markers in the target
+    pub conflicts: Vec<String>,
+    /// Whether the changes were removed from the source (only for a clean move)
+    pub source_cleared: bool,
+    /// Snapshot of the target taken before applying
+    pub target_snapshot: Snapshot,
+    /// Snapshot of the source taken before clearing it
+    pub source_snapshot: Option<Snapshot>,
+}
+
+/// Carry the uncommitted changes (staged, unstaged and untracked) of `paths`, or of everything,
+/// from one worktree to another of the same repository
+pub fn transfer_changes(
+    from_worktree: &Path,
+    to_worktree: &Path,
+    paths: Option<&[String]>,
+    mode: TransferMode,
+) -> Result<TransferResult, AppError> {
+    if git::common_dir(from_worktree)? != git::common_dir(to_worktree)? {
+        return Err(AppError::invalid("Both worktrees must belong to the same repository"));
+    }
+    if fs::canonicalize(from_worktree).ok() == fs::canonicalize(to_worktree).ok() {
+        return Err(AppError::invalid("Source and target are the same worktree"));
+    }
+    let paths = paths.unwrap_or_default();
+
+    let (patch, files) = build_patch(from_worktree, paths)?;
+    if files.is_empty() {
+        return Err(AppError::invalid("No changes to transfer"));
+    }
+
+    let target_snapshot = create_snapshot(to_worktree, "transfer changes", &files)?;
+    let three_way = match apply_patch(to_worktree, &patch, false) {
+        Ok(()) => None,
+        Err(_) => {
+            // --3way updates the index too; remember it so the result can be left unstaged
+            let index_tree = git::run_text(to_worktree, &["write-tree"])
+                .map_err(|e| e.context("Failed to apply changes"))?;
+            if let Err(e) = apply_patch(to_worktree, &patch, true) {
+                // Without conflicts git leaves the target untouched
+                if conflicted_paths(to_worktree)?.is_empty() {
+                    return Err(e.context("Failed to apply changes"));
+                }
+            }
+            Some(index_tree)
+        }
+    };
+    let conflicts: Vec<String> = conflicted_paths(to_worktree)?
+        .into_iter()
+        .filter(|path| files.contains(path))
+        .collect();
+
+    // Leave cleanly merged files unstaged like a direct apply does; conflicts stay in the index
+    if let Some(index_tree) = &three_way {
+        let source = format!("--source={}", index_tree);
+        let mut args = vec!["restore", &source, "--staged", "--"];
+        args.extend(files.iter().filter(|file| !conflicts.contains(file)).map(String::as_str));
+        if args.len() > 4 {
+            git::run(to_worktree, &args).map_err(|e| e.context("Failed to unstage transferred changes"))?;
+        }
+    }
+
+    let mut source_snapshot = None;
+    if mode == TransferMode::Move && conflicts.is_empty() {
+        source_snapshot = Some(create_snapshot(from_worktree, "move changes", &files)?);
+        clear_changes(from_worktree, &files)?;
+    }
+
+
+    Ok(TransferResult {
+        mode,
+        files,
+        three_way: three_way.is_some(),
+        conflicts,
+        source_cleared: source_snapshot.is_some(),
+        target_snapshot,
+        source_snapshot,
+    })
+}
+
+/// Binary patch from `HEAD` to the worktree state of `paths`, and the paths it touches
+fn build_patch(worktree_path: &Path, paths: &[String]) -> Result<(Vec<u8>, Vec<String>), AppError> {
+    let tree = worktree_tree(worktree_path, paths)?;
+    let base = match git::run_text(worktree_path, &["rev-parse", "--verify", "-q", "HEAD^{tree}"]) {
+        Ok(head) => head,
+        Err(_) => git::run_text(worktree_path, &["hash-object", "-t", "tree", "/dev/null"])?,
+    };
+
+    let mut diff_args = vec![
+        "diff", "--no-color", "--no-ext-diff", "--no-renames",
+        "--src-prefix=a/", "--dst-prefix=b/",
+        "--binary", "--full-index", &base, &tree, "--",
+    ];
+    diff_args.extend(paths.iter().map(String::as_str));
+    let patch = git::run(worktree_path, &diff_args)?;
+
+    let mut name_args = vec!["diff", "--name-only", "-z", "--no-renames", &base, &tree, "--"];
+    name_args.extend(paths.iter().map(String::as_str));
+    let files = git::run(worktree_path, &name_args)?
+        .split(|b| *b == 0)
+        .filter(|path| !path.is_empty())
+        .map(|path| String::from_utf8_lossy(path).into_owned())
+        .collect();
+
+    Ok((patch, files))
+}
+
+fn apply_patch(worktree_path: &Path, patch: &[u8], three_way: bool) -> Result<(), AppError> {
+    let mut args = vec!["apply", "--binary", "--whitespace=nowarn"];
+    if three_way {
+        args.push("--3way");
+    }
+    args.push("-");
+
+    git::run_with_input(worktree_path, &args, patch).map(|_| ())
+}
+
+/// Put `files` back to `HEAD` in the index and worktree, deleting ones `HEAD` doesn't have
+fn clear_changes(worktree_path: &Path, files: &[String]) -> Result<(), AppError> {
+    let mut ls_args = vec!["ls-tree", "-r", "-z", "--name-only", "HEAD", "--"];
+    ls_args.extend(files.iter().map(String::as_str));
+    let in_head: Vec<String> = git::run(worktree_path, &ls_args)
+        .unwrap_or_default()
+        .split(|b| *b == 0)
+        .filter(|path| !path.is_empty())
+        .map(|path| String::from_utf8_lossy(path).into_owned())
+        .collect();
+
+    if !in_head.is_empty() {
+        let mut args = vec!["restore", "--source=HEAD", "--staged", "--worktree", "--"];
+        args.extend(in_head.iter().map(String::as_str));
+        git::run(worktree_path, &args)?;
+    }
+
+    for file in files.iter().filter(|file| !in_head.contains(file)) {
+        git::run(worktree_path, &["rm", "-q", "--cached", "--ignore-unmatch", "--", file])?;
+        let path = worktree_path.join(file);
+        if path.exists() {
+            fs::remove_file(&path).map_err(|e| AppError::io(format!("Failed to remove {}", file), e))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::fixture::TempRepo;
+
+    fn with_worktree() -> (TempRepo, TempRepo) {
+        let repo = TempRepo::new();
+        repo.write("a.txt", "one\ntwo\nthree\n");
+        repo.write("b.txt", "b\n");
+        repo.commit_all("initial");
+        let other = repo.add_worktree("other");
+        (repo, other)
+    }
+
+    #[test]
+    fn test_copy_and_move_with_untracked_and_binary() {
+        let (repo, other) = with_worktree();
+        repo.write("a.txt", "one\nTWO\nthree\n");
+        repo.git(&["add", "a.txt"]);
+        repo.write("new/notes.md", "untracked\n");
+        std::fs::write(repo.path().join("image.bin"), [0u8, 159, 146, 150, 0, 1]).unwrap();
+        repo.write("b.txt", "not selected\n");
+
+        let selected = vec!["a.txt".to_string(), "new".to_string(), "image.bin".to_string()];
+        let copied = transfer_changes(repo.path(), other.path(), Some(&selected), TransferMode::Copy).unwrap();
+        assert_eq!(copied.files, vec!["a.txt", "image.bin", "new/notes.md"]);
+        assert!(!copied.three_way);
+        assert!(!copied.source_cleared);
+        assert_eq!(other.read("a.txt"), "one\nTWO\nthree\n");
+        assert_eq!(std::fs::read(other.path().join("image.bin")).unwrap(), vec![0u8, 159, 146, 150, 0, 1]);
+        assert_eq!(other.read("b.txt"), "b\n");
+        assert_eq!(repo.read("a.txt"), "one\nTWO\nthree\n");
+
+        let third = repo.add_worktree("third");
+        let moved = transfer_changes(repo.path(), third.path(), Some(&selected), TransferMode::Move).unwrap();
+        assert!(moved.source_cleared);
+        assert_eq!(repo.read("a.txt"), "one\ntwo\nthree\n");
+        assert!(!repo.path().join("image.bin").exists());
+        assert!(!repo.path().join("new/notes.md").exists());
+        assert_eq!(repo.read("b.txt"), "not selected\n");
+        assert_eq!(repo.git(&["status", "--porcelain"]).trim(), "M b.txt");
+
+        assert!(transfer_changes(repo.path(), third.path(), Some(&selected), TransferMode::Copy)
+            .unwrap_err()
+            .to_string()
+            .contains("No changes"));
+    }
+
+    #[test]
+    fn test_three_way_fallback_leaves_changes_unstaged() {
+        let (repo, other) = with_worktree();
+        // Line three moved on in the target, breaking the patch's context without overlapping it
+        other.write("a.txt", "one\ntwo\nTHREE\n");
+        other.commit_all("change three");
+        repo.write("a.txt", "ONE\ntwo\nthree\n");
+        repo.write("b.txt", "changed b\n");
+
+        let merged = transfer_changes(repo.path(), other.path(), None, TransferMode::Copy).unwrap();
+        assert!(merged.three_way);
+        assert!(merged.conflicts.is_empty());
+        assert_eq!(other.read("a.txt"), "ONE\ntwo\nTHREE\n");
+        // Same as a direct apply: modified in the worktree, nothing staged
+        assert_eq!(other.git(&["status", "--porcelain"]), " M a.txt\n M b.txt\n");
+    }
+
+    #[test]
+    fn test_three_way_fallback_reports_conflicts() {
+        let (repo, other) = with_worktree();
+        // The target branch moved on, so the patch's context no longer matches
+        other.write("a.txt", "one\ntwo\nthree\nfour\n");
+        other.commit_all("add four");
+        repo.write("a.txt", "one\ntwo\nthree\nFOUR\n");
+
+        let merged = transfer_changes(repo.path(), other.path(), None, TransferMode::Move).unwrap();
+        assert!(merged.three_way);
+        assert_eq!(merged.conflicts, vec!["a.txt"]);
+        // A conflicted move leaves the source alone
+        assert!(!merged.source_cleared);
+        assert_eq!(repo.read("a.txt"), "one\ntwo\nthree\nFOUR\n");
+        assert!(other.read("a.txt").contains("<<<<<<<"));
+    }
+}
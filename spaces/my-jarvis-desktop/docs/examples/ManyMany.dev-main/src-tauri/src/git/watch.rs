@@ -0,0 +1,609 @@
+//! Keeps the status of open worktrees current by watching the filesystem instead of polling.
+//!
+//! Each watch covers the worktree's directories except gitignored and [`SKIPPED_DIRS`] ones, the
+//! top level of its git admin directory (`.git` or `.git/worktrees/<name>`) and the shared refs.
+//! On macOS the worktree is one recursive watch instead (see [`RECURSIVE_ROOT`]). Events are
+//! debounced; changes to gitignored files are dropped, changed worktree files are re-read with a
+//! path-limited `git status`, and changes to the index, `HEAD` or refs trigger a full refresh.
+//! If a watch can't be added, e.g. past inotify's `max_user_watches`, the worktree is polled too.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+use crate::error::AppError;
+use crate::git;
+use crate::git::status::{parse_status, read_status, GitFile, GitStatus, STATUS_ARGS};
+use crate::process::{self, RunOptions};
+
+/// Quiet period before a burst of events is processed
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Longest a continuous stream of events can hold back a refresh
+const MAX_DELAY: Duration = Duration::from_secs(2);
+
+/// Beyond this many changed paths a full refresh is cheaper
+const MAX_INCREMENTAL_PATHS: usize = 200;
+
+/// How often a worktree that couldn't be watched completely gets a full refresh
+const POLL_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Directories that are never watched, even when not ignored: they're huge and tools rewrite them
+const SKIPPED_DIRS: &[&str] = &[".git", "node_modules", "target"];
+
+/// FSEvents watches whole trees natively and restarts its stream for every path added, so there
+/// the root is watched recursively and ignored paths are filtered from the events instead
+const RECURSIVE_ROOT: bool = cfg!(target_os = "macos");
+
+/// Files in a git directory whose changes can change the status
+const STATE_FILES: &[&str] = &[
+    "index", "HEAD", "MERGE_HEAD", "CHERRY_PICK_HEAD", "REVERT_HEAD", "BISECT_LOG",
+    "rebase-merge", "rebase-apply", "refs", "packed-refs",
+];
+
+/// Payload of the `git-status-changed` event
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GitStatusChanged {
+    pub worktree_path: String,
+    pub status: GitStatus,
+}
+
+/// Running watches, by canonical worktree path
+#[derive(Default)]
+pub struct StatusWatchers {
+    watches: Mutex<HashMap<PathBuf, StatusWatch>>,
+}
+
+struct StatusWatch {
+    // Owns the watcher, so aborting it stops the events
+    task: tokio::task::JoinHandle<()>,
+}
+
+/// The watcher and the paths it has a watch on
+struct TreeWatcher {
+    watcher: RecommendedWatcher,
+    watched: HashSet<PathBuf>,
+    /// False once a watch couldn't be added; changes may then be missed, so the loop polls
+    complete: bool,
+}
+
+impl TreeWatcher {
+    fn new(watcher: RecommendedWatcher) -> Self {
+        TreeWatcher { watcher, watched: HashSet::new(), complete: true }
+    }
+
+    /// Watch `path`. A failure leaves the watch partial rather than failing it altogether.
+    fn watch(&mut self, path: &Path, mode: RecursiveMode) {
+        if !self.complete || self.watched.contains(path) {
+            return;
+        }
+        match self.watcher.watch(path, mode) {
+            Ok(()) => {
+                self.watched.insert(path.to_path_buf());
+            }
+            Err(_) => self.complete = false,
+        }
+    }
+
+    fn add(&mut self, dirs: Vec<PathBuf>) {
+        for dir in dirs {
+            self.watch(&dir, RecursiveMode::NonRecursive);
+        }
+    }
+
+    /// Forget `path` and everything under it once it's gone
+    fn remove(&mut self, path: &Path) {
+        let gone: Vec<PathBuf> = self.watched.iter().filter(|dir| dir.starts_with(path)).cloned().collect();
+        for dir in gone {
+            let _ = self.watcher.unwatch(&dir);
+            self.watched.remove(&dir);
+        }
+    }
+}
+
+impl Drop for StatusWatch {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[derive(Debug, Clone)]
+struct WatchedDirs {
+    root: PathBuf,
+    git_dir: PathBuf,
+    common_dir: PathBuf,
+}
+
+#[derive(Debug, PartialEq)]
+enum Change {
+    /// A path relative to the worktree root
+    Worktree(String),
+    /// The index, `HEAD`, refs or operation state
+    GitState,
+    Irrelevant,
+}
+
+impl StatusWatchers {
+    /// Start watching `worktree_path` (a no-op if it already is) and return its current status.
+    /// `on_change` gets every status that differs from the last one. Must be called within a tokio runtime.
+    pub async fn watch(
+        &self,
+        worktree_path: &Path,
+        on_change: impl Fn(GitStatus) + Send + 'static,
+    ) -> Result<GitStatus, AppError> {
+        let path = worktree_path.to_path_buf();
+        let dirs = process::blocking(move || watched_dirs(&path)).await?;
+        if self.watches.lock().unwrap().contains_key(&dirs.root) {
+            let root = dirs.root.clone();
+            return process::blocking(move || read_status(&root)).await;
+        }
+
+        // Walking the tree can take a while, so it happens off the executor and without the lock
+        let (events_tx, events_rx) = mpsc::unbounded_channel();
+        let setup_dirs = dirs.clone();
+        let (status, tree) = process::blocking(move || {
+            let status = read_status(&setup_dirs.root)?;
+            Ok((status, start_watcher(&setup_dirs, events_tx)?))
+        })
+        .await?;
+
+        let mut watches = self.watches.lock().unwrap();
+        if watches.contains_key(&dirs.root) {
+            return Ok(status);
+        }
+        let tree = Arc::new(Mutex::new(tree));
+        let task = tokio::spawn(watch_loop(dirs.clone(), tree, events_rx, status.clone(), on_change));
+        watches.insert(dirs.root, StatusWatch { task });
+        Ok(status)
+    }
+
+    /// Returns false if the worktree wasn't being watched
+    pub fn unwatch(&self, worktree_path: &Path) -> bool {
+        let root = fs::canonicalize(worktree_path).unwrap_or_else(|_| worktree_path.to_path_buf());
+        self.watches.lock().unwrap().remove(&root).is_some()
+    }
+}
+
+/// A watcher sending event paths to `events_tx`, watching the worktree and its git state
+fn start_watcher(dirs: &WatchedDirs, events_tx: mpsc::UnboundedSender<Vec<PathBuf>>) -> Result<TreeWatcher, AppError> {
+    let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        if let Ok(event) = event {
+            if !matches!(event.kind, EventKind::Access(_)) {
+                let _ = events_tx.send(event.paths);
+            }
+        }
+    })
+    .map_err(|e| AppError::other(format!("Failed to create watcher: {}", e)))?;
+    let mut tree = TreeWatcher::new(watcher);
+    if RECURSIVE_ROOT {
+        tree.watch(&dirs.root, RecursiveMode::Recursive);
+    } else {
+        tree.add(watchable_dirs(&dirs.root, &ignored_dirs(&dirs.root)?));
+    }
+
+    // Only the top level of the git dir: HEAD, the index and operation state, not objects
+    tree.watch(&dirs.git_dir, RecursiveMode::NonRecursive);
+    tree.watch(&dirs.common_dir.join("refs"), RecursiveMode::Recursive);
+    if dirs.common_dir != dirs.git_dir {
+        // packed-refs
+        tree.watch(&dirs.common_dir, RecursiveMode::NonRecursive);
+    }
+    Ok(tree)
+}
+
+fn watched_dirs(worktree_path: &Path) -> Result<WatchedDirs, AppError> {
+    let canonical = |path: PathBuf| fs::canonicalize(&path).map_err(|e| AppError::io(format!("Failed to resolve {}", path.display()), e));
+    Ok(WatchedDirs {
+        root: canonical(PathBuf::from(git::run_text(worktree_path, &["rev-parse", "--show-toplevel"])?))?,
+        git_dir: canonical(PathBuf::from(git::run_text(worktree_path, &["rev-parse", "--absolute-git-dir"])?))?,
+        common_dir: canonical(git::common_dir(worktree_path)?)?,
+    })
+}
+
+/// Directories git reports as ignored, e.g. `build/`; their contents aren't listed
+fn ignored_dirs(root: &Path) -> Result<HashSet<PathBuf>, AppError> {
+    let output = git::run(root, &["ls-files", "-z", "--others", "--ignored", "--exclude-standard", "--directory"])?;
+    Ok(output
+        .split(|b| *b == 0)
+        .filter_map(|path| String::from_utf8_lossy(path).strip_suffix('/').map(|dir| root.join(dir)))
+        .collect())
+}
+
+/// `dir` and the directories under it, leaving out `ignored` ones and [`SKIPPED_DIRS`].
+/// Symlinks aren't followed.
+fn watchable_dirs(dir: &Path, ignored: &HashSet<PathBuf>) -> Vec<PathBuf> {
+    let mut found = vec![dir.to_path_buf()];
+    let mut next = 0;
+    while next < found.len() {
+        let entries = fs::read_dir(&found[next]);
+        next += 1;
+        for entry in entries.into_iter().flatten().flatten() {
+            let path = entry.path();
+            let skipped = entry.file_name().to_str().is_some_and(|name| SKIPPED_DIRS.contains(&name));
+            if !skipped && entry.file_type().is_ok_and(|kind| kind.is_dir()) && !ignored.contains(&path) {
+                found.push(path);
+            }
+        }
+    }
+    found
+}
+
+/// Watch directories created under the worktree and forget deleted ones. A recursive watch
+/// covers them already.
+fn track_dirs(tree: &Mutex<TreeWatcher>, dirs: &WatchedDirs, paths: &[PathBuf]) -> Result<(), AppError> {
+    if RECURSIVE_ROOT {
+        return Ok(());
+    }
+    let mut created = Vec::new();
+    {
+        let mut tree = tree.lock().unwrap();
+        for path in paths.iter().filter(|path| matches!(classify(path, dirs), Change::Worktree(_))) {
+            let is_dir = fs::symlink_metadata(path).map(|metadata| metadata.is_dir());
+            match is_dir {
+                Err(_) => tree.remove(path),
+                Ok(true) if !tree.watched.contains(path) => created.push(path.clone()),
+                Ok(_) => {}
+            }
+        }
+    }
+    if created.is_empty() {
+        return Ok(());
+    }
+
+    let ignored = ignored_dirs(&dirs.root)?;
+    let new_dirs: Vec<PathBuf> = created.iter()
+        .filter(|dir| !ignored.contains(*dir))
+        .filter(|dir| !dir.strip_prefix(&dirs.root).unwrap_or(dir).components().any(|part| {
+            part.as_os_str().to_str().is_some_and(|name| SKIPPED_DIRS.contains(&name))
+        }))
+        .flat_map(|dir| watchable_dirs(dir, &ignored))
+        .collect();
+    tree.lock().unwrap().add(new_dirs);
+    Ok(())
+}
+
+async fn watch_loop(
+    dirs: WatchedDirs,
+    tree: Arc<Mutex<TreeWatcher>>,
+    mut events_rx: mpsc::UnboundedReceiver<Vec<PathBuf>>,
+    mut status: GitStatus,
+    on_change: impl Fn(GitStatus),
+) {
+    let mut last_full = Instant::now();
+    loop {
+        // A partial watch can miss changes, so it also gets a full refresh every POLL_INTERVAL
+        let polling = !tree.lock().unwrap().complete;
+        let first = if polling {
+            match tokio::time::timeout(POLL_INTERVAL.saturating_sub(last_full.elapsed()), events_rx.recv()).await {
+                Ok(Some(paths)) => Some(paths),
+                Ok(None) => return,
+                Err(_) => None,
+            }
+        } else {
+            match events_rx.recv().await {
+                Some(paths) => Some(paths),
+                None => return,
+            }
+        };
+        let full = polling && (first.is_none() || last_full.elapsed() >= POLL_INTERVAL);
+        let mut paths = first.unwrap_or_default();
+        let started = Instant::now();
+        loop {
+            let remaining = MAX_DELAY.saturating_sub(started.elapsed());
+            if full || remaining.is_zero() {
+                break;
+            }
+            match tokio::time::timeout(DEBOUNCE.min(remaining), events_rx.recv()).await {
+                Ok(Some(more)) => paths.extend(more),
+                Ok(None) => return,
+                Err(_) => break,
+            }
+        }
+        if full {
+            last_full = Instant::now();
+        }
+
+        let (task_dirs, task_tree, previous) = (dirs.clone(), tree.clone(), status.clone());
+        let refreshed = tokio::task::spawn_blocking(move || {
+            if let Err(e) = track_dirs(&task_tree, &task_dirs, &paths) {
+                eprintln!("Failed to watch new directories in {}: {}", task_dirs.root.display(), e);
+            }
+            if full {
+                read_status(&task_dirs.root).map(Some)
+            } else {
+                refresh(&task_dirs, &previous, &paths)
+            }
+        })
+        .await;
+        match refreshed {
+            Ok(Ok(Some(refreshed))) if refreshed != status => {
+                status = refreshed;
+                on_change(status.clone());
+            }
+            Ok(Err(e)) => eprintln!("Failed to refresh status of {}: {}", dirs.root.display(), e),
+            _ => {}
+        }
+    }
+}
+
+/// The new status after `paths` changed, or `None` if nothing relevant did
+fn refresh(dirs: &WatchedDirs, previous: &GitStatus, paths: &[PathBuf]) -> Result<Option<GitStatus>, AppError> {
+    let mut git_state = false;
+    let mut changed = Vec::new();
+    for path in paths {
+        match classify(path, dirs) {
+            Change::GitState => git_state = true,
+            Change::Worktree(path) if !changed.contains(&path) => changed.push(path),
+            _ => {}
+        }
+    }
+    if git_state {
+        return read_status(&dirs.root).map(Some);
+    }
+
+    // Before counting: a recursive watch reports everything a build writes to ignored dirs
+    let changed = not_ignored(&dirs.root, changed)?;
+    if changed.is_empty() {
+        return Ok(None);
+    }
+    if changed.len() > MAX_INCREMENTAL_PATHS {
+        return read_status(&dirs.root).map(Some);
+    }
+
+    let mut args = vec!["--literal-pathspecs"];
+    args.extend(STATUS_ARGS);
+    args.push("--");
+    args.extend(changed.iter().map(String::as_str));
+    let partial = parse_status(&git::run(&dirs.root, &args)?)?;
+    match merge_partial_status(previous, partial, &changed) {
+        Some(status) => Ok(Some(status)),
+        // Renames span paths outside the ones that changed
+        None => read_status(&dirs.root).map(Some),
+    }
+}
+
+fn classify(path: &Path, dirs: &WatchedDirs) -> Change {
+    let in_state = |relative: &Path| {
+        let is_lock = relative.extension().is_some_and(|extension| extension == "lock");
+        let first = relative.components().next().and_then(|first| first.as_os_str().to_str());
+        !is_lock && first.is_some_and(|first| STATE_FILES.contains(&first))
+    };
+
+    if let Ok(relative) = path.strip_prefix(&dirs.git_dir) {
+        return if in_state(relative) { Change::GitState } else { Change::Irrelevant };
+    }
+    if let Ok(relative) = path.strip_prefix(&dirs.common_dir) {
+        let shared = relative.starts_with("refs") || relative == Path::new("packed-refs");
+        return if shared && in_state(relative) { Change::GitState } else { Change::Irrelevant };
+    }
+    match path.strip_prefix(&dirs.root) {
+        // Skipped dirs only show up under a recursive watch
+        Ok(relative) if !relative.as_os_str().is_empty() && !relative.components().any(|part| {
+            part.as_os_str().to_str().is_some_and(|name| SKIPPED_DIRS.contains(&name))
+        }) => {
+            Change::Worktree(relative.to_string_lossy().into_owned())
+        }
+        _ => Change::Irrelevant,
+    }
+}
+
+/// Drop the paths git ignores
+fn not_ignored(root: &Path, paths: Vec<String>) -> Result<Vec<String>, AppError> {
+    if paths.is_empty() {
+        return Ok(paths);
+    }
+    let input: Vec<u8> = paths.iter().flat_map(|path| path.bytes().chain([0])).collect();
+    let options = RunOptions { stdin: Some(input), ..Default::default() };
+    // Exit code 1 just means nothing is ignored
+    let output = git::output(root, &["check-ignore", "-z", "--stdin"], options)?;
+
+    let ignored: Vec<String> = output.stdout
+        .split(|b| *b == 0)
+        .filter(|path| !path.is_empty())
+        .map(|path| String::from_utf8_lossy(path).into_owned())
+        .collect();
+    Ok(paths.into_iter().filter(|path| !ignored.contains(path)).collect())
+}
+
+/// Replace the entries of `paths` (and anything under them) in `previous` with `partial`'s.
+/// `None` if a rename or copy is involved, since its other side may be outside `paths`.
+pub fn merge_partial_status(previous: &GitStatus, partial: GitStatus, paths: &[String]) -> Option<GitStatus> {
+    let covered = |file: &GitFile| {
+        paths.iter().any(|path| file.path == *path || file.path.starts_with(&format!("{}/", path)))
+    };
+    let renamed = |file: &GitFile| file.orig_path.is_some();
+
+    let previous_files = || {
+        previous.staged.iter()
+            .chain(&previous.unstaged)
+            .chain(&previous.untracked)
+            .chain(&previous.conflicted)
+    };
+    let partial_files = partial.staged.iter()
+        .chain(&partial.unstaged)
+        .chain(&partial.untracked)
+        .chain(&partial.conflicted);
+    if previous_files().any(|file| covered(file) && renamed(file)) || partial_files.clone().any(renamed) {
+        return None;
+    }
+
+    let merge = |previous: &[GitFile], partial: Vec<GitFile>| {
+        let mut files: Vec<GitFile> = previous.iter().filter(|file| !covered(file)).cloned().collect();
+        files.extend(partial);
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        files
+    };
+
+    Some(GitStatus {
+        staged: merge(&previous.staged, partial.staged),
+        unstaged: merge(&previous.unstaged, partial.unstaged),
+        untracked: merge(&previous.untracked, partial.untracked),
+        conflicted: merge(&previous.conflicted, partial.conflicted),
+        // Only the index, HEAD or refs change these, and they get a full refresh
+        operation_in_progress: previous.operation_in_progress.clone(),
+        ..partial
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::fixture::TempRepo;
+    use crate::git::status::FileStatus;
+
+    fn file(path: &str, status: FileStatus) -> GitFile {
+        GitFile {
+            path: path.to_string(),
+            status,
+            orig_path: None,
+            similarity: None,
+            submodule: None,
+            conflict: None,
+        }
+    }
+
+    #[test]
+    fn test_merge_partial_status() {
+        let mut previous = parse_status(b"# branch.oid (initial)\0# branch.head main\0").unwrap();
+        previous.unstaged = vec![file("a.txt", FileStatus::Modified), file("dir/b.txt", FileStatus::Modified)];
+        previous.untracked = vec![file("dir/new.txt", FileStatus::Untracked), file("z.txt", FileStatus::Untracked)];
+
+        let mut partial = parse_status(b"# branch.oid (initial)\0# branch.head main\0").unwrap();
+        partial.unstaged = vec![file("dir/c.txt", FileStatus::Deleted)];
+        partial.untracked = vec![file("b.txt", FileStatus::Untracked)];
+
+        let merged = merge_partial_status(&previous, partial.clone(), &["dir".to_string(), "b.txt".to_string()]).unwrap();
+        let paths = |files: &[GitFile]| files.iter().map(|file| file.path.clone()).collect::<Vec<_>>();
+        assert_eq!(paths(&merged.unstaged), vec!["a.txt", "dir/c.txt"]);
+        assert_eq!(paths(&merged.untracked), vec!["b.txt", "z.txt"]);
+
+        partial.staged = vec![GitFile {
+            orig_path: Some("old.txt".to_string()),
+            ..file("b.txt", FileStatus::Renamed)
+        }];
+        assert_eq!(merge_partial_status(&previous, partial, &["b.txt".to_string()]), None);
+    }
+
+    #[test]
+    fn test_classify_paths() {
+        let dirs = WatchedDirs {
+            root: PathBuf::from("/repo"),
+            git_dir: PathBuf::from("/repo/.git"),
+            common_dir: PathBuf::from("/repo/.git"),
+        };
+        assert_eq!(classify(Path::new("/repo/src/main.rs"), &dirs), Change::Worktree("src/main.rs".to_string()));
+        assert_eq!(classify(Path::new("/repo/.git/index"), &dirs), Change::GitState);
+        assert_eq!(classify(Path::new("/repo/.git/index.lock"), &dirs), Change::Irrelevant);
+        assert_eq!(classify(Path::new("/repo/.git/refs/heads/main"), &dirs), Change::GitState);
+        assert_eq!(classify(Path::new("/repo/.git/objects/ab/cdef"), &dirs), Change::Irrelevant);
+        assert_eq!(classify(Path::new("/repo/web/node_modules/x/index.js"), &dirs), Change::Irrelevant);
+
+        let linked = WatchedDirs {
+            root: PathBuf::from("/work/feature"),
+            git_dir: PathBuf::from("/repo/.git/worktrees/feature"),
+            common_dir: PathBuf::from("/repo/.git"),
+        };
+        assert_eq!(classify(Path::new("/repo/.git/worktrees/feature/HEAD"), &linked), Change::GitState);
+        assert_eq!(classify(Path::new("/repo/.git/packed-refs"), &linked), Change::GitState);
+        // The main worktree's own index doesn't affect this one
+        assert_eq!(classify(Path::new("/repo/.git/index"), &linked), Change::Irrelevant);
+        assert_eq!(classify(Path::new("/work/feature/.git"), &linked), Change::Irrelevant);
+    }
+
+    #[test]
+    fn test_watchable_dirs_skip_ignored_and_heavy() {
+        let repo = TempRepo::new();
+        repo.write(".gitignore", "dist/\n");
+        repo.write("src/app/main.rs", "");
+        repo.write("dist/bundle/out.js", "");
+        repo.write("node_modules/left-pad/index.js", "");
+        repo.write("crates/core/target/debug/build.log", "");
+
+        let ignored = ignored_dirs(repo.path()).unwrap();
+        let mut found: Vec<String> = watchable_dirs(repo.path(), &ignored)
+            .iter()
+            .map(|dir| dir.strip_prefix(repo.path()).unwrap().to_string_lossy().into_owned())
+            .collect();
+        found.sort();
+        assert_eq!(found, vec!["", "crates", "crates/core", "src", "src/app"]);
+    }
+
+    #[tokio::test]
+    async fn test_partial_watch_falls_back_to_polling() {
+        let repo = TempRepo::new();
+        repo.write("a.txt", "a\n");
+        repo.commit_all("initial");
+        let dirs = watched_dirs(repo.path()).unwrap();
+
+        let (events_tx, events_rx) = mpsc::unbounded_channel();
+        let mut tree = start_watcher(&dirs, events_tx.clone()).unwrap();
+        assert!(tree.complete);
+        // Stands in for running out of watches
+        tree.watch(&repo.path().join("missing"), RecursiveMode::NonRecursive);
+        assert!(!tree.complete);
+        // Nothing reports events any more, only polling can notice the change
+        tree.watcher = notify::recommended_watcher(|_: notify::Result<notify::Event>| {}).unwrap();
+
+        let (changes_tx, mut changes_rx) = mpsc::unbounded_channel();
+        let status = read_status(&dirs.root).unwrap();
+        let tree = Arc::new(Mutex::new(tree));
+        let task = tokio::spawn(watch_loop(dirs, tree, events_rx, status, move |status| {
+            let _ = changes_tx.send(status);
+        }));
+        repo.write("b.txt", "b\n");
+        assert_eq!(next(&mut changes_rx).await.untracked[0].path, "b.txt");
+        task.abort();
+        drop(events_tx);
+    }
+
+    async fn next(changes_rx: &mut mpsc::UnboundedReceiver<GitStatus>) -> GitStatus {
+        tokio::time::timeout(Duration::from_secs(10), changes_rx.recv())
+            .await
+            .expect("no status change reported")
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_watch_reports_changes() {
+        let repo = TempRepo::new();
+        repo.write(".gitignore", "build/\n");
+        repo.write("a.txt", "a\n");
+        repo.commit_all("initial");
+
+        let watchers = StatusWatchers::default();
+        let (changes_tx, mut changes_rx) = mpsc::unbounded_channel();
+        let initial = watchers.watch(repo.path(), move |status| {
+            let _ = changes_tx.send(status);
+        })
+        .await
+        .unwrap();
+        assert!(initial.untracked.is_empty());
+
+        // Ignored files alone don't produce an event; the next one reflects both writes
+        repo.write("build/out.o", "binary\n");
+        repo.write("notes.txt", "new\n");
+        let status = next(&mut changes_rx).await;
+        let untracked: Vec<_> = status.untracked.iter().map(|file| file.path.as_str()).collect();
+        assert_eq!(untracked, vec!["notes.txt"]);
+
+        repo.git(&["add", "notes.txt"]);
+        let status = next(&mut changes_rx).await;
+        assert_eq!(status.staged[0].path, "notes.txt");
+
+        // A directory created after the watch started gets watched too
+        repo.write("src/deep/new.rs", "");
+        let status = next(&mut changes_rx).await;
+        assert_eq!(status.untracked[0].path, "src/deep/new.rs");
+        repo.write("src/deep/other.rs", "");
+        let status = next(&mut changes_rx).await;
+        assert_eq!(status.untracked.len(), 2);
+
+        assert!(watchers.unwatch(repo.path()));
+        assert!(!watchers.unwatch(repo.path()));
+    }
+}
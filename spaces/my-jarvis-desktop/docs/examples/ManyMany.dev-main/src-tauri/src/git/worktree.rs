@@ -0,0 +1,98 @@
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppError;
+use crate::git;
+
+/// A worktree of a repository as git records it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorktreeInfo {
+    pub path: String,
+    /// `None` before the first commit
+    pub head: Option<String>,
+    /// Checked out branch; `None` when detached
+    pub branch: Option<String>,
+    /// The repository's own checkout rather than one added with `git worktree add`
+    pub is_main: bool,
+    pub bare: bool,
+    pub locked: bool,
+    /// Its directory is gone and `git worktree prune` would remove it
+    pub prunable: bool,
+}
+
+/// The main worktree first, then linked ones by path
+pub fn list_worktrees(repo: &Path) -> Result<Vec<WorktreeInfo>, AppError> {
+    let output = git::run(repo, &["worktree", "list", "--porcelain", "-z"])
+        .map_err(|e| e.context("Failed to list worktrees"))?;
+    let mut worktrees = parse_worktrees(&String::from_utf8_lossy(&output));
+    sort_worktrees(&mut worktrees);
+    Ok(worktrees)
+}
+
+pub fn sort_worktrees(worktrees: &mut [WorktreeInfo]) {
+    worktrees.sort_by(|a, b| b.is_main.cmp(&a.is_main).then_with(|| a.path.cmp(&b.path)));
+}
+
+/// Parse `git worktree list --porcelain -z`: NUL-terminated attributes, an empty one ending each worktree
+fn parse_worktrees(output: &str) -> Vec<WorktreeInfo> {
+    let mut worktrees: Vec<WorktreeInfo> = Vec::new();
+    let mut starts_worktree = true;
+
+    for attribute in output.split('\0') {
+        if attribute.is_empty() {
+            starts_worktree = true;
+            continue;
+        }
+        let (key, value) = attribute.split_once(' ').unwrap_or((attribute, ""));
+        if key == "worktree" {
+            worktrees.push(WorktreeInfo {
+                path: value.to_string(),
+                head: None,
+                branch: None,
+                // Git always lists the main worktree first
+                is_main: worktrees.is_empty(),
+                bare: false,
+                locked: false,
+                prunable: false,
+            });
+            starts_worktree = false;
+            continue;
+        }
+        let worktree = match worktrees.last_mut() {
+            Some(worktree) if !starts_worktree => worktree,
+            _ => continue,
+        };
+        match key {
+            "HEAD" if value.chars().any(|c| c != '0') => worktree.head = Some(value.to_string()),
+            "branch" => worktree.branch = Some(value.trim_start_matches("refs/heads/").to_string()),
+            "bare" => worktree.bare = true,
+            "locked" => worktree.locked = true,
+            "prunable" => worktree.prunable = true,
+            _ => {}
+        }
+    }
+
+    worktrees
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_worktrees() {
+        let output = "worktree /repo\0HEAD 1111111111111111111111111111111111111111\0branch refs/heads/main\0\0\
+            worktree /work/fix\0HEAD 2222222222222222222222222222222222222222\0detached\0locked reason\0\0\
+            worktree /work/gone\0HEAD 0000000000000000000000000000000000000000\0branch refs/heads/feature/x\0prunable gitdir file points to non-existent location\0\0";
+        let worktrees = parse_worktrees(output);
+
+        assert_eq!(worktrees.len(), 3);
+        assert!(worktrees[0].is_main);
+        assert_eq!(worktrees[0].branch.as_deref(), Some("main"));
+        assert_eq!(worktrees[1].branch, None);
+        assert!(worktrees[1].locked && !worktrees[1].is_main);
+        assert_eq!(worktrees[2].head, None);
+        assert_eq!(worktrees[2].branch.as_deref(), Some("feature/x"));
+        assert!(worktrees[2].prunable);
+    }
+}
@@ -1,22 +1,20 @@
 use std::path::Path;
-use std::process::Command;
+
+use crate::error::{AppError, ErrorKind};
+use crate::process::{self, RunOptions};
 
 #[tauri::command]
-pub fn is_git_repository(path: String) -> Result<bool, String> {
+pub async fn is_git_repository(path: String) -> Result<bool, AppError> {
     let path = Path::new(&path);
     
     if !path.exists() {
         return Ok(false);
     }
     
-    let output = Command::new("git")
-        .arg("-C")
-        .arg(path)
-        .arg("rev-parse")
-        .arg("--is-inside-work-tree")
-        .output()
-        .map_err(|e| e.to_string())?;
-    
-    Ok(output.status.success())
+    match process::git(path, &["rev-parse", "--is-inside-work-tree"], RunOptions::default()).await {
+        Ok(output) => Ok(output.status.success()),
+        Err(e) if e.kind == ErrorKind::GitNotFound => Err(e),
+        Err(_) => Ok(false),
+    }
 }
 
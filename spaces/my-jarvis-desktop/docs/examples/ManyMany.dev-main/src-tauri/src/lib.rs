@@ -1,14 +1,26 @@
 mod commands;
+mod error;
+mod git;
 mod git_commands;
+mod process;
+mod secrets;
 mod terminal;
 
 use commands::{
     project::{add_project, list_projects, remove_project, get_default_branch, parse_workspace_file, open_in_app},
     worktree::{create_worktree, list_worktrees, remove_worktree, get_available_branches},
-    git::{get_git_status, git_commit, git_stage_file, git_unstage_file},
+    git::{get_git_status, get_default_branch_divergence, get_git_backend, set_git_backend, watch_worktree, unwatch_worktree, get_file_diff, get_worktree_diff, get_commit_log, get_commit_details, get_blame, get_file_at_revision, stage_hunks, unstage_hunks, discard_hunks, discard_file_changes, delete_untracked, list_snapshots, restore_snapshot, git_commit, transfer_changes, compare_worktrees, git_merge, git_rebase, git_cherry_pick, git_continue_operation, git_skip_operation, git_abort_operation, get_conflict, resolve_conflict, git_stage_file, git_unstage_file},
+    remote::{git_fetch, git_pull, git_push, git_push_tag, cancel_git_operation},
+    stash::{list_stashes, push_stash, apply_stash, pop_stash, drop_stash, get_stash_diff},
+    tag::{list_tags, create_tag, delete_tag, suggest_next_tag, get_changelog},
+    branch::{list_branches, create_branch, rename_branch, delete_branch, set_branch_upstream, compare_branch_to_default},
     terminal::{open_editor, create_terminal, write_to_terminal, read_from_terminal, resize_terminal, close_terminal, list_terminals, terminal_input, get_terminal_info, cleanup_terminals},
+    environment::{get_effective_env, trust_env_file, deny_env_file, forget_env_file, refresh_environment, get_toolchain_report, list_env_profiles, save_env_profile, delete_env_profile, set_worktree_profile, get_worktree_profile, list_secrets, set_secret, delete_secret},
 };
+use commands::environment::update_environment;
 use git_commands::{is_git_repository};
+use git::remote::GitOperations;
+use git::watch::StatusWatchers;
 use terminal::TerminalManager;
 use std::sync::Mutex;
 
@@ -22,6 +34,18 @@ pub fn run() {
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_updater::Builder::new().build())
         .manage(Mutex::new(terminal_manager))
+        .manage(GitOperations::default())
+        .manage(StatusWatchers::default())
+        .setup(|app| {
+            // Full environment detection spawns a login shell - keep it off the startup path
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = update_environment(&handle, false).await {
+                    eprintln!("Failed to detect environment: {}", e);
+                }
+            });
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             add_project,
             list_projects,
@@ -31,9 +55,59 @@ pub fn run() {
             remove_worktree,
             get_available_branches,
             get_git_status,
+            get_default_branch_divergence,
+            get_git_backend,
+            set_git_backend,
+            watch_worktree,
+            unwatch_worktree,
+            get_file_diff,
+            get_worktree_diff,
+            get_commit_log,
+            get_commit_details,
+            get_blame,
+            get_file_at_revision,
+            stage_hunks,
+            unstage_hunks,
+            discard_hunks,
+            discard_file_changes,
+            delete_untracked,
+            list_snapshots,
+            restore_snapshot,
             git_commit,
+            transfer_changes,
+            compare_worktrees,
+            git_merge,
+            git_rebase,
+            git_cherry_pick,
+            git_continue_operation,
+            git_skip_operation,
+            git_abort_operation,
+            get_conflict,
+            resolve_conflict,
             git_stage_file,
             git_unstage_file,
+            git_fetch,
+            git_pull,
+            git_push,
+            git_push_tag,
+            cancel_git_operation,
+            list_stashes,
+            push_stash,
+            apply_stash,
+            pop_stash,
+            drop_stash,
+            get_stash_diff,
+            list_branches,
+            create_branch,
+            rename_branch,
+            delete_branch,
+            set_branch_upstream,
+            compare_branch_to_default,
+            list_tags,
+            create_tag,
+            delete_tag,
+            suggest_next_tag,
+            get_changelog,
             open_editor,
             create_terminal,
             terminal_input,
@@ -44,6 +118,20 @@ pub fn run() {
             list_terminals,
             get_terminal_info,
             cleanup_terminals,
+            get_effective_env,
+            trust_env_file,
+            deny_env_file,
+            forget_env_file,
+            refresh_environment,
+            get_toolchain_report,
+            list_env_profiles,
+            save_env_profile,
+            delete_env_profile,
+            set_worktree_profile,
+            get_worktree_profile,
+            list_secrets,
+            set_secret,
+            delete_secret,
             is_git_repository,
             get_default_branch,
             parse_workspace_file,
@@ -0,0 +1,285 @@
+//! Runs git and other subprocesses on tokio, so a hung or slow process never ties up the
+//! executor. Every call can time out or be cancelled, and git calls share a per-repository limit.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Output, Stdio};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::runtime::{Handle, Runtime, RuntimeFlavor};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio_util::sync::CancellationToken;
+
+use crate::error::{AppError, ErrorKind};
+use crate::git;
+
+/// Long enough for hooks and big repositories, short enough that a stuck git gives up eventually
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// How many git processes may run at once in one repository, across all its worktrees
+const MAX_PER_REPO: usize = 4;
+
+static REPO_LIMITS: LazyLock<Mutex<HashMap<PathBuf, Arc<Semaphore>>>> = LazyLock::new(Default::default);
+
+/// Drives processes for synchronous callers that aren't on a multi-threaded runtime, e.g. tests
+static FALLBACK: LazyLock<Runtime> = LazyLock::new(|| {
+    tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(1)
+        .thread_name("manymany-process")
+        .enable_all()
+        .build()
+        .expect("Failed to start process runtime")
+});
+
+#[derive(Debug, Clone)]
+pub struct RunOptions {
+    /// Kill the process after this long; `None` waits for it however long it takes
+    pub timeout: Option<Duration>,
+    pub cancel: Option<CancellationToken>,
+    /// Written to the process's stdin, which is closed afterwards
+    pub stdin: Option<Vec<u8>>,
+    pub env: Vec<(String, String)>,
+    /// Start from an empty environment instead of inheriting this process's, so only `env` is set
+    pub clear_env: bool,
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        RunOptions {
+            timeout: Some(DEFAULT_TIMEOUT),
+            cancel: None,
+            stdin: None,
+            env: Vec::new(),
+            clear_env: false,
+        }
+    }
+}
+
+/// `git -C <repo>` with prompts disabled and messages in English, for any caller that spawns git itself
+pub fn git_command(repo: &Path) -> Command {
+    let mut command = Command::new("git");
+    command
+        .arg("-C")
+        .arg(repo)
+        // Fail instead of waiting for a password prompt nobody can answer
+        .env("GIT_TERMINAL_PROMPT", "0")
+        // Output and error messages are parsed, so keep them untranslated
+        .env("LC_ALL", "C");
+    command
+}
+
+/// Run git in `repo` and return its output whatever the exit code
+pub async fn git(repo: &Path, args: &[&str], options: RunOptions) -> Result<Output, AppError> {
+    let _permit = repo_permit(repo).await;
+    let mut command = git_command(repo);
+    command.args(args);
+    let subcommand = args.iter().find(|arg| !arg.starts_with('-') && !arg.contains('=')).unwrap_or(&"");
+    run_command(command, &format!("git {}", subcommand), options, git::spawn_error).await
+}
+
+/// Run any program and return its output whatever the exit code
+pub async fn run(program: &str, args: &[&str], cwd: Option<&Path>, options: RunOptions) -> Result<Output, AppError> {
+    let mut command = Command::new(program);
+    command.args(args);
+    if let Some(cwd) = cwd {
+        command.current_dir(cwd);
+    }
+    run_command(command, program, options, |e| AppError::io(format!("Failed to run {}", program), e)).await
+}
+
+async fn run_command(
+    mut command: Command,
+    description: &str,
+    options: RunOptions,
+    spawn_error: impl FnOnce(io::Error) -> AppError,
+) -> Result<Output, AppError> {
+    if options.clear_env {
+        command.env_clear();
+    }
+    let mut child = command
+        .envs(options.env)
+        .stdin(if options.stdin.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(spawn_error)?;
+
+    let stdin = child.stdin.take();
+    let input = options.stdin.unwrap_or_default();
+    let finished = async move {
+        let write = async {
+            match stdin {
+                // Dropping stdin afterwards closes it
+                Some(mut stdin) => stdin.write_all(&input).await,
+                None => Ok(()),
+            }
+        };
+        let (written, output) = tokio::join!(write, child.wait_with_output());
+        written.map_err(|e| AppError::io(format!("Failed to write to {}", description), e))?;
+        output.map_err(|e| AppError::io(format!("Failed to run {}", description), e))
+    };
+    let timed_out = async {
+        match options.timeout {
+            Some(timeout) => tokio::time::sleep(timeout).await,
+            None => std::future::pending().await,
+        }
+    };
+    let cancelled = async {
+        match &options.cancel {
+            Some(cancel) => cancel.cancelled().await,
+            None => std::future::pending().await,
+        }
+    };
+
+    // Returning early drops the child, which kills it
+    tokio::select! {
+        output = finished => output,
+        _ = timed_out => {
+            let timeout = options.timeout.unwrap_or_default();
+            Err(AppError::new(
+                ErrorKind::TimedOut { command: description.to_string(), timeout_ms: timeout.as_millis() as u64 },
+                format!("{} timed out after {:?}", description, timeout),
+            ))
+        }
+        _ = cancelled => Err(AppError::cancelled(description)),
+    }
+}
+
+/// Wait for a free slot in the repository `repo` belongs to
+pub async fn repo_permit(repo: &Path) -> OwnedSemaphorePermit {
+    let semaphore = {
+        let mut limits = REPO_LIMITS.lock().unwrap();
+        // Holders and waiters keep a clone, so a semaphore only the map holds is idle
+        limits.retain(|_, semaphore| Arc::strong_count(semaphore) > 1);
+        limits
+            .entry(repo_key(repo))
+            .or_insert_with(|| Arc::new(Semaphore::new(MAX_PER_REPO)))
+            .clone()
+    };
+    semaphore.acquire_owned().await.expect("Repository semaphores are never closed")
+}
+
+/// The git directory shared by all worktrees of `repo`, found on disk without running git
+fn repo_key(repo: &Path) -> PathBuf {
+    let repo = std::fs::canonicalize(repo).unwrap_or_else(|_| repo.to_path_buf());
+    for dir in repo.ancestors() {
+        let dot_git = dir.join(".git");
+        if dot_git.is_dir() {
+            return dot_git;
+        }
+        // Linked worktrees have a `.git` file: `gitdir: <common dir>/worktrees/<name>`
+        if let Ok(content) = std::fs::read_to_string(&dot_git) {
+            if let Some(git_dir) = content.trim().strip_prefix("gitdir: ") {
+                let git_dir = std::fs::canonicalize(dir.join(git_dir)).unwrap_or_else(|_| PathBuf::from(git_dir));
+                return match git_dir.parent() {
+                    Some(worktrees) if worktrees.ends_with("worktrees") => {
+                        worktrees.parent().unwrap_or(worktrees).to_path_buf()
+                    }
+                    _ => git_dir,
+                };
+            }
+        }
+    }
+    repo
+}
+
+/// Run synchronous work - libgit2 calls, file walks, chains of git calls - on the blocking pool,
+/// so it never holds up an executor worker
+pub async fn blocking<T: Send + 'static>(work: impl FnOnce() -> Result<T, AppError> + Send + 'static) -> Result<T, AppError> {
+    tokio::task::spawn_blocking(work)
+        .await
+        .map_err(|e| AppError::other(format!("Background task failed: {}", e)))?
+}
+
+/// Run `future` to completion from synchronous code, which may itself be running on the runtime
+pub fn block_on<F>(future: F) -> F::Output
+where
+    F: Future + Send,
+    F::Output: Send,
+{
+    match Handle::try_current() {
+        // Hand this worker's other tasks off while waiting, so the executor keeps running
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+            tokio::task::block_in_place(|| handle.block_on(future))
+        }
+        // A current-thread runtime can't be blocked from its own thread
+        Ok(_) => std::thread::scope(|scope| {
+            scope.spawn(|| FALLBACK.block_on(future)).join().expect("Process thread panicked")
+        }),
+        Err(_) => FALLBACK.block_on(future),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::fixture::TempRepo;
+    use std::time::Instant;
+
+    #[tokio::test]
+    async fn test_timeout_and_cancel_kill_the_process() {
+        let started = Instant::now();
+        let options = RunOptions { timeout: Some(Duration::from_millis(100)), ..Default::default() };
+        let error = run("sleep", &["5"], None, options).await.unwrap_err();
+        assert!(matches!(error.kind, ErrorKind::TimedOut { timeout_ms: 100, .. }), "{}", error);
+
+        let cancel = CancellationToken::new();
+        let options = RunOptions { cancel: Some(cancel.clone()), ..Default::default() };
+        let (result, _) = tokio::join!(run("sleep", &["5"], None, options), async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            cancel.cancel();
+        });
+        assert!(matches!(result.unwrap_err().kind, ErrorKind::Cancelled { .. }));
+        assert!(started.elapsed() < Duration::from_secs(3));
+    }
+
+    #[tokio::test]
+    async fn test_git_environment_and_stdin() {
+        let repo = TempRepo::new();
+        let output = git(repo.path(), &["-c", "alias.env=!echo $LC_ALL $GIT_TERMINAL_PROMPT", "env"], RunOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "C 0");
+
+        let options = RunOptions { stdin: Some(b"hello\n".to_vec()), ..Default::default() };
+        let output = git(repo.path(), &["hash-object", "--stdin"], options).await.unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "ce013625030ba8dba906f756967f9e9ca394464a");
+    }
+
+    #[tokio::test]
+    async fn test_worktrees_share_a_limit() {
+        let repo = TempRepo::new();
+        repo.write("a.txt", "a\n");
+        repo.commit_all("initial");
+        let linked = repo.add_worktree("feature");
+        std::fs::create_dir_all(linked.path().join("sub")).unwrap();
+
+        let key = repo_key(repo.path());
+        assert!(key.ends_with(".git"));
+        assert_eq!(repo_key(&linked.path().join("sub")), key);
+
+        let mut permits = Vec::new();
+        for i in 0..MAX_PER_REPO {
+            let path = if i % 2 == 0 { repo.path() } else { linked.path() };
+            permits.push(repo_permit(path).await);
+        }
+        let waiting = tokio::time::timeout(Duration::from_millis(100), repo_permit(linked.path())).await;
+        assert!(waiting.is_err(), "a permit was handed out beyond the limit");
+
+        permits.pop();
+        let permit = tokio::time::timeout(Duration::from_secs(5), repo_permit(linked.path())).await;
+        assert!(permit.is_ok());
+
+        // Once nothing holds or waits on it, the next acquire drops the entry
+        drop(permit);
+        drop(permits);
+        let other = TempRepo::new();
+        let _other = repo_permit(other.path()).await;
+        assert!(!REPO_LIMITS.lock().unwrap().contains_key(&key));
+    }
+}
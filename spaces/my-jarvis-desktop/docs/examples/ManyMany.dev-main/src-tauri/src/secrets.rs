@@ -0,0 +1,345 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AppError, ErrorKind};
+
+/// Keychain service every vault entry is stored under
+const KEYCHAIN_SERVICE: &str = "dev.manymany.secrets";
+
+/// Held across each read-modify-write of an index, so concurrent sets and deletes don't drop
+/// each other's entries
+static INDEX_LOCK: Mutex<()> = Mutex::new(());
+
+/// A secret value. Deliberately not `Serialize`, and `Debug` never prints it.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SecretValue(String);
+
+impl SecretValue {
+    pub fn new(value: impl Into<String>) -> Self {
+        Self(value.into())
+    }
+
+    /// The plain value - only for handing to a spawned process
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for SecretValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[REDACTED]")
+    }
+}
+
+/// Where secret values physically live
+pub trait SecretBackend: Send + Sync {
+    fn get(&self, name: &str) -> Result<Option<SecretValue>, AppError>;
+    fn set(&self, name: &str, value: &SecretValue) -> Result<(), AppError>;
+    fn delete(&self, name: &str) -> Result<(), AppError>;
+}
+
+/// macOS Keychain, Windows Credential Manager or Secret Service on Linux
+pub struct KeychainBackend;
+
+impl KeychainBackend {
+    fn entry(name: &str) -> Result<keyring::Entry, AppError> {
+        keyring::Entry::new(KEYCHAIN_SERVICE, name)
+            .map_err(|e| AppError::other(format!("Failed to open keychain entry: {}", e)))
+    }
+}
+
+impl SecretBackend for KeychainBackend {
+    fn get(&self, name: &str) -> Result<Option<SecretValue>, AppError> {
+        match Self::entry(name)?.get_password() {
+            Ok(value) => Ok(Some(SecretValue(value))),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(AppError::other(format!("Failed to read secret '{}': {}", name, e))),
+        }
+    }
+
+    fn set(&self, name: &str, value: &SecretValue) -> Result<(), AppError> {
+        Self::entry(name)?
+            .set_password(value.expose())
+            .map_err(|e| AppError::other(format!("Failed to store secret '{}': {}", name, e)))
+    }
+
+    fn delete(&self, name: &str) -> Result<(), AppError> {
+        match Self::entry(name)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(AppError::other(format!("Failed to delete secret '{}': {}", name, e))),
+        }
+    }
+}
+
+/// What the frontend gets to see about a secret
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SecretInfo {
+    pub name: String,
+    pub description: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Named secrets. Values go to the backend; names and metadata are kept in an index
+/// because keychains can't enumerate entries portably.
+pub struct SecretVault {
+    backend: Box<dyn SecretBackend>,
+    index_path: PathBuf,
+}
+
+impl SecretVault {
+    /// The vault backed by the platform keychain
+    pub fn open() -> Result<Self, AppError> {
+        let home_dir = dirs::home_dir()
+            .ok_or_else(|| AppError::other("Could not find home directory"))?;
+        Ok(Self::with_backend(
+            Box::new(KeychainBackend),
+            home_dir.join(".manymany").join("secrets.json"),
+        ))
+    }
+
+    pub fn with_backend(backend: Box<dyn SecretBackend>, index_path: impl Into<PathBuf>) -> Self {
+        Self {
+            backend,
+            index_path: index_path.into(),
+        }
+    }
+
+    fn read_index(&self) -> Result<BTreeMap<String, SecretInfo>, AppError> {
+        match fs::read_to_string(&self.index_path) {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|e| AppError::other(format!("Failed to parse secrets index: {}", e))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+            Err(e) => Err(AppError::io("Failed to read secrets index", e)),
+        }
+    }
+
+    fn write_index(&self, index: &BTreeMap<String, SecretInfo>) -> Result<(), AppError> {
+        let content = serde_json::to_string_pretty(index)
+            .map_err(|e| AppError::other(format!("Failed to serialize secrets index: {}", e)))?;
+        write_private(&self.index_path, &content)
+    }
+
+    pub fn list(&self) -> Result<Vec<SecretInfo>, AppError> {
+        Ok(self.read_index()?.into_values().collect())
+    }
+
+    pub fn get(&self, name: &str) -> Result<SecretValue, AppError> {
+        self.backend
+            .get(name)?
+            .ok_or_else(|| AppError::new(
+                ErrorKind::SecretNotFound { name: name.to_string() },
+                format!("Secret not found: {}", name),
+            ))
+    }
+
+    pub fn set(&self, name: &str, value: SecretValue, description: Option<String>) -> Result<SecretInfo, AppError> {
+        validate_name(name)?;
+        self.backend.set(name, &value)?;
+
+        let _guard = INDEX_LOCK.lock().unwrap();
+        let mut index = self.read_index()?;
+        let now = Utc::now().to_rfc3339();
+        let info = match index.remove(name) {
+            Some(existing) => SecretInfo {
+                description: description.or(existing.description),
+                updated_at: now,
+                ..existing
+            },
+            None => SecretInfo {
+                name: name.to_string(),
+                description,
+                created_at: now.clone(),
+                updated_at: now,
+            },
+        };
+        index.insert(name.to_string(), info.clone());
+        self.write_index(&index)?;
+        Ok(info)
+    }
+
+    pub fn delete(&self, name: &str) -> Result<(), AppError> {
+        self.backend.delete(name)?;
+        let _guard = INDEX_LOCK.lock().unwrap();
+        let mut index = self.read_index()?;
+        if index.remove(name).is_some() {
+            self.write_index(&index)?;
+        }
+        Ok(())
+    }
+}
+
+fn validate_name(name: &str) -> Result<(), AppError> {
+    let valid = !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(AppError::invalid(format!("Invalid secret name '{}': use letters, digits, '_', '-' or '.'", name)))
+    }
+}
+
+/// Plain JSON file standing in for the keychain in tests
+#[cfg(test)]
+pub struct FileBackend {
+    path: PathBuf,
+}
+
+#[cfg(test)]
+impl FileBackend {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    fn read(&self) -> Result<BTreeMap<String, String>, AppError> {
+        match fs::read_to_string(&self.path) {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|e| AppError::other(format!("Failed to parse {}: {}", self.path.display(), e))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+            Err(e) => Err(AppError::io(format!("Failed to read {}", self.path.display()), e)),
+        }
+    }
+
+    fn write(&self, values: &BTreeMap<String, String>) -> Result<(), AppError> {
+        let content = serde_json::to_string_pretty(values)
+            .map_err(|e| AppError::other(format!("Failed to serialize secrets: {}", e)))?;
+        write_private(&self.path, &content)
+    }
+}
+
+#[cfg(test)]
+impl SecretBackend for FileBackend {
+    fn get(&self, name: &str) -> Result<Option<SecretValue>, AppError> {
+        Ok(self.read()?.remove(name).map(SecretValue))
+    }
+
+    fn set(&self, name: &str, value: &SecretValue) -> Result<(), AppError> {
+        let mut values = self.read()?;
+        values.insert(name.to_string(), value.expose().to_string());
+        self.write(&values)
+    }
+
+    fn delete(&self, name: &str) -> Result<(), AppError> {
+        let mut values = self.read()?;
+        if values.remove(name).is_some() {
+            self.write(&values)?;
+        }
+        Ok(())
+    }
+}
+
+/// Variable names that usually hold credentials - their values stay out of logs and the UI
+pub fn looks_sensitive(key: &str) -> bool {
+    const SUFFIXES: &[&str] = &["_API_KEY", "_TOKEN", "_SECRET", "_AUTH", "_PASSWORD", "_CREDENTIALS"];
+    SUFFIXES.iter().any(|suffix| key.ends_with(suffix))
+}
+
+/// Write `content` to `path` readable only by the user. The file is created private and
+/// renamed into place, so there is no moment where others can read it.
+pub fn write_private(path: &Path, content: &str) -> Result<(), AppError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| AppError::io(format!("Failed to create {}", parent.display()), e))?;
+    }
+
+    // Unique per write, so concurrent writers never share or delete each other's temp file.
+    // `create_new` makes sure the mode below applies, since it only does on creation.
+    let temp = path.with_extension(format!("tmp-{}-{}", std::process::id(), uuid::Uuid::new_v4()));
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let written = options.open(&temp).and_then(|mut file| {
+        file.write_all(content.as_bytes())?;
+        file.sync_all()
+    });
+    if let Err(e) = written.and_then(|_| fs::rename(&temp, path)) {
+        let _ = fs::remove_file(&temp);
+        return Err(AppError::io(format!("Failed to write {}", path.display()), e));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::fixture::TempRepo;
+
+    #[test]
+    fn test_file_backed_vault() {
+        let temp = TempRepo::dir();
+        let dir = temp.path();
+        let vault = SecretVault::with_backend(
+            Box::new(FileBackend::new(dir.join("values.json"))),
+            dir.join("index.json"),
+        );
+
+        assert!(vault.list().unwrap().is_empty());
+        assert_eq!(vault.set("bad name", SecretValue::new("x"), None).unwrap_err().kind, ErrorKind::InvalidInput);
+
+        vault.set("kimi-token", SecretValue::new("sk-123"), Some("Moonshot".to_string())).unwrap();
+        vault.set("kimi-token", SecretValue::new("sk-456"), None).unwrap();
+        assert_eq!(vault.get("kimi-token").unwrap().expose(), "sk-456");
+
+        let listed = vault.list().unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].description.as_deref(), Some("Moonshot"));
+        // The index only ever holds names and metadata
+        let index = fs::read_to_string(dir.join("index.json")).unwrap();
+        assert!(!index.contains("sk-456"));
+
+        vault.delete("kimi-token").unwrap();
+        assert!(matches!(vault.get("kimi-token").unwrap_err().kind, ErrorKind::SecretNotFound { .. }));
+        assert!(vault.list().unwrap().is_empty());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(dir.join("values.json")).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn test_concurrent_writes_keep_every_entry() {
+        let temp = TempRepo::dir();
+        let dir = temp.path();
+        // One vault per thread, like commands that each open their own
+        std::thread::scope(|scope| {
+            for i in 0..8 {
+                scope.spawn(move || {
+                    let vault = SecretVault::with_backend(
+                        Box::new(FileBackend::new(dir.join(format!("values-{}.json", i)))),
+                        dir.join("index.json"),
+                    );
+                    vault.set(&format!("secret-{}", i), SecretValue::new("x"), None).unwrap();
+                });
+            }
+        });
+
+        let vault = SecretVault::with_backend(Box::new(FileBackend::new(dir.join("values.json"))), dir.join("index.json"));
+        assert_eq!(vault.list().unwrap().len(), 8);
+        let leftovers: Vec<_> = fs::read_dir(dir).unwrap()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name().to_string_lossy().contains(".tmp-"))
+            .collect();
+        assert!(leftovers.is_empty());
+    }
+
+    #[test]
+    fn test_secret_value_is_redacted() {
+        let value = SecretValue::new("sk-live-abc");
+        assert_eq!(format!("{:?}", value), "[REDACTED]");
+        assert!(!format!("{:?}", Some(value)).contains("sk-live"));
+    }
+}
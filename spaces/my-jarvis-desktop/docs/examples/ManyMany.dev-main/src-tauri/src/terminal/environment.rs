@@ -2,23 +2,164 @@ use std::collections::HashMap;
 use std::env;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
 use dirs;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone)]
+use crate::error::AppError;
+use crate::process::{self, RunOptions};
+use crate::secrets::{looks_sensitive, write_private};
+use crate::terminal::expand::{expand, Expander, Quoting};
+use crate::terminal::toolchain::{detect_version_managers, resolve_version, VersionManager};
+
+/// A login shell sources every rc file; one that hangs shouldn't hang terminal startup with it
+const LOGIN_SHELL_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct EnvironmentInfo {
     pub shell: String,
     pub path_dirs: Vec<String>,
     pub env_vars: HashMap<String, String>,
     pub dev_tools: HashMap<String, String>,
+    /// Version reported by each tool in `dev_tools`
+    #[serde(default)]
+    pub tool_versions: HashMap<String, String>,
+    #[serde(default)]
+    pub version_managers: Vec<VersionManager>,
+}
+
+/// Identifies the inputs a detection ran against: the shell plus every rc file's mtime
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct CacheKey {
+    shell: String,
+    rc_files: Vec<(String, Option<u128>)>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedEnvironment {
+    key: CacheKey,
+    /// Detected environment without the variables named in `redacted`
+    info: EnvironmentInfo,
+    /// Credentials that were left out of the file; [`CachedEnvironment::restore`] puts them back
+    #[serde(default)]
+    redacted: Vec<String>,
+}
+
+impl CachedEnvironment {
+    fn new(key: CacheKey, info: &EnvironmentInfo) -> Self {
+        let mut info = info.clone();
+        let mut redacted: Vec<String> = info.env_vars.keys().filter(|key| looks_sensitive(key)).cloned().collect();
+        redacted.sort();
+        for key in &redacted {
+            info.env_vars.remove(key);
+        }
+        CachedEnvironment { key, info, redacted }
+    }
+
+    /// The cached info with the redacted credentials put back: from this process's environment
+    /// where it has them, otherwise from a single login-shell `env`
+    fn restore(self) -> EnvironmentInfo {
+        let mut info = self.info;
+        let missing = info.fill_vars(&self.redacted, |key| env::var(key).ok());
+        if !missing.is_empty() {
+            if let Some(output) = login_shell_env(&info.shell) {
+                let shell_vars: HashMap<&str, &str> = output.lines().filter_map(|line| line.split_once('=')).collect();
+                info.fill_vars(&missing, |key| shell_vars.get(key).map(|value| value.to_string()));
+            }
+        }
+        info
+    }
+}
+
+/// Summary of what a re-detection changed. Only names are reported, never values.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EnvironmentChange {
+    pub shell: Option<String>,
+    pub added_vars: Vec<String>,
+    pub removed_vars: Vec<String>,
+    pub changed_vars: Vec<String>,
+    pub added_path_dirs: Vec<String>,
+    pub removed_path_dirs: Vec<String>,
+    pub changed_dev_tools: Vec<String>,
+    pub changed_tool_versions: Vec<String>,
+}
+
+impl EnvironmentChange {
+    pub fn is_empty(&self) -> bool {
+        *self == EnvironmentChange::default()
+    }
 }
 
 impl EnvironmentInfo {
+    /// Cheap environment built from this process only - no login shell is spawned.
+    /// Used until a full detection finishes in the background.
+    pub fn from_process() -> Self {
+        let mut env_info = Self::base();
+        env_info.discover_dev_tools();
+        env_info.env_vars.insert("PATH".to_string(), env_info.path_dirs.join(":"));
+        env_info
+    }
+
+    /// Cached detection result, if none of the shell rc files changed since it was taken.
+    /// Credentials aren't cached, so this lacks any the shell exported.
+    pub fn load_cached() -> Option<Self> {
+        read_cache().map(|cached| cached.info)
+    }
+
+    /// Use the on-disk cache when it's still valid, otherwise detect and refresh the cache
+    pub fn detect_cached() -> Self {
+        match read_cache() {
+            Some(cached) => cached.restore(),
+            None => Self::detect_and_cache(),
+        }
+    }
+
+    /// Always run a full detection and store the result for the next startup
+    pub fn detect_and_cache() -> Self {
+        let key = CacheKey::current();
+        let env_info = Self::detect();
+
+        if let Err(e) = write_cache(&CachedEnvironment::new(key, &env_info)) {
+            eprintln!("Failed to write environment cache: {}", e);
+        }
+
+        env_info
+    }
+
     pub fn detect() -> Self {
+        let mut env_info = Self::base();
+        let inherited = env_info.env_vars.clone();
+
+        // Load shell configuration and enhance environment. Only values parsed out of rc
+        // files need expanding - anything the login shell printed is already final.
+        let mut unexpanded = env_info.load_shell_config();
+        env_info.load_shell_environment(&mut unexpanded);
+
+        // Resolve all variable references in collected environment
+        env_info.resolve_all_variables(&unexpanded, &inherited);
+
+        // Detect development tools and their versions
+        env_info.discover_dev_tools();
+        env_info.resolve_tool_versions();
+
+        // Ensure critical authentication variables are preserved and resolved
+        env_info.preserve_authentication_vars();
+
+        // Update PATH in env vars
+        env_info.env_vars.insert("PATH".to_string(), env_info.path_dirs.join(":"));
+
+        env_info
+    }
+
+    /// Process environment plus the common development paths
+    fn base() -> Self {
         let mut env_info = EnvironmentInfo {
             shell: detect_shell(),
             path_dirs: Vec::new(),
             env_vars: HashMap::new(),
             dev_tools: HashMap::new(),
+            tool_versions: HashMap::new(),
+            version_managers: Vec::new(),
         };
 
         // Start with system PATH
@@ -34,29 +175,45 @@ impl EnvironmentInfo {
         // Add common development paths
         env_info.add_common_dev_paths();
 
-        // Load shell configuration and enhance environment
-        env_info.load_shell_config();
-        env_info.load_shell_environment();
-
-        // Debug: Show KIMI-related variables before resolution
-        env_info.debug_kimi_variables("Before resolution");
-
-        // Resolve all variable references in collected environment
-        env_info.resolve_all_variables();
-
-        // Debug: Show KIMI-related variables after resolution
-        env_info.debug_kimi_variables("After resolution");
-
-        // Detect development tools
-        env_info.discover_dev_tools();
+        env_info
+    }
 
-        // Ensure critical authentication variables are preserved and resolved
-        env_info.preserve_authentication_vars();
+    /// Describe how `newer` differs from this environment
+    pub fn diff(&self, newer: &EnvironmentInfo) -> EnvironmentChange {
+        let mut change = EnvironmentChange::default();
 
-        // Update PATH in env vars
-        env_info.env_vars.insert("PATH".to_string(), env_info.path_dirs.join(":"));
+        if self.shell != newer.shell {
+            change.shell = Some(newer.shell.clone());
+        }
 
-        env_info
+        for (key, value) in &newer.env_vars {
+            match self.env_vars.get(key) {
+                None => change.added_vars.push(key.clone()),
+                Some(old) if old != value => change.changed_vars.push(key.clone()),
+                Some(_) => {}
+            }
+        }
+        change.removed_vars = self.env_vars.keys()
+            .filter(|key| !newer.env_vars.contains_key(*key))
+            .cloned()
+            .collect();
+
+        change.added_path_dirs = newer.path_dirs.iter()
+            .filter(|dir| !self.path_dirs.contains(dir))
+            .cloned()
+            .collect();
+        change.removed_path_dirs = self.path_dirs.iter()
+            .filter(|dir| !newer.path_dirs.contains(dir))
+            .cloned()
+            .collect();
+
+        change.changed_dev_tools = changed_keys(&self.dev_tools, &newer.dev_tools);
+        change.changed_tool_versions = changed_keys(&self.tool_versions, &newer.tool_versions);
+
+        change.added_vars.sort();
+        change.removed_vars.sort();
+        change.changed_vars.sort();
+        change
     }
 
     fn add_common_dev_paths(&mut self) {
@@ -110,45 +267,43 @@ impl EnvironmentInfo {
         }
     }
 
-    fn load_shell_config(&mut self) {
+    /// Returns the variables whose values were taken from rc files unexpanded, and how they were quoted
+    fn load_shell_config(&mut self) -> HashMap<String, Quoting> {
         let home_dir = dirs::home_dir().unwrap_or_default();
-        
-        let config_files = match self.shell.as_str() {
-            "zsh" => vec![".zshrc", ".zprofile", ".zshenv"],
-            "bash" => vec![".bashrc", ".bash_profile", ".profile"],
-            "fish" => vec![".config/fish/config.fish"],
-            _ => vec![".profile"],
-        };
+        let mut unexpanded = HashMap::new();
 
-        for config_file in config_files {
+        for config_file in shell_config_files(&self.shell) {
             let config_path = home_dir.join(config_file);
             if let Ok(content) = fs::read_to_string(&config_path) {
-                self.parse_shell_config(&content);
+                self.parse_shell_config(&content, &mut unexpanded);
             }
         }
+
+        unexpanded
     }
 
-    fn load_shell_environment(&mut self) {
+    fn load_shell_environment(&mut self, unexpanded: &mut HashMap<String, Quoting>) {
         // Try to get full environment by running the user's shell with profile loading
-        let shell_command = match self.shell.as_str() {
-            "zsh" => vec!["zsh", "-l", "-c", "env"],
-            "bash" => vec!["bash", "--login", "-c", "env"],
-            "fish" => vec!["fish", "-l", "-c", "env"],
-            _ => vec!["sh", "-l", "-c", "env"],
-        };
+        if let Some(env_output) = login_shell_env(&self.shell) {
+            self.parse_env_output(&env_output, unexpanded);
+        }
+    }
 
-        if let Ok(output) = std::process::Command::new(shell_command[0])
-            .args(&shell_command[1..])
-            .output()
-        {
-            if output.status.success() {
-                let env_output = String::from_utf8_lossy(&output.stdout);
-                self.parse_env_output(&env_output);
+    /// Set each of `keys` that `lookup` has a value for; returns the ones it didn't
+    fn fill_vars(&mut self, keys: &[String], lookup: impl Fn(&str) -> Option<String>) -> Vec<String> {
+        let mut missing = Vec::new();
+        for key in keys {
+            match lookup(key) {
+                Some(value) => {
+                    self.env_vars.insert(key.clone(), value);
+                }
+                None => missing.push(key.clone()),
             }
         }
+        missing
     }
 
-    fn parse_env_output(&mut self, output: &str) {
+    fn parse_env_output(&mut self, output: &str, unexpanded: &mut HashMap<String, Quoting>) {
         for line in output.lines() {
             let line = line.trim();
             if let Some(equals_pos) = line.find('=') {
@@ -158,117 +313,35 @@ impl EnvironmentInfo {
                 // Only override if it's an authentication/development related variable
                 // or if we don't already have it
                 if self.is_important_env_var(key) || !self.env_vars.contains_key(key) {
-                    // Don't resolve here - we'll do it in a separate pass
+                    // Expanded by the shell already, must not be expanded again
                     self.env_vars.insert(key.to_string(), value.to_string());
+                    unexpanded.remove(key);
                 }
             }
         }
     }
 
-    fn resolve_all_variables(&mut self) {
-        println!("🔧 Starting global variable resolution...");
-        
-        // Perform multiple passes of variable resolution until no more changes
-        let mut max_passes = 10; // Prevent infinite loops
-        let mut changed = true;
-        let mut pass_count = 0;
-        
-        while changed && max_passes > 0 {
-            changed = false;
-            max_passes -= 1;
-            pass_count += 1;
-            
-            println!("🔧 Variable resolution pass {}", pass_count);
-            
-            // Collect keys to avoid borrowing issues
-            let keys: Vec<String> = self.env_vars.keys().cloned().collect();
-            
-            for key in keys {
-                if let Some(value) = self.env_vars.get(&key).cloned() {
-                    if value.contains('$') {
-                        println!("🔧   Resolving {}: {} -> ", key, value);
-                        let resolved_value = self.resolve_variable_references(&value);
-                        if resolved_value != value {
-                            println!("🔧   {} -> {}", value, resolved_value);
-                            self.env_vars.insert(key, resolved_value);
-                            changed = true;
-                        } else {
-                            println!("🔧   No change: {}", value);
-                        }
-                    }
-                }
-            }
+    /// Expand the rc-file values in one go; each value is expanded exactly once.
+    /// `inherited` is the environment from before the rc files, which self-references read.
+    fn resolve_all_variables(&mut self, unexpanded: &HashMap<String, Quoting>, inherited: &HashMap<String, String>) {
+        let mut keys: Vec<&String> = unexpanded.keys().collect();
+        keys.sort();
+
+        let mut expander = Expander::new(&self.env_vars, unexpanded, inherited);
+        let resolved: Vec<(String, Option<String>)> = keys
+            .into_iter()
+            .map(|key| (key.clone(), expander.resolve(key)))
+            .collect();
+        for warning in expander.warnings() {
+            println!("⚠️ {}", warning);
         }
-        
-        println!("🔧 Variable resolution completed after {} passes", pass_count);
-    }
 
-    fn resolve_variable_references(&self, value: &str) -> String {
-        let mut result = value.to_string();
-        println!("🔧     Resolving variable references in: '{}'", value);
-        
-        // Handle ${VAR} pattern first
-        while let Some(start) = result.find("${") {
-            if let Some(end) = result[start..].find('}') {
-                let var_name = &result[start + 2..start + end];
-                let full_pattern = &result[start..start + end + 1];
-                
-                println!("🔧     Found ${{{}}}, looking for value...", var_name);
-                
-                if let Some(replacement) = self.env_vars.get(var_name) {
-                    println!("🔧     Found value for {}: '{}'", var_name, replacement);
-                    result = result.replace(full_pattern, replacement);
-                } else {
-                    println!("🔧     No value found for {}", var_name);
-                    // If we can't resolve it, leave it as is and break to avoid infinite loop
-                    break;
-                }
-            } else {
-                break;
-            }
+        for (key, value) in resolved {
+            // Cycles resolve to unset, like a shell referencing a variable not yet defined
+            self.env_vars.insert(key, value.unwrap_or_default());
         }
-        
-        // Handle $VAR pattern (simple variable references)
-        let mut start_pos = 0;
-        while let Some(start) = result[start_pos..].find('$') {
-            let absolute_start = start_pos + start;
-            let remaining = &result[absolute_start + 1..];
-            
-            // Find the end of the variable name
-            let var_end = remaining.find(|c: char| !c.is_alphanumeric() && c != '_')
-                .unwrap_or(remaining.len());
-            
-            if var_end > 0 {
-                let var_name = &remaining[..var_end];
-                
-                // Only replace if it looks like a valid variable name
-                if var_name.chars().all(|c| c.is_alphanumeric() || c == '_') 
-                    && var_name.chars().next().map_or(false, |c| c.is_alphabetic() || c == '_') {
-                    
-                    println!("🔧     Found ${}, looking for value...", var_name);
-                    
-                    if let Some(replacement) = self.env_vars.get(var_name) {
-                        println!("🔧     Found value for {}: '{}'", var_name, replacement);
-                        let full_pattern = &result[absolute_start..absolute_start + 1 + var_end];
-                        result = result.replace(full_pattern, replacement);
-                        // Start search from beginning since we modified the string
-                        start_pos = 0;
-                        continue;
-                    } else {
-                        println!("🔧     No value found for {}", var_name);
-                    }
-                }
-            }
-            
-            // Move past this $ to continue searching
-            start_pos = absolute_start + 1;
-            if start_pos >= result.len() {
-                break;
-            }
-        }
-        
-        println!("🔧     Final result: '{}'", result);
-        result
+
+        println!("🔧 Expanded {} variables from shell config", unexpanded.len());
     }
 
     fn is_important_env_var(&self, key: &str) -> bool {
@@ -315,11 +388,9 @@ impl EnvironmentInfo {
         
         for var in claude_vars {
             if let Ok(value) = env::var(var) {
-                println!("🔐   Found {} in current env: '{}'", var, value);
-                // Resolve any variable references in the current environment value
-                let resolved_value = self.resolve_variable_references(&value);
-                println!("🔐   After resolution: '{}'", resolved_value);
-                self.env_vars.insert(var.to_string(), resolved_value);
+                println!("🔐   Found {} in current env", var);
+                // The process environment is final - never expand it again
+                self.env_vars.insert(var.to_string(), value);
             } else {
                 println!("🔐   {} not found in current environment", var);
             }
@@ -329,49 +400,13 @@ impl EnvironmentInfo {
         let auth_vars = vec!["SSH_AUTH_SOCK", "SSH_AGENT_PID", "GPG_AGENT_INFO"];
         for var in auth_vars {
             if let Ok(value) = env::var(var) {
-                println!("🔐   Found {} in current env: '{}'", var, value);
+                println!("🔐   Found {} in current env", var);
                 self.env_vars.insert(var.to_string(), value);
             }
         }
-        
-        // Perform one final resolution pass on authentication variables specifically
-        self.resolve_authentication_variables();
-    }
-    
-    fn resolve_authentication_variables(&mut self) {
-        println!("🔐 Final authentication variable resolution...");
-        
-        let auth_keys = vec![
-            "ANTHROPIC_AUTH_TOKEN", "ANTHROPIC_API_KEY", "ANTHROPIC_BASE_URL",
-            "CLAUDE_CODE_SSE_PORT", "CLAUDE_CODE_ENTRYPOINT", "CLAUDECODE"
-        ];
-        
-        for key in auth_keys {
-            if let Some(value) = self.env_vars.get(key).cloned() {
-                println!("🔐   Final check for {}: '{}'", key, value);
-                let resolved_value = self.resolve_variable_references(&value);
-                if resolved_value != value {
-                    println!("🔐   Updated {} from '{}' to '{}'", key, value, resolved_value);
-                    self.env_vars.insert(key.to_string(), resolved_value);
-                } else {
-                    println!("🔐   No change needed for {}", key);
-                }
-            } else {
-                println!("🔐   {} not found in env_vars", key);
-            }
-        }
     }
 
-    fn debug_kimi_variables(&self, phase: &str) {
-        println!("🐛 Debug KIMI variables ({})", phase);
-        for (key, value) in &self.env_vars {
-            if key.contains("KIMI") || key.contains("ANTHROPIC") || value.contains("KIMI") {
-                println!("🐛   {}: '{}'", key, value);
-            }
-        }
-    }
-
-    fn parse_shell_config(&mut self, content: &str) {
+    fn parse_shell_config(&mut self, content: &str, unexpanded: &mut HashMap<String, Quoting>) {
         for line in content.lines() {
             let line = line.trim();
             
@@ -387,7 +422,12 @@ impl EnvironmentInfo {
             
             // Look for other environment variable exports
             if line.starts_with("export ") {
-                self.parse_env_export(line);
+                if let Some((key, quoting)) = self.parse_env_export(line) {
+                    match quoting {
+                        Some(quoting) => unexpanded.insert(key, quoting),
+                        None => unexpanded.remove(&key),
+                    };
+                }
             }
         }
     }
@@ -396,46 +436,51 @@ impl EnvironmentInfo {
         // Handle patterns like: export PATH="/some/path:$PATH"
         if let Some(equals_pos) = line.find('=') {
             let value_part = &line[equals_pos + 1..];
-            let value_part = value_part.trim_matches('"').trim_matches('\'');
-            
-            // Split by colons and process each path
-            for path in value_part.split(':') {
-                let path = path.trim();
-                if path == "$PATH" || path == "${PATH}" {
-                    continue; // Skip PATH variable references
-                }
-                
-                // Expand ~ to home directory
-                let expanded_path = if path.starts_with('~') {
-                    if let Some(home) = dirs::home_dir() {
-                        path.replace('~', &home.to_string_lossy())
-                    } else {
-                        continue;
-                    }
-                } else {
-                    path.to_string()
-                };
+            let home = dirs::home_dir().map(|home| home.to_string_lossy().to_string());
 
-                if Path::new(&expanded_path).exists() && !self.path_dirs.contains(&expanded_path) {
-                    self.path_dirs.push(expanded_path);
+            // `$PATH` itself expands to nothing, leaving only the entries being added
+            let quoting = if value_part.starts_with('"') { Quoting::Double } else { Quoting::Bare };
+            let expanded = if value_part.starts_with('\'') {
+                value_part.trim_matches('\'').to_string()
+            } else {
+                expand(value_part.trim_matches('"'), quoting, &mut |name| match name {
+                    "PATH" => None,
+                    "HOME" => home.clone(),
+                    _ => self.env_vars.get(name).cloned(),
+                })
+            };
+
+            for path in expanded.split(':') {
+                let path = path.trim().to_string();
+                if !path.is_empty() && Path::new(&path).exists() && !self.path_dirs.contains(&path) {
+                    self.path_dirs.push(path);
                 }
             }
         }
     }
 
-    fn parse_env_export(&mut self, line: &str) {
+    /// Store an `export VAR=value` line. Returns the key and, if its value still needs
+    /// expanding, how it was quoted (single-quoted values are literal).
+    fn parse_env_export(&mut self, line: &str) -> Option<(String, Option<Quoting>)> {
         // Handle patterns like: export VAR="value"
-        if let Some(export_start) = line.find("export ") {
-            let var_part = &line[export_start + 7..];
-            if let Some(equals_pos) = var_part.find('=') {
-                let var_name = var_part[..equals_pos].trim();
-                let var_value = var_part[equals_pos + 1..].trim_matches('"').trim_matches('\'');
-                
-                if var_name != "PATH" {
-                    self.env_vars.insert(var_name.to_string(), var_value.to_string());
-                }
-            }
+        let export_start = line.find("export ")?;
+        let var_part = &line[export_start + 7..];
+        let equals_pos = var_part.find('=')?;
+        let var_name = var_part[..equals_pos].trim();
+        let raw_value = &var_part[equals_pos + 1..];
+
+        if var_name == "PATH" {
+            return None;
         }
+
+        let quoting = match raw_value.chars().next() {
+            Some('\'') => None,
+            Some('"') => Some(Quoting::Double),
+            _ => Some(Quoting::Bare),
+        };
+        let var_value = raw_value.trim_matches('"').trim_matches('\'');
+        self.env_vars.insert(var_name.to_string(), var_value.to_string());
+        Some((var_name.to_string(), quoting))
     }
 
     fn discover_dev_tools(&mut self) {
@@ -446,6 +491,8 @@ impl EnvironmentInfo {
             ("pnpm", vec!["pnpm"]),
             ("bun", vec!["bun"]),
             ("cargo", vec!["cargo"]),
+            ("rustc", vec!["rustc"]),
+            ("go", vec!["go"]),
             ("git", vec!["git"]),
             ("python", vec!["python3", "python"]),
             ("pip", vec!["pip3", "pip"]),
@@ -459,6 +506,17 @@ impl EnvironmentInfo {
                 }
             }
         }
+
+        self.version_managers = detect_version_managers(&self.env_vars);
+    }
+
+    /// Ask each discovered tool for its version (spawns one process per tool)
+    fn resolve_tool_versions(&mut self) {
+        for (tool_name, path) in &self.dev_tools {
+            if let Some(version) = resolve_version(path) {
+                self.tool_versions.insert(tool_name.clone(), version);
+            }
+        }
     }
 
     fn find_executable(&self, name: &str) -> Option<String> {
@@ -486,6 +544,93 @@ impl EnvironmentInfo {
     }
 }
 
+/// Keys present in either map whose values differ, sorted
+fn changed_keys(old: &HashMap<String, String>, new: &HashMap<String, String>) -> Vec<String> {
+    let mut keys: Vec<&String> = old.keys().chain(new.keys()).collect();
+    keys.sort();
+    keys.dedup();
+    keys.into_iter()
+        .filter(|key| old.get(*key) != new.get(*key))
+        .cloned()
+        .collect()
+}
+
+/// `env` as printed by `shell` started as a login shell, so every rc file has been sourced
+fn login_shell_env(shell: &str) -> Option<String> {
+    let shell_command = match shell {
+        "zsh" => vec!["zsh", "-l", "-c", "env"],
+        "bash" => vec!["bash", "--login", "-c", "env"],
+        "fish" => vec!["fish", "-l", "-c", "env"],
+        _ => vec!["sh", "-l", "-c", "env"],
+    };
+
+    let options = RunOptions { timeout: Some(LOGIN_SHELL_TIMEOUT), ..Default::default() };
+    match process::block_on(process::run(shell_command[0], &shell_command[1..], None, options)) {
+        Ok(output) if output.status.success() => Some(String::from_utf8_lossy(&output.stdout).into_owned()),
+        Ok(_) => None,
+        Err(e) => {
+            eprintln!("⚠️ Failed to load login shell environment: {}", e);
+            None
+        }
+    }
+}
+
+fn shell_config_files(shell: &str) -> Vec<&'static str> {
+    match shell {
+        "zsh" => vec![".zshrc", ".zprofile", ".zshenv"],
+        "bash" => vec![".bashrc", ".bash_profile", ".profile"],
+        "fish" => vec![".config/fish/config.fish"],
+        _ => vec![".profile"],
+    }
+}
+
+impl CacheKey {
+    fn current() -> Self {
+        let shell = detect_shell();
+        let home_dir = dirs::home_dir().unwrap_or_default();
+
+        // Login shells also read the system-wide files, so edits there invalidate too
+        let mut files: Vec<PathBuf> = shell_config_files(&shell)
+            .into_iter()
+            .map(|file| home_dir.join(file))
+            .collect();
+        files.extend(["/etc/profile", "/etc/zprofile", "/etc/zshrc", "/etc/zshenv", "/etc/bashrc", "/etc/paths"]
+            .iter()
+            .map(PathBuf::from));
+
+        let rc_files = files
+            .into_iter()
+            .map(|file| {
+                let mtime = fs::metadata(&file)
+                    .and_then(|metadata| metadata.modified())
+                    .ok()
+                    .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                    .map(|since_epoch| since_epoch.as_nanos());
+                (file.to_string_lossy().to_string(), mtime)
+            })
+            .collect();
+
+        CacheKey { shell, rc_files }
+    }
+}
+
+fn cache_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".manymany").join("env-cache.json"))
+}
+
+fn read_cache() -> Option<CachedEnvironment> {
+    let content = fs::read_to_string(cache_path()?).ok()?;
+    let cached: CachedEnvironment = serde_json::from_str(&content).ok()?;
+    (cached.key == CacheKey::current()).then_some(cached)
+}
+
+fn write_cache(cached: &CachedEnvironment) -> Result<(), AppError> {
+    let path = cache_path().ok_or_else(|| AppError::other("Could not find home directory"))?;
+    let content = serde_json::to_string(cached)
+        .map_err(|e| AppError::other(format!("Failed to serialize environment: {}", e)))?;
+    write_private(&path, &content)
+}
+
 fn detect_shell() -> String {
     // Try to detect user's shell
     if let Ok(shell) = env::var("SHELL") {
@@ -501,6 +646,7 @@ fn detect_shell() -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::git::fixture::TempRepo;
 
     #[test]
     fn test_environment_detection() {
@@ -516,4 +662,85 @@ mod tests {
         println!("PATH directories: {:?}", env_info.path_dirs);
         println!("Dev tools found: {:?}", env_info.dev_tools);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_environment_diff_reports_names_only() {
+        let old = EnvironmentInfo {
+            shell: "zsh".to_string(),
+            path_dirs: vec!["/usr/bin".to_string(), "/opt/old/bin".to_string()],
+            env_vars: HashMap::from([
+                ("KEEP".to_string(), "1".to_string()),
+                ("TOKEN".to_string(), "old-secret".to_string()),
+                ("GONE".to_string(), "x".to_string()),
+            ]),
+            dev_tools: HashMap::from([("node".to_string(), "/opt/old/bin/node".to_string())]),
+            tool_versions: HashMap::from([("node".to_string(), "18.19.0".to_string())]),
+            version_managers: Vec::new(),
+        };
+        let new = EnvironmentInfo {
+            shell: "zsh".to_string(),
+            path_dirs: vec!["/usr/bin".to_string(), "/opt/new/bin".to_string()],
+            env_vars: HashMap::from([
+                ("KEEP".to_string(), "1".to_string()),
+                ("TOKEN".to_string(), "new-secret".to_string()),
+                ("ADDED".to_string(), "y".to_string()),
+            ]),
+            dev_tools: HashMap::from([
+                ("node".to_string(), "/opt/new/bin/node".to_string()),
+                ("bun".to_string(), "/opt/new/bin/bun".to_string()),
+            ]),
+            tool_versions: HashMap::from([("node".to_string(), "20.11.1".to_string())]),
+            version_managers: Vec::new(),
+        };
+
+        let change = old.diff(&new);
+        assert_eq!(change.shell, None);
+        assert_eq!(change.added_vars, vec!["ADDED"]);
+        assert_eq!(change.removed_vars, vec!["GONE"]);
+        assert_eq!(change.changed_vars, vec!["TOKEN"]);
+        assert_eq!(change.added_path_dirs, vec!["/opt/new/bin"]);
+        assert_eq!(change.removed_path_dirs, vec!["/opt/old/bin"]);
+        assert_eq!(change.changed_dev_tools, vec!["bun", "node"]);
+        assert_eq!(change.changed_tool_versions, vec!["node"]);
+        assert!(old.diff(&old).is_empty());
+    }
+
+    #[test]
+    fn test_cache_leaves_out_credentials() {
+        let info = EnvironmentInfo {
+            shell: "zsh".to_string(),
+            path_dirs: vec!["/usr/bin".to_string()],
+            env_vars: HashMap::from([
+                ("GITHUB_TOKEN".to_string(), "ghp-secret".to_string()),
+                ("EDITOR".to_string(), "vim".to_string()),
+            ]),
+            dev_tools: HashMap::new(),
+            tool_versions: HashMap::new(),
+            version_managers: Vec::new(),
+        };
+
+        let cached = CachedEnvironment::new(CacheKey { shell: "zsh".to_string(), rc_files: Vec::new() }, &info);
+        assert_eq!(cached.redacted, vec!["GITHUB_TOKEN"]);
+        assert_eq!(cached.info.env_vars["EDITOR"], "vim");
+
+        let mut restored = cached.info.clone();
+        let shell = HashMap::from([("GITHUB_TOKEN", "ghp-secret")]);
+        let missing = restored.fill_vars(&["GITHUB_TOKEN".to_string(), "NPM_TOKEN".to_string()], |key| {
+            shell.get(key).map(|value| value.to_string())
+        });
+        assert_eq!(missing, vec!["NPM_TOKEN"]);
+        assert_eq!(restored, info);
+
+        let dir = TempRepo::dir();
+        let path = dir.path().join("env-cache.json");
+        write_private(&path, &serde_json::to_string(&cached).unwrap()).unwrap();
+        write_private(&path, &serde_json::to_string(&cached).unwrap()).unwrap();
+        assert!(!dir.read("env-cache.json").contains("ghp-secret"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
@@ -0,0 +1,336 @@
+//! Shell-style expansion for values read out of rc files.
+//!
+//! Supported, in a single left-to-right pass (substituted text is never re-scanned):
+//! - `$NAME` and `${NAME}`; unset variables expand to an empty string, as in `sh`
+//! - `${NAME:-word}` / `${NAME-word}`: `word` if NAME is unset-or-empty / unset
+//! - `${NAME:+word}` / `${NAME+word}`: `word` if NAME is set-and-non-empty / set
+//! - `~` at the start of the value or after a `:` when followed by `/`, `:` or the end,
+//!   unless the value was double-quoted
+//! - `\$` for a literal `$`; any other `$` that doesn't start a name (e.g. `$(`, `$1`) is kept
+//!
+//! [`Expander`] resolves a whole map of raw values, expanding each variable at most once.
+//! A variable referring to itself (`FOO=$FOO:x`) sees the value it inherited; longer
+//! reference cycles leave every variable on them unset.
+
+use std::collections::{HashMap, HashSet};
+
+/// How a raw value was quoted where it was assigned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quoting {
+    Bare,
+    /// Variables still expand, `~` doesn't
+    Double,
+}
+
+/// Expand `value` as it was quoted, looking variables up through `lookup`
+pub fn expand(value: &str, quoting: Quoting, lookup: &mut dyn FnMut(&str) -> Option<String>) -> String {
+    let chars: Vec<char> = value.chars().collect();
+    let mut result = String::with_capacity(value.len());
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '\\' if chars.get(i + 1) == Some(&'$') => {
+                result.push('$');
+                i += 2;
+            }
+            '~' if quoting == Quoting::Bare
+                && (i == 0 || chars[i - 1] == ':')
+                && matches!(chars.get(i + 1), None | Some('/') | Some(':')) =>
+            {
+                match lookup("HOME") {
+                    Some(home) => result.push_str(&home),
+                    None => result.push('~'),
+                }
+                i += 1;
+            }
+            '$' => {
+                let (expansion, consumed) = expand_dollar(&chars[i..], quoting, lookup);
+                result.push_str(&expansion);
+                i += consumed;
+            }
+            c => {
+                result.push(c);
+                i += 1;
+            }
+        }
+    }
+
+    result
+}
+
+/// Expand the reference starting at `chars[0] == '$'`.
+/// Returns the expansion and the number of characters consumed.
+fn expand_dollar(chars: &[char], quoting: Quoting, lookup: &mut dyn FnMut(&str) -> Option<String>) -> (String, usize) {
+    match chars.get(1) {
+        Some('{') => expand_braced(chars, quoting, lookup).unwrap_or_else(|| ("$".to_string(), 1)),
+        Some(c) if is_name_start(*c) => {
+            let len = 1 + chars[1..].iter().take_while(|c| is_name_char(**c)).count();
+            let name: String = chars[1..len].iter().collect();
+            (lookup(&name).unwrap_or_default(), len)
+        }
+        _ => ("$".to_string(), 1),
+    }
+}
+
+/// `${NAME}`, `${NAME:-word}`, `${NAME-word}`, `${NAME:+word}`, `${NAME+word}`.
+/// `None` when the reference is malformed, in which case the `$` is kept literally.
+fn expand_braced(
+    chars: &[char],
+    quoting: Quoting,
+    lookup: &mut dyn FnMut(&str) -> Option<String>,
+) -> Option<(String, usize)> {
+    let close = find_closing_brace(chars)?;
+    let body = &chars[2..close];
+
+    let name_len = body.iter().take_while(|c| is_name_char(**c)).count();
+    if name_len == 0 || !is_name_start(body[0]) {
+        return None;
+    }
+    let name: String = body[..name_len].iter().collect();
+    let rest = &body[name_len..];
+
+    let (colon, operator, word) = match rest {
+        [] => return Some((lookup(&name).unwrap_or_default(), close + 1)),
+        [':', op, word @ ..] => (true, *op, word),
+        [op, word @ ..] => (false, *op, word),
+    };
+
+    let value = lookup(&name);
+    // With a colon, an empty value counts as unset
+    let is_set = match &value {
+        Some(value) => !(colon && value.is_empty()),
+        None => false,
+    };
+
+    let word: String = word.iter().collect();
+    let expansion = match operator {
+        '-' if is_set => value.unwrap_or_default(),
+        '-' => expand(&word, quoting, lookup),
+        '+' if is_set => expand(&word, quoting, lookup),
+        '+' => String::new(),
+        _ => return None,
+    };
+
+    Some((expansion, close + 1))
+}
+
+/// Index of the `}` closing the `${` at the start of `chars`, honoring nested `${...}`
+fn find_closing_brace(chars: &[char]) -> Option<usize> {
+    let mut depth = 0;
+    let mut i = 2;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '$' if chars.get(i + 1) == Some(&'{') => {
+                depth += 1;
+                i += 1;
+            }
+            '}' if depth == 0 => return Some(i),
+            '}' => depth -= 1,
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+fn is_name_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_'
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Resolves variables whose raw values may reference each other
+pub struct Expander<'a> {
+    values: &'a HashMap<String, String>,
+    /// Keys whose values still need expanding, and how they were quoted; everything else
+    /// is used verbatim
+    unexpanded: &'a HashMap<String, Quoting>,
+    /// Values from before these assignments, which self-references read
+    inherited: &'a HashMap<String, String>,
+    resolved: HashMap<String, Option<String>>,
+    in_progress: Vec<String>,
+    cyclic: HashSet<String>,
+    warnings: Vec<String>,
+}
+
+impl<'a> Expander<'a> {
+    pub fn new(
+        values: &'a HashMap<String, String>,
+        unexpanded: &'a HashMap<String, Quoting>,
+        inherited: &'a HashMap<String, String>,
+    ) -> Self {
+        Self {
+            values,
+            unexpanded,
+            inherited,
+            resolved: HashMap::new(),
+            in_progress: Vec::new(),
+            cyclic: HashSet::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Final value of `key`, or `None` if it's unset or part of a reference cycle.
+    /// The result doesn't depend on the order keys are resolved in.
+    pub fn resolve(&mut self, key: &str) -> Option<String> {
+        if let Some(resolved) = self.resolved.get(key) {
+            return resolved.clone();
+        }
+
+        let raw = self.values.get(key)?;
+        let Some(&quoting) = self.unexpanded.get(key) else {
+            return Some(raw.clone());
+        };
+
+        if let Some(start) = self.in_progress.iter().position(|k| k == key) {
+            // Everything from `key` up the stack depends on itself; none of it gets a value
+            let cycle = &self.in_progress[start..];
+            self.warnings.push(format!("Variable reference cycle: {} -> {}", cycle.join(" -> "), key));
+            self.cyclic.extend(cycle.iter().cloned());
+            return None;
+        }
+
+        let inherited = self.inherited;
+        self.in_progress.push(key.to_string());
+        let value = expand(raw, quoting, &mut |name| {
+            if name == key {
+                inherited.get(name).cloned()
+            } else {
+                self.resolve(name)
+            }
+        });
+        self.in_progress.pop();
+
+        let value = (!self.cyclic.contains(key)).then_some(value);
+        self.resolved.insert(key.to_string(), value.clone());
+        value
+    }
+
+    /// Problems found so far, e.g. reference cycles
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars() -> HashMap<String, String> {
+        HashMap::from([
+            ("HOME".to_string(), "/Users/dev".to_string()),
+            ("NAME".to_string(), "kimi".to_string()),
+            ("EMPTY".to_string(), String::new()),
+            ("TRICKY".to_string(), "$NAME".to_string()),
+        ])
+    }
+
+    #[test]
+    fn test_expand_table() {
+        let cases: &[(&str, &str)] = &[
+            // plain and braced references
+            ("$NAME", "kimi"),
+            ("${NAME}", "kimi"),
+            ("pre-${NAME}-post", "pre-kimi-post"),
+            ("$NAME.$NAME", "kimi.kimi"),
+            ("${NAME}_suffix", "kimi_suffix"),
+            ("$NAME_suffix", ""),
+            // unset expands to empty
+            ("$MISSING", ""),
+            ("a${MISSING}b", "ab"),
+            // default / alternate operators
+            ("${MISSING:-fallback}", "fallback"),
+            ("${EMPTY:-fallback}", "fallback"),
+            ("${EMPTY-fallback}", ""),
+            ("${MISSING-fallback}", "fallback"),
+            ("${NAME:-fallback}", "kimi"),
+            ("${NAME:+alt}", "alt"),
+            ("${EMPTY:+alt}", ""),
+            ("${EMPTY+alt}", "alt"),
+            ("${MISSING+alt}", ""),
+            ("${MISSING:-$HOME/x}", "/Users/dev/x"),
+            ("${MISSING:-${NAME}}", "kimi"),
+            // substituted text is never re-scanned
+            ("$TRICKY", "$NAME"),
+            // tilde
+            ("~", "/Users/dev"),
+            ("~/bin", "/Users/dev/bin"),
+            ("/usr/bin:~/bin", "/usr/bin:/Users/dev/bin"),
+            ("a~b", "a~b"),
+            ("~other/bin", "~other/bin"),
+            ("${MISSING:-~/x}", "/Users/dev/x"),
+            // literal dollars
+            ("\\$NAME", "$NAME"),
+            ("cost: 5$", "cost: 5$"),
+            ("$(brew --prefix)", "$(brew --prefix)"),
+            ("$1", "$1"),
+            ("${", "${"),
+            ("${NAME", "${NAME"),
+            ("${1}", "${1}"),
+            ("${NAME?err}", "${NAME?err}"),
+        ];
+
+        let vars = vars();
+        for (input, expected) in cases {
+            let actual = expand(input, Quoting::Bare, &mut |name| vars.get(name).cloned());
+            assert_eq!(&actual, expected, "expanding {:?}", input);
+        }
+
+        // Double quotes keep `~` literal, variables still expand
+        let quoted = expand("~/bin:$HOME/bin:${MISSING:-~}", Quoting::Double, &mut |name| vars.get(name).cloned());
+        assert_eq!(quoted, "~/bin:/Users/dev/bin:~");
+    }
+
+    #[test]
+    fn test_expander_resolves_chains_once() {
+        let values = HashMap::from([
+            ("BASE".to_string(), "https://api.moonshot.ai".to_string()),
+            ("ANTHROPIC_BASE_URL".to_string(), "${BASE}/anthropic".to_string()),
+            ("DERIVED".to_string(), "$ANTHROPIC_BASE_URL/v1".to_string()),
+            ("FROM_SHELL".to_string(), "already $expanded".to_string()),
+        ]);
+        let unexpanded = HashMap::from([
+            ("ANTHROPIC_BASE_URL".to_string(), Quoting::Double),
+            ("DERIVED".to_string(), Quoting::Bare),
+        ]);
+
+        let inherited = HashMap::new();
+        let mut expander = Expander::new(&values, &unexpanded, &inherited);
+        assert_eq!(expander.resolve("DERIVED").as_deref(), Some("https://api.moonshot.ai/anthropic/v1"));
+        assert_eq!(expander.resolve("ANTHROPIC_BASE_URL").as_deref(), Some("https://api.moonshot.ai/anthropic"));
+        assert_eq!(expander.resolve("FROM_SHELL").as_deref(), Some("already $expanded"));
+        assert_eq!(expander.resolve("MISSING"), None);
+    }
+
+    #[test]
+    fn test_expander_breaks_cycles() {
+        let values = HashMap::from([
+            ("A".to_string(), "a:$B".to_string()),
+            ("B".to_string(), "b:$A".to_string()),
+            ("C".to_string(), "c:$A".to_string()),
+            ("SELF".to_string(), "$SELF:/extra".to_string()),
+            ("NEW".to_string(), "${NEW:-none}".to_string()),
+        ]);
+        let unexpanded: HashMap<String, Quoting> = values.keys().map(|key| (key.clone(), Quoting::Bare)).collect();
+        let inherited = HashMap::from([("SELF".to_string(), "/usr/bin".to_string())]);
+
+        // Same results whichever key is resolved first
+        for first in ["A", "B", "C"] {
+            let mut expander = Expander::new(&values, &unexpanded, &inherited);
+            expander.resolve(first);
+            assert_eq!(expander.resolve("A"), None);
+            assert_eq!(expander.resolve("B"), None);
+            assert_eq!(expander.resolve("C").as_deref(), Some("c:"));
+            assert_eq!(expander.warnings().len(), 1);
+        }
+
+        let mut expander = Expander::new(&values, &unexpanded, &inherited);
+        assert_eq!(expander.resolve("SELF").as_deref(), Some("/usr/bin:/extra"));
+        assert_eq!(expander.resolve("NEW").as_deref(), Some("none"));
+        assert!(expander.warnings().is_empty());
+    }
+}
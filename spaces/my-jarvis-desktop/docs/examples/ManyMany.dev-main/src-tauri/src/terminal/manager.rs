@@ -5,59 +5,48 @@ use tokio::task::JoinHandle;
 use tauri::AppHandle;
 use uuid::Uuid;
 
+use crate::error::AppError;
 use crate::terminal::task::{TerminalTask, CreateTerminalRequest};
 use crate::terminal::task::terminal_task as run_terminal_task;
 use crate::terminal::environment::EnvironmentInfo;
+use crate::terminal::profile::ProfileStore;
+use crate::secrets::looks_sensitive;
 
 #[derive(Debug)]
 pub struct TerminalManager {
     terminals: HashMap<String, TerminalTask>,
-    tasks: HashMap<String, JoinHandle<Result<(), String>>>,
+    tasks: HashMap<String, JoinHandle<Result<(), AppError>>>,
     env_info: Arc<EnvironmentInfo>,
+    /// Last known directory for each worktree a terminal was opened in
+    worktree_paths: HashMap<String, String>,
 }
 
 impl TerminalManager {
     pub fn new() -> Self {
-        // Detect environment once at startup
-        let env_info = Arc::new(EnvironmentInfo::detect());
-        
-        // Log discovered environment for debugging
-        println!("Terminal Environment Detected:");
-        println!("  Shell: {}", env_info.shell);
-        println!("  PATH: {}", env_info.get_path());
-        println!("  Dev Tools: {:?}", env_info.dev_tools);
-        
-        // Log authentication variables for debugging
-        let auth_vars: Vec<_> = env_info.env_vars.iter()
-            .filter(|(key, _)| {
-                key.contains("CLAUDE") || key.contains("ANTHROPIC") || 
-                key.contains("SSH_") || key.ends_with("_API_KEY") ||
-                key.ends_with("_TOKEN") || key.ends_with("_AUTH")
-            })
-            .collect();
-        
-        if !auth_vars.is_empty() {
-            println!("  Authentication Variables Found:");
-            for (key, value) in auth_vars {
-                // Don't log full values for security, just show they exist
-                println!("    {}: {}", key, if value.len() > 20 { "[REDACTED]" } else { value });
-            }
-        }
+        // Full detection spawns a login shell, so startup only uses the cache (or this
+        // process's environment) and `refresh_environment` swaps in the real result later
+        let env_info = Arc::new(EnvironmentInfo::load_cached().unwrap_or_else(EnvironmentInfo::from_process));
+        log_environment(&env_info);
         
         Self {
             terminals: HashMap::new(),
             tasks: HashMap::new(),
             env_info,
+            worktree_paths: HashMap::new(),
         }
     }
 
     /// Create a new terminal with async streaming
     pub fn create_terminal(
         &mut self,
-        request: CreateTerminalRequest,
+        mut request: CreateTerminalRequest,
         app: AppHandle,
-    ) -> Result<String, String> {
+    ) -> Result<String, AppError> {
         let terminal_id = Uuid::new_v4().to_string();
+
+        // Settle on the profile now, so the terminal records the worktree default too
+        let profile = ProfileStore::load()?.select(request.profile.as_deref(), &request.worktree_id)?;
+        request.profile = profile.map(|profile| profile.id);
         
         // Create communication channel for input
         let (input_tx, input_rx) = mpsc::unbounded_channel::<String>();
@@ -68,6 +57,7 @@ impl TerminalManager {
             request.name.clone(),
             request.worktree_id.clone(),
             request.working_directory.clone(),
+            request.profile.clone(),
             input_tx,
         );
         
@@ -79,6 +69,7 @@ impl TerminalManager {
         });
         
         // Store terminal and task
+        self.worktree_paths.insert(terminal_task.worktree_id.clone(), terminal_task.working_directory.clone());
         self.terminals.insert(terminal_id.clone(), terminal_task);
         self.tasks.insert(terminal_id.clone(), handle);
         
@@ -86,17 +77,17 @@ impl TerminalManager {
     }
 
     /// Send input to a specific terminal
-    pub fn send_input(&self, terminal_id: &str, data: &str) -> Result<(), String> {
+    pub fn send_input(&self, terminal_id: &str, data: &str) -> Result<(), AppError> {
         if let Some(terminal) = self.terminals.get(terminal_id) {
             terminal.send_input(data)?;
             Ok(())
         } else {
-            Err("Terminal not found".to_string())
+            Err(AppError::terminal_not_found(terminal_id))
         }
     }
 
     /// Close a specific terminal
-    pub fn close_terminal(&mut self, terminal_id: &str) -> Result<(), String> {
+    pub fn close_terminal(&mut self, terminal_id: &str) -> Result<(), AppError> {
         // Remove terminal from active list
         if let Some(_terminal) = self.terminals.remove(terminal_id) {
             // Terminal removed successfully
@@ -120,6 +111,30 @@ impl TerminalManager {
         self.terminals.get(terminal_id)
     }
 
+    /// Record the profile a terminal ended up with, once its environment is built
+    pub fn set_terminal_profile(&mut self, terminal_id: &str, profile: Option<String>) {
+        if let Some(terminal) = self.terminals.get_mut(terminal_id) {
+            terminal.profile = profile;
+        }
+    }
+
+    /// Global environment new terminals start from
+    pub fn env_info(&self) -> Arc<EnvironmentInfo> {
+        self.env_info.clone()
+    }
+
+    /// Replace the environment used for terminals created from now on.
+    /// Running terminals keep the environment they were spawned with.
+    pub fn set_env_info(&mut self, env_info: Arc<EnvironmentInfo>) -> Arc<EnvironmentInfo> {
+        log_environment(&env_info);
+        std::mem::replace(&mut self.env_info, env_info)
+    }
+
+    /// Directory of a worktree that has had a terminal opened in it
+    pub fn worktree_path(&self, worktree_id: &str) -> Option<String> {
+        self.worktree_paths.get(worktree_id).cloned()
+    }
+
     /// Check if terminal exists
     pub fn has_terminal(&self, terminal_id: &str) -> bool {
         self.terminals.contains_key(terminal_id)
@@ -152,6 +167,32 @@ impl TerminalManager {
     }
 }
 
+fn log_environment(env_info: &EnvironmentInfo) {
+    // Log discovered environment for debugging
+    println!("Terminal Environment Detected:");
+    println!("  Shell: {}", env_info.shell);
+    println!("  PATH: {}", env_info.get_path());
+    println!("  Dev Tools: {:?}", env_info.dev_tools);
+    println!("  Tool Versions: {:?}", env_info.tool_versions);
+    
+    // Log authentication variables for debugging
+    let auth_vars: Vec<_> = env_info.env_vars.iter()
+        .filter(|(key, _)| {
+            key.contains("CLAUDE") || key.contains("ANTHROPIC") || 
+            key.contains("SSH_") || key.ends_with("_API_KEY") ||
+            key.ends_with("_TOKEN") || key.ends_with("_AUTH")
+        })
+        .collect();
+    
+    if !auth_vars.is_empty() {
+        println!("  Authentication Variables Found:");
+        for (key, value) in auth_vars {
+            // Only show credentials exist; other values (e.g. base URLs, sockets) are fine
+            println!("    {}: {}", key, if looks_sensitive(key) { "[REDACTED]" } else { value });
+        }
+    }
+}
+
 impl Default for TerminalManager {
     fn default() -> Self {
         Self::new()
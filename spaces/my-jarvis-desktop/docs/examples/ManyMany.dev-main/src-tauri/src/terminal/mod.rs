@@ -1,6 +1,10 @@
 pub mod manager;
 pub mod task;
 pub mod environment;
+pub mod worktree_env;
+pub mod toolchain;
+pub mod expand;
+pub mod profile;
 
 pub use manager::TerminalManager;
 pub use task::{TerminalTask, terminal_task};
@@ -0,0 +1,228 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::error::{AppError, ErrorKind};
+use crate::secrets::{looks_sensitive, write_private, SecretValue, SecretVault};
+use crate::terminal::worktree_env::EnvValue;
+
+/// Value a profile assigns to a variable
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ProfileValue {
+    Literal { value: String },
+    /// A named secret from the vault
+    Secret { name: String },
+    /// Any other OS keychain entry, read when a terminal is spawned
+    Keychain { service: String, account: String },
+}
+
+/// A named set of overrides, e.g. "Anthropic", "Kimi" or "Local proxy"
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EnvProfile {
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub set: BTreeMap<String, ProfileValue>,
+    #[serde(default)]
+    pub unset: Vec<String>,
+}
+
+impl EnvProfile {
+    /// Resolve every value, reading secrets and keychain entries as needed
+    pub fn resolve(&self, vault: &SecretVault) -> Result<Vec<(String, EnvValue)>, AppError> {
+        self.set
+            .iter()
+            .map(|(key, value)| {
+                let resolved = match value {
+                    ProfileValue::Literal { value } => EnvValue::Plain(value.clone()),
+                    ProfileValue::Secret { name } => EnvValue::Secret(vault.get(name)
+                        .map_err(|e| e.context(format!("{} (profile '{}')", key, self.name)))?),
+                    ProfileValue::Keychain { service, account } => EnvValue::Secret(read_keychain(service, account)
+                        .map_err(|e| e.context(format!("{} (profile '{}', {})", key, self.name, service)))?),
+                };
+                Ok((key.clone(), resolved))
+            })
+            .collect()
+    }
+}
+
+fn read_keychain(service: &str, account: &str) -> Result<SecretValue, AppError> {
+    keyring::Entry::new(service, account)
+        .and_then(|entry| entry.get_password())
+        .map(SecretValue::new)
+        .map_err(|e| AppError::other(format!("Failed to read keychain entry: {}", e)))
+}
+
+fn profile_not_found(profile_id: &str) -> AppError {
+    AppError::new(
+        ErrorKind::ProfileNotFound { profile_id: profile_id.to_string() },
+        format!("Profile not found: {}", profile_id),
+    )
+}
+
+/// Profiles plus which one each worktree uses by default
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ProfileStore {
+    profiles: Vec<EnvProfile>,
+    /// worktree id -> profile id
+    worktree_profiles: HashMap<String, String>,
+    #[serde(skip)]
+    location: Option<PathBuf>,
+}
+
+impl ProfileStore {
+    pub fn load() -> Result<Self, AppError> {
+        let location = dirs::home_dir().map(|home| home.join(".manymany").join("env-profiles.json"));
+        let mut store = match &location {
+            Some(path) => Self::read(path)?,
+            None => Self::default(),
+        };
+        store.location = location;
+        Ok(store)
+    }
+
+    /// The store saved at `path`, or an empty one if there's no file. A file that doesn't parse
+    /// is an error, not an empty store, so a later save can't overwrite the user's profiles.
+    fn read(path: &Path) -> Result<Self, AppError> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(AppError::io(format!("Failed to read {}", path.display()), e)),
+        };
+        serde_json::from_str(&content).map_err(|e| {
+            AppError::other(format!("{} is not valid ({}); fix or remove it to edit profiles", path.display(), e))
+        })
+    }
+
+    /// Written private to the user, since keychain references and literals can be sensitive
+    pub fn save(&self) -> Result<(), AppError> {
+        let location = self.location.as_ref()
+            .ok_or_else(|| AppError::other("Could not find home directory"))?;
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| AppError::other(format!("Failed to serialize profiles: {}", e)))?;
+        write_private(location, &content).map_err(|e| e.context("Failed to write profiles"))
+    }
+
+    pub fn profiles(&self) -> &[EnvProfile] {
+        &self.profiles
+    }
+
+    pub fn get(&self, profile_id: &str) -> Option<&EnvProfile> {
+        self.profiles.iter().find(|profile| profile.id == profile_id)
+    }
+
+    /// Insert or replace a profile by id. A profile without an id gets a new one.
+    /// Credentials must come from the vault or keychain, never a literal stored in the profile.
+    pub fn upsert(&mut self, mut profile: EnvProfile) -> Result<EnvProfile, AppError> {
+        if profile.name.trim().is_empty() {
+            return Err(AppError::invalid("Profile name cannot be empty"));
+        }
+        for (key, value) in &profile.set {
+            if matches!(value, ProfileValue::Literal { .. }) && looks_sensitive(key) {
+                return Err(AppError::invalid(format!(
+                    "{} looks like a credential; store it as a secret and reference that instead",
+                    key
+                )));
+            }
+        }
+        if profile.id.is_empty() {
+            profile.id = Uuid::new_v4().to_string();
+        }
+
+        match self.profiles.iter_mut().find(|existing| existing.id == profile.id) {
+            Some(existing) => *existing = profile.clone(),
+            None => self.profiles.push(profile.clone()),
+        }
+        Ok(profile)
+    }
+
+    pub fn remove(&mut self, profile_id: &str) -> Result<(), AppError> {
+        let before = self.profiles.len();
+        self.profiles.retain(|profile| profile.id != profile_id);
+        if self.profiles.len() == before {
+            return Err(profile_not_found(profile_id));
+        }
+        self.worktree_profiles.retain(|_, assigned| assigned != profile_id);
+        Ok(())
+    }
+
+    pub fn worktree_profile(&self, worktree_id: &str) -> Option<&EnvProfile> {
+        self.worktree_profiles
+            .get(worktree_id)
+            .and_then(|profile_id| self.get(profile_id))
+    }
+
+    /// Set (or with `None`, clear) the default profile for new terminals in a worktree
+    pub fn assign(&mut self, worktree_id: &str, profile_id: Option<&str>) -> Result<(), AppError> {
+        match profile_id {
+            Some(profile_id) => {
+                if self.get(profile_id).is_none() {
+                    return Err(profile_not_found(profile_id));
+                }
+                self.worktree_profiles.insert(worktree_id.to_string(), profile_id.to_string());
+            }
+            None => {
+                self.worktree_profiles.remove(worktree_id);
+            }
+        }
+        Ok(())
+    }
+
+    /// Explicitly requested profile, falling back to the worktree's default
+    pub fn select(&self, requested: Option<&str>, worktree_id: &str) -> Result<Option<EnvProfile>, AppError> {
+        match requested {
+            Some(profile_id) => self.get(profile_id)
+                .cloned()
+                .map(Some)
+                .ok_or_else(|| profile_not_found(profile_id)),
+            None => Ok(self.worktree_profile(worktree_id).cloned()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::fixture::TempRepo;
+
+    fn profile(key: &str, value: ProfileValue) -> EnvProfile {
+        EnvProfile {
+            id: String::new(),
+            name: "Kimi".to_string(),
+            description: None,
+            set: BTreeMap::from([(key.to_string(), value)]),
+            unset: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_credentials_must_be_secrets() {
+        let mut store = ProfileStore::default();
+        let literal = ProfileValue::Literal { value: "sk-kimi".to_string() };
+        let error = store.upsert(profile("ANTHROPIC_AUTH_TOKEN", literal.clone())).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::InvalidInput);
+        assert!(store.profiles().is_empty());
+
+        store.upsert(profile("ANTHROPIC_BASE_URL", literal)).unwrap();
+        store.upsert(profile("ANTHROPIC_AUTH_TOKEN", ProfileValue::Secret { name: "kimi".to_string() })).unwrap();
+        assert_eq!(store.profiles().len(), 2);
+    }
+
+    #[test]
+    fn test_unreadable_store_is_not_replaced() {
+        let dir = TempRepo::dir();
+        let path = dir.path().join("env-profiles.json");
+        assert!(ProfileStore::read(&path).unwrap().profiles().is_empty());
+
+        dir.write("env-profiles.json", "{\"profiles\": [");
+        assert!(ProfileStore::read(&path).is_err());
+        assert_eq!(dir.read("env-profiles.json"), "{\"profiles\": [");
+    }
+}
@@ -1,17 +1,35 @@
 use std::io::Write;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 use tokio::sync::mpsc;
-use tauri::{AppHandle, Emitter};
+use tauri::{AppHandle, Emitter, Manager};
 use portable_pty::{CommandBuilder, PtySize, native_pty_system};
 use serde::{Deserialize, Serialize};
 
+use crate::error::AppError;
 use crate::terminal::environment::EnvironmentInfo;
+use crate::terminal::manager::TerminalManager;
+use crate::terminal::worktree_env::{build_terminal_env, EnvFileStatus};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CreateTerminalRequest {
     pub worktree_id: String,
     pub name: String,
     pub working_directory: String,
+    /// Environment profile id; defaults to the one assigned to the worktree
+    #[serde(default)]
+    pub profile: Option<String>,
+    /// Environment variable -> name of a vault secret to inject
+    #[serde(default)]
+    pub secrets: HashMap<String, String>,
+}
+
+/// Payload of the `env-trust-required` event
+#[derive(Debug, Clone, Serialize)]
+pub struct EnvTrustRequest {
+    pub terminal_id: String,
+    pub worktree_id: String,
+    pub files: Vec<EnvFileStatus>,
 }
 
 #[derive(Debug, Clone)]
@@ -20,6 +38,8 @@ pub struct TerminalTask {
     pub name: String,
     pub worktree_id: String,
     pub working_directory: String,
+    /// Profile applied to the environment: the requested one or the worktree's default
+    pub profile: Option<String>,
     pub input_tx: mpsc::UnboundedSender<String>,
     pub is_active: bool,
 }
@@ -30,6 +50,7 @@ impl TerminalTask {
         name: String,
         worktree_id: String,
         working_directory: String,
+        profile: Option<String>,
         input_tx: mpsc::UnboundedSender<String>,
     ) -> Self {
         Self {
@@ -37,15 +58,16 @@ impl TerminalTask {
             name,
             worktree_id,
             working_directory,
+            profile,
             input_tx,
             is_active: true,
         }
     }
 
-    pub fn send_input(&self, data: &str) -> Result<(), String> {
+    pub fn send_input(&self, data: &str) -> Result<(), AppError> {
         self.input_tx
             .send(data.to_string())
-            .map_err(|_| "Terminal task not running".to_string())
+            .map_err(|_| AppError::other("Terminal task not running"))
     }
 }
 
@@ -56,7 +78,7 @@ pub async fn terminal_task(
     input_rx: mpsc::UnboundedReceiver<String>,
     app: AppHandle,
     env_info: Arc<EnvironmentInfo>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     
     // Create PTY system
     let pty_system = native_pty_system();
@@ -69,7 +91,7 @@ pub async fn terminal_task(
             pixel_width: 0,
             pixel_height: 0,
         })
-        .map_err(|e| format!("Failed to create PTY: {}", e))?;
+        .map_err(|e| AppError::other(format!("Failed to create PTY: {}", e)))?;
     
     // Set up shell command with detected environment
     let mut cmd = if cfg!(windows) {
@@ -95,23 +117,53 @@ pub async fn terminal_task(
         CommandBuilder::new(final_shell)
     };
     
-    // Add enhanced environment variables
-    for (key, value) in env_info.get_env_for_spawn() {
-        cmd.env(key, value);
-    }
-    
     // Validate working directory
     let working_dir = std::path::Path::new(&request.working_directory);
     if !working_dir.exists() {
-        return Err(format!("Working directory does not exist: {}", request.working_directory));
+        return Err(AppError::path_not_found(&request.working_directory).context("Invalid working directory"));
     }
     cmd.cwd(&request.working_directory);
     
+    // Layer global, project, worktree, profile and secret environments
+    // (.envrc may spawn bash and secrets hit the keychain, so keep it off the runtime)
+    let layered_env = {
+        let env_info = env_info.clone();
+        let working_dir = working_dir.to_path_buf();
+        let requested_profile = request.profile.clone();
+        let worktree_id = request.worktree_id.clone();
+        let secrets = request.secrets.clone();
+        tokio::task::spawn_blocking(move || {
+            build_terminal_env(&env_info, &working_dir, &worktree_id, requested_profile.as_deref(), &secrets)
+        })
+        .await
+        .map_err(|e| AppError::other(format!("Failed to load worktree environment: {}", e)))??
+    };
+    
+    // A profile that failed to resolve wasn't applied
+    if layered_env.profile != request.profile {
+        let manager = app.state::<Mutex<TerminalManager>>();
+        manager.lock().unwrap().set_terminal_profile(&terminal_id, layered_env.profile.clone());
+    }
+
+    for (key, value) in layered_env.to_spawn_env() {
+        cmd.env(key, value);
+    }
+    
+    // Files that changed or were never seen are skipped until the user trusts them
+    let pending_trust = layered_env.pending_trust();
+    if !pending_trust.is_empty() {
+        let _ = app.emit("env-trust-required", EnvTrustRequest {
+            terminal_id: terminal_id.clone(),
+            worktree_id: request.worktree_id.clone(),
+            files: pending_trust,
+        });
+    }
+    
     // Spawn shell process
     let _child = pty_pair
         .slave
         .spawn_command(cmd)
-        .map_err(|e| format!("Failed to spawn shell: {}", e))?;
+        .map_err(|e| AppError::other(format!("Failed to spawn shell: {}", e)))?;
     
     // Give the shell a moment to initialize and send initial prompt
     tokio::time::sleep(std::time::Duration::from_millis(100)).await;
@@ -123,14 +175,14 @@ pub async fn terminal_task(
     let reader = match pty_master.try_clone_reader() {
         Ok(r) => r,
         Err(e) => {
-            return Err(format!("Failed to get PTY reader: {}", e));
+            return Err(AppError::other(format!("Failed to get PTY reader: {}", e)));
         }
     };
     
     let writer = match pty_master.take_writer() {
         Ok(w) => w,
         Err(e) => {
-            return Err(format!("Failed to get PTY writer: {}", e));
+            return Err(AppError::other(format!("Failed to get PTY writer: {}", e)));
         }
     };
     
@@ -0,0 +1,474 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+
+use crate::process::{self, RunOptions};
+use crate::terminal::environment::EnvironmentInfo;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VersionManagerKind {
+    Nvm,
+    Fnm,
+    Asdf,
+    Mise,
+    Volta,
+    Rustup,
+    Pyenv,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VersionManager {
+    pub kind: VersionManagerKind,
+    pub root: String,
+}
+
+/// A version requested by a file in the worktree
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolchainPin {
+    pub tool: String,
+    pub version: String,
+    pub file: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolchainState {
+    /// The pinned version is what terminals in the worktree will run
+    Ok,
+    /// Pinned, and the global tool is a different version with no installed match to switch to
+    Mismatch,
+    /// Pinned, but no version manager has a matching install and no tool is on PATH
+    NotInstalled,
+    /// Nothing pinned - the global tool is used
+    Unpinned,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolStatus {
+    pub tool: String,
+    pub pinned: Option<ToolchainPin>,
+    /// Version found on the global PATH
+    pub global_version: Option<String>,
+    /// Installed version terminals in this worktree will use for the pin
+    pub resolved_version: Option<String>,
+    pub resolved_by: Option<VersionManagerKind>,
+    pub bin_dir: Option<String>,
+    pub state: ToolchainState,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolchainReport {
+    pub worktree_path: String,
+    pub managers: Vec<VersionManager>,
+    pub tools: Vec<ToolStatus>,
+}
+
+/// Tools pins can refer to, with the name each version manager installs them under
+const PINNABLE_TOOLS: &[(&str, &[&str])] = &[
+    ("node", &["node", "nodejs"]),
+    ("python", &["python"]),
+    ("rust", &["rust"]),
+];
+
+/// Locate installed version managers from their env vars or default directories
+pub fn detect_version_managers(env_vars: &HashMap<String, String>) -> Vec<VersionManager> {
+    let home = dirs::home_dir().unwrap_or_default();
+    let candidates = [
+        (VersionManagerKind::Nvm, "NVM_DIR", vec![home.join(".nvm")]),
+        (VersionManagerKind::Fnm, "FNM_DIR", vec![
+            home.join(".local/share/fnm"),
+            home.join("Library/Application Support/fnm"),
+            home.join(".fnm"),
+        ]),
+        (VersionManagerKind::Asdf, "ASDF_DATA_DIR", vec![home.join(".asdf")]),
+        (VersionManagerKind::Mise, "MISE_DATA_DIR", vec![home.join(".local/share/mise")]),
+        (VersionManagerKind::Volta, "VOLTA_HOME", vec![home.join(".volta")]),
+        (VersionManagerKind::Rustup, "RUSTUP_HOME", vec![home.join(".rustup")]),
+        (VersionManagerKind::Pyenv, "PYENV_ROOT", vec![home.join(".pyenv")]),
+    ];
+
+    let mut managers = Vec::new();
+    for (kind, env_var, defaults) in candidates {
+        let root = env_vars
+            .get(env_var)
+            .map(PathBuf::from)
+            .into_iter()
+            .chain(defaults)
+            .find(|dir| dir.is_dir());
+
+        if let Some(root) = root {
+            managers.push(VersionManager {
+                kind,
+                root: root.to_string_lossy().to_string(),
+            });
+        }
+    }
+    managers
+}
+
+/// Run `<executable> --version` and pull out the version number
+pub fn resolve_version(executable: &str) -> Option<String> {
+    // A broken shim can hang instead of answering
+    let options = RunOptions { timeout: Some(Duration::from_secs(10)), ..Default::default() };
+    let output = process::block_on(process::run(executable, &["--version"], None, options)).ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    // Some tools (older pythons) print their version on stderr
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    parse_version_output(&stdout).or_else(|| parse_version_output(&stderr))
+}
+
+/// First token that looks like a version, e.g. "v20.11.0", "cargo 1.75.0 (abc)", "Python 3.11.4"
+fn parse_version_output(output: &str) -> Option<String> {
+    output
+        .split_whitespace()
+        .map(|token| token.trim_start_matches('v').trim_end_matches(','))
+        .find(|token| token.starts_with(|c: char| c.is_ascii_digit()) && token.contains('.'))
+        .map(String::from)
+}
+
+/// Read every pin file in the worktree. Dedicated files win over `.tool-versions`.
+pub fn read_pins(worktree_path: &Path) -> Vec<ToolchainPin> {
+    let mut pins: Vec<ToolchainPin> = Vec::new();
+    let mut add = |tool: &str, version: &str, file: &Path| {
+        let version = version.trim().trim_start_matches('v');
+        if !version.is_empty() && !pins.iter().any(|pin| pin.tool == tool) {
+            pins.push(ToolchainPin {
+                tool: tool.to_string(),
+                version: version.to_string(),
+                file: file.to_string_lossy().to_string(),
+            });
+        }
+    };
+
+    for file_name in [".nvmrc", ".node-version"] {
+        let file = worktree_path.join(file_name);
+        if let Some(version) = first_line(&file) {
+            add("node", &version, &file);
+        }
+    }
+
+    let file = worktree_path.join(".python-version");
+    if let Some(version) = first_line(&file) {
+        add("python", &version, &file);
+    }
+
+    for file_name in ["rust-toolchain.toml", "rust-toolchain"] {
+        let file = worktree_path.join(file_name);
+        if let Ok(content) = fs::read_to_string(&file) {
+            if let Some(channel) = parse_rust_toolchain(&content) {
+                add("rust", &channel, &file);
+            }
+        }
+    }
+
+    let file = worktree_path.join(".tool-versions");
+    if let Ok(content) = fs::read_to_string(&file) {
+        for line in content.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let mut parts = line.split_whitespace();
+            if let (Some(name), Some(version)) = (parts.next(), parts.next()) {
+                let tool = PINNABLE_TOOLS
+                    .iter()
+                    .find(|(_, aliases)| aliases.contains(&name))
+                    .map_or(name, |(tool, _)| tool);
+                add(tool, version, &file);
+            }
+        }
+    }
+
+    pins
+}
+
+fn first_line(path: &Path) -> Option<String> {
+    let content = fs::read_to_string(path).ok()?;
+    content
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+}
+
+/// `channel` from `rust-toolchain.toml`, or the bare legacy `rust-toolchain` format
+fn parse_rust_toolchain(content: &str) -> Option<String> {
+    for line in content.lines() {
+        let line = line.trim();
+        if let Some(rest) = line.strip_prefix("channel") {
+            let value = rest.trim_start().strip_prefix('=')?.trim();
+            return Some(value.trim_matches('"').trim_matches('\'').to_string());
+        }
+    }
+
+    let first = content.lines().map(str::trim).find(|line| !line.is_empty())?;
+    if first.starts_with('[') || first.contains('=') {
+        None
+    } else {
+        Some(first.to_string())
+    }
+}
+
+/// Installed (version, bin directory) pairs a manager has for `tool`
+fn installed_versions(manager: &VersionManager, tool: &str) -> Vec<(String, PathBuf)> {
+    let root = Path::new(&manager.root);
+    let (install_dir, bin_suffix): (PathBuf, &[&str]) = match (manager.kind, tool) {
+        (VersionManagerKind::Nvm, "node") => (root.join("versions/node"), &["bin"]),
+        (VersionManagerKind::Fnm, "node") => (root.join("node-versions"), &["installation", "bin"]),
+        (VersionManagerKind::Volta, "node") => (root.join("tools/image/node"), &["bin"]),
+        (VersionManagerKind::Asdf, "node") => (root.join("installs/nodejs"), &["bin"]),
+        (VersionManagerKind::Asdf, "python") => (root.join("installs/python"), &["bin"]),
+        (VersionManagerKind::Mise, "node") => (root.join("installs/node"), &["bin"]),
+        (VersionManagerKind::Mise, "python") => (root.join("installs/python"), &["bin"]),
+        (VersionManagerKind::Pyenv, "python") => (root.join("versions"), &["bin"]),
+        (VersionManagerKind::Rustup, "rust") => (root.join("toolchains"), &["bin"]),
+        _ => return Vec::new(),
+    };
+
+    let Ok(entries) = fs::read_dir(&install_dir) else {
+        return Vec::new();
+    };
+
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let bin_dir = bin_suffix.iter().fold(entry.path(), |dir, part| dir.join(part));
+            bin_dir.is_dir().then(|| (name.trim_start_matches('v').to_string(), bin_dir))
+        })
+        .collect()
+}
+
+/// Node LTS lines by codename, for `.nvmrc` files that say `lts/iron`
+const NODE_LTS: &[(&str, u64)] = &[
+    ("argon", 4),
+    ("boron", 6),
+    ("carbon", 8),
+    ("dubnium", 10),
+    ("erbium", 12),
+    ("fermium", 14),
+    ("gallium", 16),
+    ("hydrogen", 18),
+    ("iron", 20),
+    ("jod", 22),
+    ("krypton", 24),
+];
+
+/// Does an installed version of `tool` satisfy a pin? "20" matches "20.11.0", "stable" matches
+/// "stable-aarch64-apple-darwin", nvm's `lts/<codename>` matches any release of that line and
+/// `lts/*` the newest line. nvm's `node` and `stable` mean the newest installed version, so any
+/// installed node matches them.
+fn version_matches(tool: &str, pin: &str, installed: &str) -> bool {
+    if tool == "node" && matches!(pin, "node" | "stable") {
+        return true;
+    }
+    if let Some(alias) = pin.strip_prefix("lts/") {
+        let Some(major) = installed.split('.').next().and_then(|major| major.parse::<u64>().ok()) else {
+            return false;
+        };
+        let alias = alias.to_ascii_lowercase();
+        // The table is oldest first, so `lts/*` is its last line
+        let line = match alias.as_str() {
+            "*" => NODE_LTS.last(),
+            _ => NODE_LTS.iter().find(|(codename, _)| *codename == alias),
+        };
+        return line.is_some_and(|(_, lts_major)| major == *lts_major);
+    }
+
+    if installed == pin {
+        return true;
+    }
+    installed
+        .strip_prefix(pin)
+        .is_some_and(|rest| rest.starts_with('.') || rest.starts_with('-'))
+}
+
+/// Compare dotted versions numerically so "20.9.0" sorts below "20.11.0"
+fn version_key(version: &str) -> Vec<u64> {
+    version
+        .split(['.', '-'])
+        .map(|part| part.parse().unwrap_or(0))
+        .collect()
+}
+
+/// Best installed match for a pin across all detected managers
+fn resolve_pin(pin: &ToolchainPin, managers: &[VersionManager]) -> Option<(String, PathBuf, VersionManagerKind)> {
+    managers
+        .iter()
+        .flat_map(|manager| {
+            installed_versions(manager, &pin.tool)
+                .into_iter()
+                .map(move |(version, bin_dir)| (version, bin_dir, manager.kind))
+        })
+        .filter(|(version, _, _)| version_matches(&pin.tool, &pin.version, version))
+        .max_by(|(a, _, _), (b, _, _)| version_key(a).cmp(&version_key(b)))
+}
+
+/// Global tool names that report the version of each pinnable tool
+fn global_tool(tool: &str) -> &str {
+    match tool {
+        "rust" => "rustc",
+        other => other,
+    }
+}
+
+/// Check every pin in the worktree against what's installed
+pub fn toolchain_report(env_info: &EnvironmentInfo, worktree_path: &Path) -> ToolchainReport {
+    let pins = read_pins(worktree_path);
+    let mut tools = Vec::new();
+
+    for (tool, _) in PINNABLE_TOOLS {
+        let pinned = pins.iter().find(|pin| pin.tool == *tool).cloned();
+        let global_version = env_info.tool_versions.get(global_tool(tool)).cloned();
+
+        let mut status = ToolStatus {
+            tool: tool.to_string(),
+            pinned: pinned.clone(),
+            global_version: global_version.clone(),
+            resolved_version: None,
+            resolved_by: None,
+            bin_dir: None,
+            state: ToolchainState::Unpinned,
+        };
+
+        if let Some(pin) = pinned {
+            if let Some((version, bin_dir, kind)) = resolve_pin(&pin, &env_info.version_managers) {
+                status.resolved_version = Some(version);
+                status.resolved_by = Some(kind);
+                status.bin_dir = Some(bin_dir.to_string_lossy().to_string());
+                status.state = ToolchainState::Ok;
+            } else {
+                status.state = match &global_version {
+                    Some(version) if version_matches(&pin.tool, &pin.version, version) => ToolchainState::Ok,
+                    Some(_) => ToolchainState::Mismatch,
+                    None => ToolchainState::NotInstalled,
+                };
+                if status.state == ToolchainState::Ok {
+                    status.resolved_version = global_version;
+                }
+            }
+        }
+
+        tools.push(status);
+    }
+
+    // Report pins for tools we don't know how to switch, so they aren't silently ignored
+    for pin in pins.iter().filter(|pin| !PINNABLE_TOOLS.iter().any(|(tool, _)| *tool == pin.tool)) {
+        let global_version = env_info.tool_versions.get(&pin.tool).cloned();
+        let state = match &global_version {
+            Some(version) if version_matches(&pin.tool, &pin.version, version) => ToolchainState::Ok,
+            Some(_) => ToolchainState::Mismatch,
+            None => ToolchainState::NotInstalled,
+        };
+        tools.push(ToolStatus {
+            tool: pin.tool.clone(),
+            pinned: Some(pin.clone()),
+            global_version,
+            resolved_version: None,
+            resolved_by: None,
+            bin_dir: None,
+            state,
+        });
+    }
+
+    ToolchainReport {
+        worktree_path: worktree_path.to_string_lossy().to_string(),
+        managers: env_info.version_managers.clone(),
+        tools,
+    }
+}
+
+/// Bin directories to put in front of PATH so pinned versions win in this worktree.
+/// rustup proxies already honor `rust-toolchain.toml` on their own.
+pub fn pinned_bin_dirs(env_info: &EnvironmentInfo, worktree_path: &Path) -> Vec<String> {
+    read_pins(worktree_path)
+        .iter()
+        .filter(|pin| pin.tool != "rust")
+        .filter_map(|pin| resolve_pin(pin, &env_info.version_managers))
+        .map(|(_, bin_dir, _)| bin_dir.to_string_lossy().to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::fixture::TempRepo;
+
+    #[test]
+    fn test_parse_version_output() {
+        assert_eq!(parse_version_output("v20.11.0\n"), Some("20.11.0".to_string()));
+        assert_eq!(parse_version_output("cargo 1.75.0 (1d8b05cdd 2023-11-20)"), Some("1.75.0".to_string()));
+        assert_eq!(parse_version_output("git version 2.39.5"), Some("2.39.5".to_string()));
+        assert_eq!(parse_version_output("Python 3.11.4"), Some("3.11.4".to_string()));
+        assert_eq!(parse_version_output("no version here"), None);
+    }
+
+    #[test]
+    fn test_pins_and_resolution() {
+        let dir = TempRepo::dir();
+        let nvm = dir.path().join("nvm");
+        for version in ["v18.19.0", "v20.9.0", "v20.11.1"] {
+            fs::create_dir_all(nvm.join("versions/node").join(version).join("bin")).unwrap();
+        }
+        let worktree = dir.path().join("worktree");
+        fs::create_dir_all(&worktree).unwrap();
+        fs::write(worktree.join(".nvmrc"), "v20\n").unwrap();
+        fs::write(worktree.join(".tool-versions"), "nodejs 18.19.0\npython 3.12.1 # comment\n").unwrap();
+        fs::write(worktree.join("rust-toolchain.toml"), "[toolchain]\nchannel = \"1.75.0\"\n").unwrap();
+
+        let pins = read_pins(&worktree);
+        let pin = |tool: &str| pins.iter().find(|pin| pin.tool == tool).map(|pin| pin.version.clone());
+        assert_eq!(pin("node"), Some("20".to_string()));
+        assert_eq!(pin("python"), Some("3.12.1".to_string()));
+        assert_eq!(pin("rust"), Some("1.75.0".to_string()));
+
+        let env_info = EnvironmentInfo {
+            shell: "zsh".to_string(),
+            path_dirs: Vec::new(),
+            env_vars: HashMap::new(),
+            dev_tools: HashMap::new(),
+            tool_versions: HashMap::from([("python".to_string(), "3.11.4".to_string())]),
+            version_managers: vec![VersionManager {
+                kind: VersionManagerKind::Nvm,
+                root: nvm.to_string_lossy().to_string(),
+            }],
+        };
+
+        let report = toolchain_report(&env_info, &worktree);
+        let status = |tool: &str| report.tools.iter().find(|status| status.tool == tool).unwrap().clone();
+        assert_eq!(status("node").state, ToolchainState::Ok);
+        assert_eq!(status("node").resolved_version, Some("20.11.1".to_string()));
+        assert_eq!(status("python").state, ToolchainState::Mismatch);
+        assert_eq!(status("rust").state, ToolchainState::NotInstalled);
+
+        let bin_dirs = pinned_bin_dirs(&env_info, &worktree);
+        assert_eq!(bin_dirs.len(), 1);
+        assert!(bin_dirs[0].ends_with("v20.11.1/bin"));
+
+        fs::write(worktree.join(".nvmrc"), "lts/hydrogen\n").unwrap();
+        let report = toolchain_report(&env_info, &worktree);
+        let node = report.tools.iter().find(|status| status.tool == "node").unwrap();
+        assert_eq!((node.state, node.resolved_version.as_deref()), (ToolchainState::Ok, Some("18.19.0")));
+    }
+
+    #[test]
+    fn test_lts_aliases() {
+        assert!(version_matches("node", "lts/*", "24.1.0"));
+        assert!(!version_matches("node", "lts/*", "20.11.1"));
+        // An even release line that isn't LTS yet
+        assert!(!version_matches("node", "lts/*", "26.0.0"));
+        assert!(!version_matches("node", "lts/*", "21.6.0"));
+        assert!(version_matches("node", "lts/Iron", "20.11.1"));
+        assert!(!version_matches("node", "lts/iron", "18.19.0"));
+        assert!(!version_matches("node", "lts/unknown", "20.11.1"));
+
+        assert!(version_matches("node", "node", "21.6.0"));
+        assert!(version_matches("node", "stable", "18.19.0"));
+        assert!(!version_matches("rust", "stable", "nightly-aarch64-apple-darwin"));
+        assert!(version_matches("rust", "stable", "stable-aarch64-apple-darwin"));
+    }
+}
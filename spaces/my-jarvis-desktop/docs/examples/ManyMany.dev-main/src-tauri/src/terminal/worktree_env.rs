@@ -0,0 +1,730 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use serde::{Deserialize, Serialize, Serializer};
+use sha2::{Digest, Sha256};
+
+use crate::error::AppError;
+use crate::git;
+use crate::process::{self, RunOptions};
+use crate::secrets::{looks_sensitive, SecretValue, SecretVault};
+use crate::terminal::environment::EnvironmentInfo;
+use crate::terminal::profile::{EnvProfile, ProfileStore};
+use crate::terminal::toolchain::pinned_bin_dirs;
+
+/// Files loaded (in order) from the project root and from the worktree
+const DOTENV_FILES: &[&str] = &[".env", ".env.local"];
+const ENVRC_FILE: &str = ".envrc";
+const ENVRC_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Minimal subset of the direnv stdlib so common `.envrc` files evaluate under plain bash.
+/// Helpers that load other files are no-ops: only the trusted `.envrc` itself runs, and `.env`
+/// files come from the dotenv layer with their own trust check.
+const ENVRC_PRELUDE: &str = r#"
+PATH_add() { for d in "$@"; do export PATH="$(cd "$d" 2>/dev/null && pwd):$PATH"; done; }
+path_add() { local v="$1"; shift; for d in "$@"; do export "$v"="$(cd "$d" 2>/dev/null && pwd):${!v}"; done; }
+dotenv() { :; }
+dotenv_if_exists() { :; }
+source_env() { :; }
+source_env_if_exists() { :; }
+watch_file() { :; }
+source_up() { :; }
+log_status() { :; }
+"#;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EnvSource {
+    Global,
+    Project,
+    Worktree,
+    /// PATH adjusted for versions pinned by `.nvmrc`, `.tool-versions`, etc.
+    Toolchain,
+    Envrc,
+    /// Named profile chosen for the terminal or the worktree
+    Profile,
+    /// Vault secrets requested by the terminal itself
+    Secret,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EnvFileKind {
+    Dotenv,
+    Envrc,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EnvTrustState {
+    Trusted,
+    /// Never seen before - needs a trust decision
+    New,
+    /// Trusted previously, but the contents changed since
+    Changed,
+    Denied,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnvFileStatus {
+    pub path: String,
+    pub kind: EnvFileKind,
+    pub source: EnvSource,
+    pub state: EnvTrustState,
+}
+
+/// A variable's value. Secrets serialize as `null` so they never reach the frontend.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EnvValue {
+    Plain(String),
+    Secret(SecretValue),
+}
+
+impl EnvValue {
+    pub fn expose(&self) -> &str {
+        match self {
+            EnvValue::Plain(value) => value,
+            EnvValue::Secret(value) => value.expose(),
+        }
+    }
+
+    pub fn is_secret(&self) -> bool {
+        matches!(self, EnvValue::Secret(_))
+    }
+}
+
+impl From<String> for EnvValue {
+    fn from(value: String) -> Self {
+        EnvValue::Plain(value)
+    }
+}
+
+impl Serialize for EnvValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            EnvValue::Plain(value) => serializer.serialize_str(value),
+            EnvValue::Secret(_) => serializer.serialize_none(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EnvVar {
+    pub value: EnvValue,
+    pub secret: bool,
+    pub source: EnvSource,
+    /// File the value came from, `None` for the global environment
+    pub file: Option<String>,
+    /// Earlier layers this variable overrode, outermost first
+    pub overrides: Vec<EnvSource>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LayeredEnv {
+    pub vars: BTreeMap<String, EnvVar>,
+    pub files: Vec<EnvFileStatus>,
+    /// Id of the profile applied last, if any
+    pub profile: Option<String>,
+    /// Problems that didn't stop the terminal from starting, e.g. a missing keychain entry
+    pub warnings: Vec<String>,
+}
+
+impl LayeredEnv {
+    fn set(&mut self, key: String, value: impl Into<EnvValue>, source: EnvSource, file: Option<&Path>) {
+        let mut overrides = Vec::new();
+        if let Some(previous) = self.vars.get(&key) {
+            overrides = previous.overrides.clone();
+            overrides.push(previous.source);
+        }
+
+        // Credentials picked up from the shell or .env files are treated like vault secrets
+        let value = match value.into() {
+            EnvValue::Plain(value) if looks_sensitive(&key) => EnvValue::Secret(SecretValue::new(value)),
+            value => value,
+        };
+
+        self.vars.insert(key, EnvVar {
+            secret: value.is_secret(),
+            value,
+            source,
+            file: file.map(|f| f.to_string_lossy().to_string()),
+            overrides,
+        });
+    }
+
+    /// Files that were skipped because they still need a trust decision
+    pub fn pending_trust(&self) -> Vec<EnvFileStatus> {
+        self.files
+            .iter()
+            .filter(|f| matches!(f.state, EnvTrustState::New | EnvTrustState::Changed))
+            .cloned()
+            .collect()
+    }
+
+    pub fn to_spawn_env(&self) -> HashMap<String, String> {
+        self.vars
+            .iter()
+            .map(|(key, var)| (key.clone(), var.value.expose().to_string()))
+            .collect()
+    }
+
+    /// Apply a profile's unsets and overrides on top of everything else. If any value fails
+    /// to resolve, none of the profile is applied.
+    pub fn apply_profile(&mut self, profile: &EnvProfile, vault: &SecretVault) {
+        let values = match profile.resolve(vault) {
+            Ok(values) => values,
+            Err(e) => {
+                let warning = format!("Profile '{}' not applied: {}", profile.name, e);
+                eprintln!("{}", warning);
+                self.warnings.push(warning);
+                return;
+            }
+        };
+
+        for key in &profile.unset {
+            self.vars.remove(key);
+        }
+        for (key, value) in values {
+            self.set(key, value, EnvSource::Profile, None);
+        }
+        self.profile = Some(profile.id.clone());
+    }
+
+    /// Inject vault secrets, keyed by variable name -> secret name
+    pub fn apply_secrets(&mut self, secrets: &HashMap<String, String>, vault: &SecretVault) {
+        for (key, secret_name) in secrets {
+            match vault.get(secret_name) {
+                Ok(value) => self.set(key.clone(), EnvValue::Secret(value), EnvSource::Secret, None),
+                Err(e) => {
+                    let warning = format!("{} not set: {}", key, e);
+                    eprintln!("{}", warning);
+                    self.warnings.push(warning);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TrustEntry {
+    fingerprint: String,
+    allowed: bool,
+}
+
+/// Allow-list of `.env`/`.envrc` files, keyed by absolute path and content fingerprint
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct EnvTrustStore {
+    files: HashMap<String, TrustEntry>,
+    #[serde(skip)]
+    location: Option<PathBuf>,
+}
+
+impl EnvTrustStore {
+    pub fn load() -> Result<Self, AppError> {
+        let location = dirs::home_dir().map(|home| home.join(".manymany").join("env-trust.json"));
+        let mut store = match &location {
+            Some(path) => Self::read(path)?,
+            None => Self::default(),
+        };
+        store.location = location;
+        Ok(store)
+    }
+
+    /// The store saved at `path`, or an empty one if there's no file. A file that doesn't parse
+    /// is an error, not an empty store, so a later save can't drop every decision in it.
+    fn read(path: &Path) -> Result<Self, AppError> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(AppError::io(format!("Failed to read {}", path.display()), e)),
+        };
+        serde_json::from_str(&content).map_err(|e| {
+            AppError::other(format!("{} is not valid ({}); fix or remove it to trust env files", path.display(), e))
+        })
+    }
+
+    pub fn save(&self) -> Result<(), AppError> {
+        let location = self.location.as_ref()
+            .ok_or_else(|| AppError::other("Could not find home directory"))?;
+        if let Some(parent) = location.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| AppError::io("Failed to create settings directory", e))?;
+        }
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| AppError::other(format!("Failed to serialize trust store: {}", e)))?;
+        fs::write(location, content)
+            .map_err(|e| AppError::io("Failed to write trust store", e))
+    }
+
+    pub fn state_for(&self, path: &Path, content: &str) -> EnvTrustState {
+        match self.files.get(&path.to_string_lossy().to_string()) {
+            None => EnvTrustState::New,
+            Some(entry) if !entry.allowed => EnvTrustState::Denied,
+            Some(entry) if entry.fingerprint != fingerprint(path, content) => EnvTrustState::Changed,
+            Some(_) => EnvTrustState::Trusted,
+        }
+    }
+
+    /// Record a decision for the file's current contents
+    pub fn decide(&mut self, path: &Path, allowed: bool) -> Result<(), AppError> {
+        let content = fs::read_to_string(path).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => AppError::path_not_found(path.display()),
+            _ => AppError::io(format!("Failed to read {}", path.display()), e),
+        })?;
+        self.files.insert(path.to_string_lossy().to_string(), TrustEntry {
+            fingerprint: fingerprint(path, &content),
+            allowed,
+        });
+        Ok(())
+    }
+
+    pub fn forget(&mut self, path: &Path) {
+        self.files.remove(&path.to_string_lossy().to_string());
+    }
+}
+
+/// SHA-256 over the canonical path and the contents. Trust decides whether `.envrc` code runs,
+/// so an edited file must not be able to match a trusted fingerprint.
+fn fingerprint(path: &Path, content: &str) -> String {
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut hasher = Sha256::new();
+    hasher.update(path.to_string_lossy().as_bytes());
+    hasher.update([0]);
+    hasher.update(content.as_bytes());
+    hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Everything a new terminal gets: the layered environment, then the selected profile
+/// (or the worktree's default), then secrets requested by the terminal.
+pub fn build_terminal_env(
+    env_info: &EnvironmentInfo,
+    worktree_path: &Path,
+    worktree_id: &str,
+    requested_profile: Option<&str>,
+    secrets: &HashMap<String, String>,
+) -> Result<LayeredEnv, AppError> {
+    let profile = ProfileStore::load()?.select(requested_profile, worktree_id)?;
+    let mut layered = build_layered_env(env_info, worktree_path, &EnvTrustStore::load()?);
+
+    if profile.is_some() || !secrets.is_empty() {
+        let vault = SecretVault::open()?;
+        if let Some(profile) = &profile {
+            layered.apply_profile(profile, &vault);
+        }
+        layered.apply_secrets(secrets, &vault);
+    }
+
+    Ok(layered)
+}
+
+/// Build the environment for a terminal in `worktree_path`: global, then project `.env`
+/// files, then worktree `.env` files, then pinned toolchains, then a trusted `.envrc`.
+/// Profiles and secrets are applied on top by the caller.
+pub fn build_layered_env(
+    env_info: &EnvironmentInfo,
+    worktree_path: &Path,
+    trust: &EnvTrustStore,
+) -> LayeredEnv {
+    let mut layered = LayeredEnv::default();
+
+    for (key, value) in env_info.get_env_for_spawn() {
+        layered.set(key, value, EnvSource::Global, None);
+    }
+
+    if let Some(project_root) = find_project_root(worktree_path) {
+        if project_root != worktree_path {
+            load_dotenv_layer(&mut layered, &project_root, EnvSource::Project, trust);
+        }
+    }
+    load_dotenv_layer(&mut layered, worktree_path, EnvSource::Worktree, trust);
+
+    let bin_dirs = pinned_bin_dirs(env_info, worktree_path);
+    if !bin_dirs.is_empty() {
+        let current_path = layered.vars.get("PATH").map(|var| var.value.expose().to_string()).unwrap_or_default();
+        let path = bin_dirs.into_iter()
+            .chain(current_path.split(':').filter(|dir| !dir.is_empty()).map(String::from))
+            .collect::<Vec<_>>()
+            .join(":");
+        layered.set("PATH".to_string(), path, EnvSource::Toolchain, None);
+    }
+
+    let envrc_path = worktree_path.join(ENVRC_FILE);
+    if let Ok(content) = fs::read_to_string(&envrc_path) {
+        let state = trust.state_for(&envrc_path, &content);
+        layered.files.push(EnvFileStatus {
+            path: envrc_path.to_string_lossy().to_string(),
+            kind: EnvFileKind::Envrc,
+            source: EnvSource::Envrc,
+            state,
+        });
+
+        if state == EnvTrustState::Trusted {
+            match evaluate_envrc(worktree_path, &content, &layered.to_spawn_env()) {
+                Ok(diff) => {
+                    for (key, value) in diff.changed {
+                        layered.set(key, value, EnvSource::Envrc, Some(&envrc_path));
+                    }
+                    for key in diff.removed {
+                        layered.vars.remove(&key);
+                    }
+                }
+                Err(e) => {
+                    let warning = format!("Failed to evaluate {}: {}", envrc_path.display(), e);
+                    eprintln!("{}", warning);
+                    layered.warnings.push(warning);
+                }
+            }
+        }
+    }
+
+    layered
+}
+
+fn load_dotenv_layer(layered: &mut LayeredEnv, dir: &Path, source: EnvSource, trust: &EnvTrustStore) {
+    for file_name in DOTENV_FILES {
+        let path = dir.join(file_name);
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(_) => continue,
+        };
+
+        let state = trust.state_for(&path, &content);
+        layered.files.push(EnvFileStatus {
+            path: path.to_string_lossy().to_string(),
+            kind: EnvFileKind::Dotenv,
+            source,
+            state,
+        });
+
+        if state == EnvTrustState::Trusted {
+            for (key, value) in parse_dotenv(&content) {
+                layered.set(key, value, source, Some(&path));
+            }
+        }
+    }
+}
+
+/// Main checkout of the repository `worktree_path` belongs to
+pub fn find_project_root(worktree_path: &Path) -> Option<PathBuf> {
+    let common_dir = git::common_dir(worktree_path).ok()?;
+    if common_dir.file_name().is_some_and(|name| name == ".git") {
+        common_dir.parent().map(Path::to_path_buf)
+    } else {
+        // Bare repository - there's no checkout to read files from
+        None
+    }
+}
+
+/// Parse a dotenv file into ordered key/value pairs
+pub fn parse_dotenv(content: &str) -> Vec<(String, String)> {
+    let mut vars = Vec::new();
+    let mut lines = content.lines();
+
+    while let Some(line) = lines.next() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let line = line.strip_prefix("export ").unwrap_or(line).trim_start();
+        let Some(equals_pos) = line.find('=') else {
+            continue;
+        };
+
+        let key = line[..equals_pos].trim();
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            continue;
+        }
+
+        let raw = line[equals_pos + 1..].trim_start();
+        let value = if let Some(rest) = raw.strip_prefix('"') {
+            // Double-quoted values may span lines and support escapes
+            let mut body = rest.to_string();
+            while !has_closing_quote(&body, '"') {
+                match lines.next() {
+                    Some(next) => {
+                        body.push('\n');
+                        body.push_str(next);
+                    }
+                    None => break,
+                }
+            }
+            unescape_double_quoted(&body)
+        } else if let Some(rest) = raw.strip_prefix('\'') {
+            rest.find('\'').map_or(rest, |end| &rest[..end]).to_string()
+        } else {
+            // Unquoted: an inline comment needs whitespace before the `#`
+            let value = match raw.find(" #") {
+                Some(comment_pos) => &raw[..comment_pos],
+                None => raw,
+            };
+            value.trim_end().to_string()
+        };
+
+        vars.push((key.to_string(), value));
+    }
+
+    vars
+}
+
+fn has_closing_quote(body: &str, quote: char) -> bool {
+    let mut escaped = false;
+    for c in body.chars() {
+        match c {
+            '\\' if !escaped => escaped = true,
+            c if c == quote && !escaped => return true,
+            _ => escaped = false,
+        }
+    }
+    false
+}
+
+fn unescape_double_quoted(body: &str) -> String {
+    let mut value = String::new();
+    let mut chars = body.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => break,
+            '\\' => match chars.next() {
+                Some('n') => value.push('\n'),
+                Some('t') => value.push('\t'),
+                Some('r') => value.push('\r'),
+                Some(other) => value.push(other),
+                None => value.push('\\'),
+            },
+            c => value.push(c),
+        }
+    }
+    value
+}
+
+/// What sourcing an `.envrc` did to the environment it was given
+struct EnvrcDiff {
+    changed: Vec<(String, String)>,
+    removed: Vec<String>,
+}
+
+/// Run the `.envrc` `content` in a bash subshell seeded with `base_env` and diff the result.
+/// The content is the exact text that was trust-checked, passed on stdin rather than re-read
+/// from disk, so an edit made after the check can't run.
+fn evaluate_envrc(worktree_path: &Path, content: &str, base_env: &HashMap<String, String>) -> Result<EnvrcDiff, AppError> {
+    let script = format!("{}\n{{ eval \"$(cat)\"; }} >/dev/null 2>&1\nenv -0", ENVRC_PRELUDE);
+
+    let options = RunOptions {
+        timeout: Some(ENVRC_TIMEOUT),
+        stdin: Some(content.as_bytes().to_vec()),
+        env: base_env.iter().map(|(key, value)| (key.clone(), value.clone())).collect(),
+        clear_env: true,
+        ..Default::default()
+    };
+    let output = process::block_on(process::run("bash", &["-c", &script], Some(worktree_path), options))?;
+
+    // An `exit` or a failure before `env -0` leaves no complete dump; diffing against
+    // it would report every variable as removed
+    if !output.status.success() {
+        return Err(AppError::other(format!("bash exited with {}", output.status)));
+    }
+    let output = output.stdout;
+    if output.last() != Some(&0) {
+        return Err(AppError::other("No environment was printed"));
+    }
+    let mut evaluated = HashMap::new();
+    for entry in output.split(|b| *b == 0) {
+        let entry = String::from_utf8_lossy(entry);
+        if let Some(equals_pos) = entry.find('=') {
+            evaluated.insert(entry[..equals_pos].to_string(), entry[equals_pos + 1..].to_string());
+        }
+    }
+
+    // Variables bash manages itself are not part of what the .envrc exported
+    for key in ["_", "PWD", "OLDPWD", "SHLVL"] {
+        evaluated.remove(key);
+    }
+
+    let mut changed: Vec<(String, String)> = evaluated
+        .iter()
+        .filter(|(key, value)| base_env.get(*key) != Some(*value))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    changed.sort();
+
+    let removed = base_env
+        .keys()
+        .filter(|key| !evaluated.contains_key(*key) && !matches!(key.as_str(), "_" | "PWD" | "OLDPWD" | "SHLVL"))
+        .cloned()
+        .collect();
+
+    Ok(EnvrcDiff { changed, removed })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::fixture::TempRepo;
+    use crate::secrets::FileBackend;
+    use crate::terminal::profile::ProfileValue;
+
+    #[test]
+    fn test_parse_dotenv() {
+        let content = r#"
+# comment
+DATABASE_URL=postgres://localhost/app_dev
+export FEATURE_FLAGS="a,b"
+QUOTED='literal $HOME'
+ESCAPED="line1\nline2"
+MULTILINE="first
+second"
+INLINE=value # trailing comment
+HASH=abc#def
+not a var
+"#;
+        let vars = parse_dotenv(content);
+        assert_eq!(vars, vec![
+            ("DATABASE_URL".to_string(), "postgres://localhost/app_dev".to_string()),
+            ("FEATURE_FLAGS".to_string(), "a,b".to_string()),
+            ("QUOTED".to_string(), "literal $HOME".to_string()),
+            ("ESCAPED".to_string(), "line1\nline2".to_string()),
+            ("MULTILINE".to_string(), "first\nsecond".to_string()),
+            ("INLINE".to_string(), "value".to_string()),
+            ("HASH".to_string(), "abc#def".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_trust_state_tracks_content_changes() {
+        let dir = TempRepo::dir();
+        let path = dir.path().join(".env");
+        fs::write(&path, "A=1\n").unwrap();
+
+        let mut store = EnvTrustStore::default();
+        assert_eq!(store.state_for(&path, "A=1\n"), EnvTrustState::New);
+
+        store.decide(&path, true).unwrap();
+        assert_eq!(store.state_for(&path, "A=1\n"), EnvTrustState::Trusted);
+        assert_eq!(store.state_for(&path, "A=2\n"), EnvTrustState::Changed);
+
+        // The same contents elsewhere need their own decision
+        let moved = dir.path().join(".env.local");
+        fs::write(&moved, "A=1\n").unwrap();
+        assert_eq!(fingerprint(&path, "A=1\n").len(), 64);
+        assert_ne!(fingerprint(&moved, "A=1\n"), fingerprint(&path, "A=1\n"));
+
+        store.decide(&path, false).unwrap();
+        assert_eq!(store.state_for(&path, "A=1\n"), EnvTrustState::Denied);
+
+        // A corrupt store is an error, so nothing saves an empty one over it
+        let saved = dir.path().join("env-trust.json");
+        assert!(EnvTrustStore::read(&saved).unwrap().files.is_empty());
+        dir.write("env-trust.json", "{\"files\": {");
+        assert!(EnvTrustStore::read(&saved).is_err());
+    }
+
+    #[test]
+    fn test_envrc_layers_over_dotenv() {
+        let temp = TempRepo::dir();
+        let dir = temp.path();
+        fs::write(dir.join(".env"), "DATABASE_URL=postgres://localhost/dev\nFLAG=env\n").unwrap();
+        fs::write(dir.join(".envrc"), "export FLAG=envrc\nunset REMOVE_ME\n").unwrap();
+
+        let env_info = EnvironmentInfo {
+            shell: "bash".to_string(),
+            path_dirs: vec!["/usr/bin".to_string(), "/bin".to_string()],
+            env_vars: HashMap::from([
+                ("PATH".to_string(), "/usr/bin:/bin".to_string()),
+                ("REMOVE_ME".to_string(), "1".to_string()),
+            ]),
+            dev_tools: HashMap::new(),
+            tool_versions: HashMap::new(),
+            version_managers: Vec::new(),
+        };
+
+        let mut store = EnvTrustStore::default();
+        let untrusted = build_layered_env(&env_info, dir, &store);
+        assert_eq!(untrusted.pending_trust().len(), 2);
+        assert!(!untrusted.vars.contains_key("DATABASE_URL"));
+
+        store.decide(&dir.join(".env"), true).unwrap();
+        store.decide(&dir.join(".envrc"), true).unwrap();
+        let layered = build_layered_env(&env_info, dir, &store);
+
+        assert_eq!(layered.vars["DATABASE_URL"].source, EnvSource::Worktree);
+        assert_eq!(layered.vars["FLAG"].value.expose(), "envrc");
+        assert_eq!(layered.vars["FLAG"].overrides, vec![EnvSource::Worktree]);
+        assert!(!layered.vars.contains_key("REMOVE_ME"));
+
+        let profile = EnvProfile {
+            id: "kimi".to_string(),
+            name: "Kimi".to_string(),
+            description: None,
+            set: BTreeMap::from([
+                ("FLAG".to_string(), ProfileValue::Literal { value: "profile".to_string() }),
+                ("ANTHROPIC_AUTH_TOKEN".to_string(), ProfileValue::Secret { name: "kimi-token".to_string() }),
+            ]),
+            unset: vec!["DATABASE_URL".to_string()],
+        };
+        let vault = SecretVault::with_backend(
+            Box::new(FileBackend::new(dir.join("vault.json"))),
+            dir.join("vault-index.json"),
+        );
+        vault.set("kimi-token", SecretValue::new("sk-kimi"), None).unwrap();
+
+        // A secret that can't be resolved leaves the whole profile out, unsets included
+        let mut broken = profile.clone();
+        broken.set.insert("OTHER_TOKEN".to_string(), ProfileValue::Secret { name: "missing".to_string() });
+        let mut without_profile = build_layered_env(&env_info, dir, &store);
+        without_profile.apply_profile(&broken, &vault);
+        assert_eq!(without_profile.profile, None);
+        assert_eq!(without_profile.vars["FLAG"].value.expose(), "envrc");
+        assert!(without_profile.vars.contains_key("DATABASE_URL"));
+        assert_eq!(without_profile.warnings.len(), 1);
+
+        let mut with_profile = build_layered_env(&env_info, dir, &store);
+        with_profile.apply_profile(&profile, &vault);
+        assert_eq!(with_profile.profile.as_deref(), Some("kimi"));
+        assert_eq!(with_profile.vars["FLAG"].value.expose(), "profile");
+        assert_eq!(with_profile.vars["FLAG"].overrides, vec![EnvSource::Worktree, EnvSource::Envrc]);
+        assert!(!with_profile.vars.contains_key("DATABASE_URL"));
+        assert_eq!(with_profile.to_spawn_env()["ANTHROPIC_AUTH_TOKEN"], "sk-kimi");
+
+        // Secrets reach the spawned shell but never the serialized view
+        let serialized = serde_json::to_string(&with_profile).unwrap();
+        assert!(!serialized.contains("sk-kimi"));
+        assert!(with_profile.vars["ANTHROPIC_AUTH_TOKEN"].secret);
+    }
+
+    #[test]
+    fn test_failed_envrc_keeps_base_env() {
+        let dir = TempRepo::dir();
+        let base = HashMap::from([
+            ("PATH".to_string(), "/usr/bin:/bin".to_string()),
+            ("KEEP".to_string(), "1".to_string()),
+        ]);
+
+        assert!(evaluate_envrc(dir.path(), "export KEEP=2\nexit 0\n", &base).is_err());
+        assert!(evaluate_envrc(dir.path(), "unset KEEP\nexit 3\n", &base).is_err());
+
+        let diff = evaluate_envrc(dir.path(), "export KEEP=2\nfalse\n", &base).unwrap();
+        assert_eq!(diff.changed, vec![("KEEP".to_string(), "2".to_string())]);
+        assert!(diff.removed.is_empty());
+    }
+
+    #[test]
+    fn test_envrc_runs_only_the_checked_content() {
+        let dir = TempRepo::dir();
+        let base = HashMap::from([("PATH".to_string(), "/usr/bin:/bin".to_string())]);
+        // Edited after the trust check, and files the .envrc pulls in, never run
+        dir.write(".envrc", "export FLAG=edited\n");
+        dir.write("extra.sh", "export EXTRA=1\n");
+        dir.write(".env", "DOTENV=1\n");
+
+        let diff = evaluate_envrc(
+            dir.path(),
+            "export FLAG=checked\nsource_env extra.sh\nsource_env_if_exists extra.sh\ndotenv\n",
+            &base,
+        )
+        .unwrap();
+        assert_eq!(diff.changed, vec![("FLAG".to_string(), "checked".to_string())]);
+    }
+}